@@ -0,0 +1,316 @@
+use super::util;
+use std::fmt;
+use std::sync::OnceLock;
+
+/// Namespace used when no override is configured.
+const DEFAULT_BUS_ADDR_NAMESPACE: &str = "opensrf";
+
+/// Process-wide namespace override; see set_bus_namespace().
+static BUS_ADDR_NAMESPACE: OnceLock<String> = OnceLock::new();
+
+/// The top-level namespace shared by all bus-level addresses, which
+/// doubles as the Redis key prefix for their streams.
+pub fn bus_namespace() -> &'static str {
+    BUS_ADDR_NAMESPACE
+        .get()
+        .map(|ns| ns.as_str())
+        .unwrap_or(DEFAULT_BUS_ADDR_NAMESPACE)
+}
+
+/// Overrides the bus namespace so multiple OpenSRF environments
+/// (e.g. test and staging) can share one Redis instance without key
+/// collisions.
+///
+/// Normally applied from the "namespace" key of the message-bus
+/// configuration.  Must be called before any addresses are created
+/// and at most once per process.
+pub fn set_bus_namespace(namespace: &str) -> Result<(), String> {
+    BUS_ADDR_NAMESPACE
+        .set(namespace.to_string())
+        .map_err(|_| format!("Bus namespace is already set to '{}'", bus_namespace()))
+}
+
+/// Models a bus-level address providing access to the components
+/// of the address.
+///
+/// Examples:
+///
+/// * opensrf:service:$service
+/// * opensrf:client:$domain:$hostname:$pid:$random
+/// * opensrf:router:$domain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusAddress {
+    /// Full address string, recompiled as needed.
+    full: String,
+
+    /// "service", "client", or "router".
+    purpose: String,
+
+    remainder: String,
+}
+
+impl fmt::Display for BusAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "BusAddress={}", &self.full)
+    }
+}
+
+impl BusAddress {
+    /// Parses a full address string into a BusAddress.
+    pub fn new_from_string(full: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = full.splitn(3, ':').collect();
+
+        if parts.len() < 3 || parts[0] != bus_namespace() {
+            return Err(format!("Invalid bus address: {full}"));
+        }
+
+        Ok(BusAddress {
+            full: full.to_string(),
+            purpose: parts[1].to_string(),
+            remainder: parts[2].to_string(),
+        })
+    }
+
+    pub fn full(&self) -> &str {
+        &self.full
+    }
+
+    pub fn purpose(&self) -> &str {
+        &self.purpose
+    }
+
+    pub fn remainder(&self) -> &str {
+        &self.remainder
+    }
+
+    pub fn is_client(&self) -> bool {
+        self.purpose == "client"
+    }
+
+    pub fn is_service(&self) -> bool {
+        self.purpose == "service"
+    }
+
+    pub fn is_router(&self) -> bool {
+        self.purpose == "router"
+    }
+}
+
+/// Address for a specific client connection, unique per Bus instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientAddress {
+    addr: BusAddress,
+    domain: String,
+}
+
+impl fmt::Display for ClientAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.full())
+    }
+}
+
+impl ClientAddress {
+    /// Generates a new unique client address for the provided domain.
+    pub fn new(domain: &str) -> Self {
+        let full = format!(
+            "{}:client:{}:{}:{}:{}",
+            bus_namespace(),
+            domain,
+            gethostname::gethostname().into_string().unwrap_or_else(|_| "localhost".to_string()),
+            std::process::id(),
+            &util::random_number(8),
+        );
+
+        ClientAddress {
+            addr: BusAddress::new_from_string(&full).unwrap(),
+            domain: domain.to_string(),
+        }
+    }
+
+    pub fn from_string(full: &str) -> Result<Self, String> {
+        let addr = BusAddress::new_from_string(full)?;
+
+        if !addr.is_client() {
+            return Err(format!("Not a client address: {full}"));
+        }
+
+        let domain = match addr.remainder().split(':').next() {
+            Some(d) => d.to_string(),
+            None => return Err(format!("Client address has no domain: {full}")),
+        };
+
+        Ok(ClientAddress { addr, domain })
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// The hostname component of the address.
+    pub fn hostname(&self) -> Option<&str> {
+        self.addr.remainder().split(':').nth(1)
+    }
+
+    /// The process id component of the address.
+    pub fn pid(&self) -> Option<u32> {
+        self.addr
+            .remainder()
+            .split(':')
+            .nth(2)
+            .and_then(|p| p.parse().ok())
+    }
+
+    pub fn addr(&self) -> &BusAddress {
+        &self.addr
+    }
+
+    pub fn full(&self) -> &str {
+        self.addr.full()
+    }
+}
+
+/// Address for a service, shared by all listening workers of the
+/// service regardless of domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceAddress {
+    addr: BusAddress,
+    service: String,
+}
+
+impl fmt::Display for ServiceAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.full())
+    }
+}
+
+impl ServiceAddress {
+    pub fn new(service: &str) -> Self {
+        let full = format!("{}:service:{service}", bus_namespace());
+        ServiceAddress {
+            addr: BusAddress::new_from_string(&full).unwrap(),
+            service: service.to_string(),
+        }
+    }
+
+    /// Address of the service's priority lane: a separate stream
+    /// where high-priority requests land, polled by workers ahead
+    /// of the main service stream.
+    pub fn new_priority(service: &str) -> Self {
+        let full = format!("{}:service:{service}:priority", bus_namespace());
+        ServiceAddress {
+            addr: BusAddress::new_from_string(&full).unwrap(),
+            service: service.to_string(),
+        }
+    }
+
+    pub fn from_string(full: &str) -> Result<Self, String> {
+        let addr = BusAddress::new_from_string(full)?;
+
+        if !addr.is_service() {
+            return Err(format!("Not a service address: {full}"));
+        }
+
+        let service = addr.remainder().to_string();
+
+        Ok(ServiceAddress { addr, service })
+    }
+
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    pub fn addr(&self) -> &BusAddress {
+        &self.addr
+    }
+
+    pub fn full(&self) -> &str {
+        self.addr.full()
+    }
+}
+
+/// Address for the router listening on a domain.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouterAddress {
+    addr: BusAddress,
+    domain: String,
+}
+
+impl fmt::Display for RouterAddress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.full())
+    }
+}
+
+impl RouterAddress {
+    pub fn new(domain: &str) -> Self {
+        let full = format!("{}:router:{domain}", bus_namespace());
+        RouterAddress {
+            addr: BusAddress::new_from_string(&full).unwrap(),
+            domain: domain.to_string(),
+        }
+    }
+
+    pub fn from_string(full: &str) -> Result<Self, String> {
+        let addr = BusAddress::new_from_string(full)?;
+
+        if !addr.is_router() {
+            return Err(format!("Not a router address: {full}"));
+        }
+
+        let domain = addr.remainder().to_string();
+
+        Ok(RouterAddress { addr, domain })
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn addr(&self) -> &BusAddress {
+        &self.addr
+    }
+
+    pub fn full(&self) -> &str {
+        self.addr.full()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_address() {
+        let addr = ClientAddress::new("private.localhost");
+        assert_eq!(addr.domain(), "private.localhost");
+        assert!(addr.full().starts_with("opensrf:client:private.localhost:"));
+
+        let addr2 = ClientAddress::from_string(addr.full()).unwrap();
+        assert_eq!(addr2.full(), addr.full());
+        assert_eq!(addr2.pid(), Some(std::process::id()));
+        assert!(addr2.hostname().is_some());
+    }
+
+    #[test]
+    fn test_service_address() {
+        let addr = ServiceAddress::new("opensrf.settings");
+        assert_eq!(addr.full(), "opensrf:service:opensrf.settings");
+        assert_eq!(addr.service(), "opensrf.settings");
+
+        let addr = ServiceAddress::new_priority("opensrf.settings");
+        assert_eq!(addr.full(), "opensrf:service:opensrf.settings:priority");
+        assert_eq!(addr.service(), "opensrf.settings");
+    }
+
+    #[test]
+    fn test_router_address() {
+        let addr = RouterAddress::new("public.localhost");
+        assert_eq!(addr.full(), "opensrf:router:public.localhost");
+    }
+
+    #[test]
+    fn test_bad_address() {
+        assert!(BusAddress::new_from_string("not-an-address").is_err());
+        assert!(ServiceAddress::from_string("opensrf:client:x:y:1:2").is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use super::client::Client;
+use super::conf;
+use super::method;
+use std::any::Any;
+use std::sync::Arc;
+
+/// Function that generates ApplicationWorker implementers.
+///
+/// This is how an Application gives the server new workers.
+pub type ApplicationWorkerFactory = fn() -> Box<dyn ApplicationWorker>;
+
+/// A published OpenSRF service.
+pub trait Application {
+    /// Application service name, e.g. opensrf.settings.
+    fn name(&self) -> &str;
+
+    /// Tell the server what methods this application exposes.
+    ///
+    /// Called before workers are spawned.
+    fn register_methods(
+        &self,
+        client: Client,
+        config: Arc<conf::Config>,
+    ) -> Result<Vec<method::Method>, String>;
+
+    /// Service-level startup hook, called once before the service
+    /// registers with any router -- a place for cache warming,
+    /// database pools, and the like.
+    fn init(&mut self, _client: Client, _config: Arc<conf::Config>) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Service-level teardown hook, called once during graceful
+    /// stop after the workers have finished.
+    fn shutdown(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Returns the function the server calls to create new workers.
+    fn worker_factory(&self) -> ApplicationWorkerFactory;
+}
+
+/// Per-worker-thread state for an Application.
+pub trait ApplicationWorker: Any {
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    /// Passes the worker its baseline runtime environment.
+    ///
+    /// Called after the worker thread is spawned but before any
+    /// requests are processed.
+    fn absorb_env(
+        &mut self,
+        client: Client,
+        config: Arc<conf::Config>,
+        methods: Arc<Vec<method::Method>>,
+    ) -> Result<(), String>;
+
+    /// Called once per worker thread shortly after spawn.
+    fn worker_start(&mut self) -> Result<(), String>;
+
+    /// Called once per worker thread shortly before exit.
+    fn worker_end(&mut self) -> Result<(), String>;
+
+    /// Called when a conversation begins with this worker, before
+    /// its first request is dispatched.
+    fn start_session(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called when a conversation ends, cleanly or otherwise -- a
+    /// place to release per-conversation state like database
+    /// transactions.
+    fn end_session(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called before each method call is dispatched; an Err here
+    /// fails the request without running its handler.
+    fn before_request(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called after each method call, whether it succeeded or
+    /// failed.
+    fn after_request(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
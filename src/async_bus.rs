@@ -0,0 +1,307 @@
+//! Async variant of bus::Bus built on redis::aio.
+//!
+//! Foundational for an async client and async gateway binaries;
+//! the sync Bus remains the primary API.  Feature parity is
+//! intentionally narrow for now: single-node connections only (no
+//! sentinel or cluster), no read batching, and no reliable-delivery
+//! mode.
+//!
+//! Each AsyncBus holds its own multiplexed connection, so a
+//! blocking stream read only stalls this bus, not unrelated
+//! commands.
+use super::addr::ClientAddress;
+use super::bus::Bus;
+use super::conf;
+use super::message::TransportMessage;
+use log::{debug, trace};
+use redis::aio::MultiplexedConnection;
+use redis::streams::{StreamMaxlen, StreamReadOptions, StreamReadReply};
+use redis::AsyncCommands;
+use std::fmt;
+use std::time::Duration;
+
+/// Manages an async Redis connection for a single bus participant.
+///
+/// Mirrors the API surface of bus::Bus, with send/recv returning
+/// futures.  There is no async Drop, so callers should invoke
+/// disconnect() before letting an AsyncBus go out of scope.
+pub struct AsyncBus {
+    connection: MultiplexedConnection,
+
+    /// Our unique bus address.
+    address: ClientAddress,
+
+    /// Domain, i.e. bus node name, we're connected to.
+    domain: String,
+
+    /// How destination streams are trimmed when we add messages.
+    trim_policy: conf::TrimPolicy,
+}
+
+impl fmt::Display for AsyncBus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AsyncBus {}", self.address.full())
+    }
+}
+
+impl AsyncBus {
+    pub async fn new(config: &conf::BusConnection) -> Result<Self, String> {
+        let connection = AsyncBus::connect(config).await?;
+
+        let address = ClientAddress::new(config.domain());
+
+        let mut bus = AsyncBus {
+            connection,
+            address,
+            domain: config.domain().to_string(),
+            trim_policy: config.node().trim_policy(),
+        };
+
+        bus.setup_stream(None).await?;
+
+        Ok(bus)
+    }
+
+    /// Opens a connection to the node, trying each of its configured
+    /// addresses in order.
+    async fn connect(config: &conf::BusConnection) -> Result<MultiplexedConnection, String> {
+        let mut last_err = format!("No addresses for node {}", config.node().name());
+
+        for address in config.node().addresses() {
+            debug!("Attempting async bus connection to {address}");
+
+            let info = Bus::connection_info(config, &address);
+
+            let client = match Bus::open_client(config, info) {
+                Ok(c) => c,
+                Err(e) => {
+                    last_err = format!("Cannot open connection to {address}: {e}");
+                    continue;
+                }
+            };
+
+            match client.get_multiplexed_async_connection().await {
+                Ok(c) => return Ok(c),
+                Err(e) => {
+                    last_err = format!("Bus connect error for {address}: {e}");
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+
+    pub fn address(&self) -> &ClientAddress {
+        &self.address
+    }
+
+    pub fn set_address(&mut self, address: &ClientAddress) {
+        self.address = address.clone();
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Creates the stream and consumer group for the provided stream
+    /// name, defaulting to our bus address.
+    pub async fn setup_stream(&mut self, name: Option<&str>) -> Result<(), String> {
+        let sname = name.unwrap_or(self.address.full()).to_string();
+
+        debug!("{self} setting up stream={sname}");
+
+        let created: Result<(), _> = redis::cmd("XGROUP")
+            .arg("CREATE")
+            .arg(&sname)
+            .arg(&sname) // group name == stream name
+            .arg("$")
+            .arg("MKSTREAM")
+            .query_async(&mut self.connection)
+            .await;
+
+        if let Err(e) = created {
+            if !e.to_string().contains("BUSYGROUP") {
+                return Err(format!("Error creating stream={sname}: {e}"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns at most one JSON value pulled from the stream.
+    ///
+    /// Same timeout semantics as bus::Bus: zero returns immediately
+    /// if no messages are available, Duration::MAX blocks
+    /// indefinitely.
+    pub async fn recv_json_value(
+        &mut self,
+        timeout: Duration,
+        stream: Option<&str>,
+    ) -> Result<Option<json::JsonValue>, String> {
+        let stream = stream.unwrap_or(self.address.full()).to_string();
+
+        trace!("{self} recv_json_value() timeout={timeout:?} stream={stream}");
+
+        let mut read_opts = StreamReadOptions::default()
+            .count(1)
+            .noack()
+            .group(&stream, &stream);
+
+        if !timeout.is_zero() {
+            if timeout == Duration::MAX {
+                // block() of 0 means block indefinitely.
+                read_opts = read_opts.block(0);
+            } else {
+                // BLOCK 0 means forever, so floor at one ms.
+                read_opts = read_opts.block(std::cmp::max(timeout.as_millis() as usize, 1));
+            }
+        }
+
+        let reply: StreamReadReply = match self
+            .connection
+            .xread_options(&[&stream], &[">"], &read_opts)
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => return Err(format!("{self} recv error: {e}")),
+        };
+
+        for stream_key in reply.keys {
+            for entry in stream_key.ids {
+                if let Some(redis::Value::Data(bytes)) = entry.map.get("message") {
+                    let json_string = match String::from_utf8(bytes.to_vec()) {
+                        Ok(s) => s,
+                        Err(e) => return Err(format!("{self} received non-utf8 data: {e}")),
+                    };
+
+                    trace!("{self} read json: {json_string}");
+
+                    return match json::parse(&json_string) {
+                        Ok(json_val) => Ok(Some(json_val)),
+                        Err(e) => Err(format!(
+                            "{self} received unparseable JSON: {e} : {json_string}"
+                        )),
+                    };
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns at most one TransportMessage pulled from the stream.
+    pub async fn recv(
+        &mut self,
+        timeout: Duration,
+        stream: Option<&str>,
+    ) -> Result<Option<TransportMessage>, String> {
+        let json_op = self.recv_json_value(timeout, stream).await?;
+
+        match json_op {
+            Some(json_val) => match TransportMessage::from_json_value(json_val) {
+                Some(msg) => Ok(Some(msg)),
+                None => Err(format!("{self} received malformed TransportMessage")),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Sends a TransportMessage to its "to" address.
+    pub async fn send(&mut self, msg: &TransportMessage) -> Result<(), String> {
+        self.send_to(msg, msg.to()).await
+    }
+
+    /// Sends a TransportMessage to the provided stream, regardless
+    /// of its "to" address.
+    pub async fn send_to(&mut self, msg: &TransportMessage, recipient: &str) -> Result<(), String> {
+        let json_str = msg.to_json_value().dump();
+
+        trace!("{self} sending to={recipient}: {json_str}");
+
+        // Stamp the send time so receivers can discard messages
+        // past the max message age.
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+            .to_string();
+
+        let fields = [("message", json_str.as_str()), ("ts", ts.as_str())];
+
+        let res: Result<String, _> = match self.trim_policy {
+            conf::TrimPolicy::MaxlenApprox(n) => {
+                self.connection
+                    .xadd_maxlen(recipient, StreamMaxlen::Approx(n), "*", &fields)
+                    .await
+            }
+            conf::TrimPolicy::MaxlenExact(n) => {
+                self.connection
+                    .xadd_maxlen(recipient, StreamMaxlen::Equals(n), "*", &fields)
+                    .await
+            }
+            conf::TrimPolicy::MaxAge(secs) => {
+                // Entry ids are millisecond timestamps; trim
+                // everything older than the cutoff.
+                let cutoff = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0)
+                    .saturating_sub(secs * 1000);
+
+                redis::cmd("XADD")
+                    .arg(recipient)
+                    .arg("MINID")
+                    .arg("~")
+                    .arg(cutoff)
+                    .arg("*")
+                    .arg("message")
+                    .arg(&json_str)
+                    .arg("ts")
+                    .arg(&ts)
+                    .query_async(&mut self.connection)
+                    .await
+            }
+        };
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Error in send() {e}")),
+        }
+    }
+
+    /// Removes all pending entries from our stream.
+    pub async fn clear_stream(&mut self) -> Result<(), String> {
+        let sname = self.address.full().to_string();
+
+        let res: Result<i32, _> = self
+            .connection
+            .xtrim(&sname, StreamMaxlen::Equals(0))
+            .await;
+
+        if let Err(e) = res {
+            return Err(format!("Error in clear_stream(): {e}"));
+        }
+
+        Ok(())
+    }
+
+    /// Removes our stream and its consumer group entirely.
+    pub async fn delete_stream(&mut self) -> Result<(), String> {
+        let sname = self.address.full().to_string();
+
+        debug!("{self} deleting stream");
+
+        let res: Result<i32, _> = self.connection.del(&sname).await;
+
+        if let Err(e) = res {
+            return Err(format!("Error in delete_stream(): {e}"));
+        }
+
+        Ok(())
+    }
+
+    /// Removes our stream, dropping any unprocessed messages.
+    pub async fn disconnect(&mut self) -> Result<(), String> {
+        self.delete_stream().await
+    }
+}
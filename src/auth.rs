@@ -0,0 +1,193 @@
+//! Convenience helpers for the Evergreen open-ils.auth service.
+//!
+//! Wraps login / session retrieval / touch and caches the auth
+//! token so Evergreen-facing tools don't have to reimplement the
+//! same handful of calls.
+use super::client::Client;
+use super::session::{Request, DEFAULT_REQUEST_TIMEOUT};
+use json::JsonValue;
+
+const AUTH_SERVICE: &str = "open-ils.auth";
+
+/// Parameters for an open-ils.auth login call.
+pub struct AuthLoginArgs {
+    username: String,
+    password: String,
+    login_type: String,
+    workstation: Option<String>,
+}
+
+impl AuthLoginArgs {
+    pub fn new(username: &str, password: &str, login_type: &str) -> AuthLoginArgs {
+        AuthLoginArgs {
+            username: username.to_string(),
+            password: password.to_string(),
+            login_type: login_type.to_string(),
+            workstation: None,
+        }
+    }
+
+    pub fn set_workstation(&mut self, workstation: &str) {
+        self.workstation = Some(workstation.to_string());
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        let mut obj = json::object! {
+            username: self.username.as_str(),
+            password: self.password.as_str(),
+            type: self.login_type.as_str(),
+        };
+
+        if let Some(w) = self.workstation.as_deref() {
+            obj.insert("workstation", w).ok();
+        }
+
+        obj
+    }
+}
+
+/// Client wrapper that caches an auth token and injects it into
+/// authtoken-style API calls.
+pub struct AuthClient {
+    client: Client,
+    token: Option<String>,
+
+    /// Session lifetime in seconds, as reported at login.
+    authtime: Option<usize>,
+}
+
+impl AuthClient {
+    pub fn new(client: Client) -> AuthClient {
+        AuthClient {
+            client,
+            token: None,
+            authtime: None,
+        }
+    }
+
+    /// Adopts an existing auth token, e.g. one handed to a CGI
+    /// script by its caller.
+    pub fn from_token(client: Client, token: &str) -> AuthClient {
+        AuthClient {
+            client,
+            token: Some(token.to_string()),
+            authtime: None,
+        }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
+    pub fn authtime(&self) -> Option<usize> {
+        self.authtime
+    }
+
+    fn require_token(&self) -> Result<&str, String> {
+        self.token
+            .as_deref()
+            .ok_or_else(|| "AuthClient has no auth token; login first".to_string())
+    }
+
+    /// Logs in, caching the resulting auth token.
+    pub fn login(&mut self, args: &AuthLoginArgs) -> Result<&str, String> {
+        let ses = self.client.session(AUTH_SERVICE);
+
+        let mut req = ses.request("open-ils.auth.login", vec![args.to_json_value()])?;
+
+        let evt = req
+            .recv(DEFAULT_REQUEST_TIMEOUT)?
+            .ok_or("No response to login call")?;
+
+        if evt["textcode"].as_str() != Some("SUCCESS") {
+            return Err(format!("Login failed: {}", evt["textcode"]));
+        }
+
+        let token = evt["payload"]["authtoken"]
+            .as_str()
+            .ok_or("Login succeeded but no authtoken returned")?
+            .to_string();
+
+        self.authtime = evt["payload"]["authtime"].as_usize();
+        self.token = Some(token);
+
+        Ok(self.token.as_deref().unwrap())
+    }
+
+    /// Fetches the user object tied to our auth session.
+    pub fn session_retrieve(&self) -> Result<JsonValue, String> {
+        let token = self.require_token()?.to_string();
+
+        let ses = self.client.session(AUTH_SERVICE);
+        let mut req = ses.request("open-ils.auth.session.retrieve", vec![token])?;
+
+        req.recv(DEFAULT_REQUEST_TIMEOUT)?
+            .ok_or_else(|| "No response to session.retrieve".to_string())
+    }
+
+    /// Extends the lifetime of our auth session.
+    pub fn session_touch(&self) -> Result<(), String> {
+        let token = self.require_token()?.to_string();
+
+        let ses = self.client.session(AUTH_SERVICE);
+        let mut req = ses.request("open-ils.auth.session.touch", vec![token])?;
+
+        req.recv(DEFAULT_REQUEST_TIMEOUT)?;
+
+        Ok(())
+    }
+
+    /// Deletes our auth session and discards the cached token.
+    pub fn logout(&mut self) -> Result<(), String> {
+        let token = self.require_token()?.to_string();
+
+        let ses = self.client.session(AUTH_SERVICE);
+        let mut req = ses.request("open-ils.auth.session.delete", vec![token])?;
+
+        req.recv(DEFAULT_REQUEST_TIMEOUT)?;
+
+        self.token = None;
+        self.authtime = None;
+
+        Ok(())
+    }
+
+    /// Issues a request with the cached auth token injected as the
+    /// first parameter, the common calling convention for
+    /// authtoken-protected Evergreen APIs.
+    pub fn authtoken_request(
+        &self,
+        service: &str,
+        method: &str,
+        mut params: Vec<JsonValue>,
+    ) -> Result<Request, String> {
+        let token = self.require_token()?;
+
+        params.insert(0, json::from(token));
+
+        let ses = self.client.session(service);
+        ses.request(method, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_login_args() {
+        let mut args = AuthLoginArgs::new("admin", "demo123", "temp");
+
+        let obj = args.to_json_value();
+        assert_eq!(obj["username"], "admin");
+        assert_eq!(obj["type"], "temp");
+        assert!(!obj.has_key("workstation"));
+
+        args.set_workstation("BR1-circ");
+        assert_eq!(args.to_json_value()["workstation"], "BR1-circ");
+    }
+}
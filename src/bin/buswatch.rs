@@ -0,0 +1,118 @@
+//! Watches the message bus for stale streams and unbounded growth,
+//! applying a TTL to every opensrf key so crashed or vanished
+//! processes cannot leave data behind forever.
+//!
+//! The bus connection is pulled from the standard configuration
+//! file.  Bus username and password may be overridden on the
+//! command line (--bus-username/--bus-password) or environment
+//! (OSRF_BUS_USERNAME/OSRF_BUS_PASSWORD) for running with an
+//! alternate (e.g. admin) account.
+use log::{debug, info, warn};
+use opensrf::addr;
+use opensrf::bus::Bus;
+use opensrf::init;
+use std::thread;
+use std::time::Duration;
+
+/// How often we scan the bus.
+const WATCH_INTERVAL: u64 = 30;
+
+/// Expire time applied to every opensrf key.  Any key that sees no
+/// activity for this long is removed by Redis.
+const DEFAULT_KEY_EXPIRE_SECS: i64 = 1800;
+
+/// Log a warning for any stream holding at least this many entries.
+const DEPTH_WARN_THRESHOLD: i32 = 500;
+
+struct BusWatch {
+    bus: Bus,
+    wait_time: u64,
+    key_expire_secs: i64,
+
+    /// When set, stream entries older than this are trimmed on
+    /// every pass, enforcing the node's max message age even for
+    /// streams whose consumers have vanished.
+    max_message_age: Option<Duration>,
+}
+
+impl BusWatch {
+    fn watch(&mut self) {
+        loop {
+            thread::sleep(Duration::from_secs(self.wait_time));
+
+            if let Err(e) = self.watch_one() {
+                warn!("buswatch error: {e}");
+            }
+        }
+    }
+
+    fn watch_one(&mut self) -> Result<(), String> {
+        let keys = self.bus.keys(&format!("{}:*", addr::bus_namespace()))?;
+
+        debug!("buswatch scanning {} keys", keys.len());
+
+        for key in keys.iter() {
+            let depth = match self.bus.xlen(key) {
+                Ok(d) => d,
+                // Not all opensrf keys are streams.
+                Err(_) => continue,
+            };
+
+            if depth >= DEPTH_WARN_THRESHOLD {
+                match self.bus.stream_info(key) {
+                    Ok(info) => warn!(
+                        "buswatch: stream {key} holds {depth} entries; \
+                        consumers={} pending={} lag={}",
+                        info.consumer_count(),
+                        info.pending(),
+                        info.lag(),
+                    ),
+                    Err(_) => warn!("buswatch: stream {key} holds {depth} entries"),
+                }
+            }
+
+            if let Some(age) = self.max_message_age {
+                match self.bus.trim_stream_older_than(key, age) {
+                    Ok(count) if count > 0 => {
+                        info!("buswatch: trimmed {count} stale entries from {key}");
+                    }
+                    Ok(_) => {}
+                    Err(e) => warn!("buswatch: cannot trim {key}: {e}"),
+                }
+            }
+
+            // Refresh the TTL on every pass.  Busy streams will
+            // never expire; abandoned ones eventually vanish.
+            self.bus.set_key_timeout(key, self.key_expire_secs)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn main() {
+    let (mut config, _params) = init::init().expect("Error in buswatch init");
+
+    let domain = config.nodes()[0].name().to_string();
+
+    config
+        .set_primary_connection("client", &domain)
+        .expect("buswatch requires a 'client' connection");
+
+    let con = config.primary_connection().unwrap();
+
+    let max_message_age = con.node().max_message_age();
+
+    let bus = Bus::new(con).expect("Cannot connect to bus");
+
+    let mut watcher = BusWatch {
+        bus,
+        wait_time: WATCH_INTERVAL,
+        key_expire_secs: DEFAULT_KEY_EXPIRE_SECS,
+        max_message_age,
+    };
+
+    info!("buswatch starting on domain {domain}");
+
+    watcher.watch();
+}
@@ -0,0 +1,138 @@
+//! Demo OpenSRF service for exercising the client and server.
+use json::JsonValue;
+use opensrf::app::{Application, ApplicationWorker, ApplicationWorkerFactory};
+use opensrf::client::Client;
+use opensrf::conf;
+use opensrf::init;
+use opensrf::message;
+use opensrf::method::{Method, MethodHandler, ParamCount};
+use opensrf::server::Server;
+use opensrf::session::ServerSession;
+use std::any::Any;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const APPNAME: &str = "opensrf.rsdemo";
+
+struct RsDemoApplication;
+
+impl Application for RsDemoApplication {
+    fn name(&self) -> &str {
+        APPNAME
+    }
+
+    fn register_methods(
+        &self,
+        _client: Client,
+        _config: Arc<conf::Config>,
+    ) -> Result<Vec<Method>, String> {
+        Ok(vec![
+            Method {
+                name: "opensrf.rsdemo.echo",
+                param_count: ParamCount::Any,
+                handler: echo as MethodHandler,
+                max_runtime: None,
+            },
+            Method {
+                name: "opensrf.rsdemo.sleep",
+                param_count: ParamCount::Range(0, 1),
+                handler: sleep as MethodHandler,
+                max_runtime: None,
+            },
+        ])
+    }
+
+    fn worker_factory(&self) -> ApplicationWorkerFactory {
+        || Box::new(RsDemoWorker { request_count: 0 })
+    }
+}
+
+struct RsDemoWorker {
+    request_count: usize,
+}
+
+impl ApplicationWorker for RsDemoWorker {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn absorb_env(
+        &mut self,
+        _client: Client,
+        _config: Arc<conf::Config>,
+        _methods: Arc<Vec<Method>>,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn worker_start(&mut self) -> Result<(), String> {
+        log::debug!("RsDemoWorker starting");
+        Ok(())
+    }
+
+    fn worker_end(&mut self) -> Result<(), String> {
+        log::debug!(
+            "RsDemoWorker exiting after {} requests",
+            self.request_count
+        );
+        Ok(())
+    }
+}
+
+/// Responds once per parameter with a copy of said parameter.
+fn echo(
+    worker: &mut Box<dyn ApplicationWorker>,
+    session: &mut ServerSession,
+    method: &message::Method,
+) -> Result<(), String> {
+    let worker = worker
+        .as_any_mut()
+        .downcast_mut::<RsDemoWorker>()
+        .ok_or("Could not downcast ApplicationWorker")?;
+
+    worker.request_count += 1;
+
+    for param in method.params() {
+        session.respond(param.clone())?;
+    }
+
+    Ok(())
+}
+
+/// Sleeps for the requested number of seconds (max 10, default 1)
+/// then responds with the slept duration.
+fn sleep(
+    worker: &mut Box<dyn ApplicationWorker>,
+    session: &mut ServerSession,
+    method: &message::Method,
+) -> Result<(), String> {
+    let worker = worker
+        .as_any_mut()
+        .downcast_mut::<RsDemoWorker>()
+        .ok_or("Could not downcast ApplicationWorker")?;
+
+    worker.request_count += 1;
+
+    let mut secs = 1;
+    if let Some(s) = method.params().first().and_then(|p| p.as_usize()) {
+        secs = std::cmp::min(s, 10);
+    }
+
+    thread::sleep(Duration::from_secs(secs as u64));
+
+    session.respond(JsonValue::from(secs))
+}
+
+fn main() {
+    let (mut config, _params) = init::init().expect("Error in demo init");
+
+    let domain = config.nodes()[0].name().to_string();
+
+    config
+        .set_primary_connection("service", &domain)
+        .expect("Demo service requires a 'service' connection");
+
+    Server::start(Box::new(RsDemoApplication), config.into_shared())
+        .expect("Error starting server");
+}
@@ -0,0 +1,550 @@
+use log::{debug, error, info, warn};
+use opensrf::addr::{ClientAddress, RouterAddress, ServiceAddress};
+use opensrf::bus::Bus;
+use opensrf::conf;
+use opensrf::init;
+use opensrf::message::{Message, MessageStatus, MessageType, Payload, Status, TransportMessage};
+use std::collections::hash_map::DefaultHasher;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+/// How long the router blocks on its stream before waking to drain
+/// the delivery schedule.
+const SCHEDULE_POLL_TIME: Duration = Duration::from_secs(5);
+
+/// A single worker process (listener) registered for a service.
+struct ServiceInstance {
+    address: ClientAddress,
+    register_time: std::time::SystemTime,
+}
+
+impl ServiceInstance {
+    fn address(&self) -> &ClientAddress {
+        &self.address
+    }
+
+    fn to_json_value(&self) -> json::JsonValue {
+        json::object! {
+            address: self.address().full(),
+            register_time: match self.register_time.duration_since(std::time::UNIX_EPOCH) {
+                Ok(d) => d.as_secs(),
+                Err(_) => 0,
+            },
+        }
+    }
+}
+
+/// A service with its registered controllers (listeners).
+struct ServiceEntry {
+    name: String,
+    controllers: Vec<ServiceInstance>,
+}
+
+impl ServiceEntry {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn controllers(&self) -> &Vec<ServiceInstance> {
+        &self.controllers
+    }
+
+    fn remove_controller(&mut self, address: &ClientAddress) {
+        if let Some(pos) = self
+            .controllers
+            .iter()
+            .position(|c| c.address().full() == address.full())
+        {
+            debug!(
+                "Removing controller for service={} address={}",
+                self.name, address
+            );
+            self.controllers.remove(pos);
+        }
+    }
+
+    fn to_json_value(&self) -> json::JsonValue {
+        json::object! {
+            name: self.name(),
+            controllers: self.controllers()
+                .iter()
+                .map(|c| c.to_json_value())
+                .collect::<Vec<json::JsonValue>>(),
+        }
+    }
+}
+
+/// Tracks service registrations on one domain and routes messages
+/// to registered services.
+struct Router {
+    bus: Bus,
+    domain: String,
+    config: conf::Config,
+    services: Vec<ServiceEntry>,
+
+    /// Clients that asked to be told when a service registers or
+    /// unregisters, as (service, client address) pairs.
+    watchers: Vec<(String, ClientAddress)>,
+}
+
+impl fmt::Display for Router {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Router({})", self.domain)
+    }
+}
+
+impl Router {
+    fn new(config: &conf::Config, domain: &str) -> Result<Router, String> {
+        let con = config.new_bus_connection("router", domain)?;
+
+        let bus = Bus::new(&con)?;
+
+        let mut router = Router {
+            bus,
+            domain: domain.to_string(),
+            config: config.clone(),
+            services: Vec::new(),
+            watchers: Vec::new(),
+        };
+
+        router.setup_router_stream()?;
+
+        Ok(router)
+    }
+
+    /// The router listens on the well-known router address for its
+    /// domain instead of a generated client address.
+    fn setup_router_stream(&mut self) -> Result<(), String> {
+        let addr = RouterAddress::new(&self.domain);
+        self.bus.setup_stream(Some(addr.full()))
+    }
+
+    fn listen_address(&self) -> String {
+        RouterAddress::new(&self.domain).full().to_string()
+    }
+
+    fn listen(&mut self) {
+        let stream = self.listen_address();
+
+        loop {
+            // Wake periodically to drain the delivery schedule even
+            // when no router traffic is arriving.
+            let tmsg = match self.bus.recv(SCHEDULE_POLL_TIME, Some(&stream)) {
+                Ok(Some(tmsg)) => Some(tmsg),
+                Ok(None) => None,
+                Err(e) => {
+                    error!("{self} error receiving: {e}");
+                    continue;
+                }
+            };
+
+            if let Some(tmsg) = tmsg {
+                if let Err(e) = self.handle_message(tmsg) {
+                    error!("{self} error handling message: {e}");
+                }
+            }
+
+            if let Err(e) = self.drain_schedule() {
+                error!("{self} error draining schedule: {e}");
+            }
+        }
+    }
+
+    /// Delivers any scheduled (send_after) messages that have come
+    /// due.
+    fn drain_schedule(&mut self) -> Result<(), String> {
+        for tmsg in self.bus.drain_schedule()? {
+            debug!("{self} delivering scheduled message to {}", tmsg.to());
+
+            let result = if ServiceAddress::from_string(tmsg.to()).is_ok() {
+                self.route_message(tmsg)
+            } else {
+                self.bus.send(&tmsg)
+            };
+
+            if let Err(e) = result {
+                error!("{self} cannot deliver scheduled message: {e}");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_message(&mut self, tmsg: TransportMessage) -> Result<(), String> {
+        if let Some(command) = tmsg.router_command() {
+            return self.handle_router_command(command.to_string(), &tmsg);
+        }
+
+        self.route_message(tmsg)
+    }
+
+    fn handle_router_command(
+        &mut self,
+        command: String,
+        tmsg: &TransportMessage,
+    ) -> Result<(), String> {
+        let sender = ClientAddress::from_string(tmsg.from())?;
+
+        debug!("{self} command={command} from={sender}");
+
+        match command.as_str() {
+            "register" => {
+                let service = tmsg
+                    .router_class()
+                    .ok_or("register command requires a service")?;
+                self.handle_register(sender, service.to_string())
+            }
+
+            "unregister" => {
+                let service = tmsg
+                    .router_class()
+                    .ok_or("unregister command requires a service")?;
+                self.handle_unregister(&sender, service.to_string())
+            }
+
+            "drain" => {
+                let service = tmsg
+                    .router_class()
+                    .ok_or("drain command requires a service")?;
+                let address = tmsg
+                    .router_argument()
+                    .ok_or("drain command requires a target address")?;
+                self.handle_drain(service.to_string(), address.to_string())
+            }
+
+            "watch" => {
+                let service = tmsg
+                    .router_class()
+                    .ok_or("watch command requires a service")?;
+                self.handle_watch(sender, service.to_string())
+            }
+
+            "summarize" => {
+                let summary = self.summarize();
+                let mut reply =
+                    TransportMessage::new(tmsg.from(), &self.listen_address(), tmsg.thread());
+                reply.set_router_reply(&summary.dump());
+                self.bus.send(&reply)
+            }
+
+            _ => Err(format!("{self} unknown router command: {command}")),
+        }
+    }
+
+    /// Registers a controller for a service.
+    ///
+    /// The service may be a wildcard prefix (e.g. "open-ils.fielder.*")
+    /// so one process can shim a whole family of services; see
+    /// route_message() for the matching rules.
+    fn handle_register(&mut self, address: ClientAddress, service: String) -> Result<(), String> {
+        info!("{self} registering service={service} address={address}");
+
+        let instance = ServiceInstance {
+            address,
+            register_time: std::time::SystemTime::now(),
+        };
+
+        if let Some(entry) = self.services.iter_mut().find(|s| s.name() == service) {
+            entry.remove_controller(instance.address());
+            entry.controllers.push(instance);
+        } else {
+            self.services.push(ServiceEntry {
+                name: service.clone(),
+                controllers: vec![instance],
+            });
+
+            // First controller for this service; it's now up.
+            self.publish_service_event(&service, true);
+
+            // Apply any service-level stream trim policy to the
+            // stream we'll be routing into.
+            if let Some(policy) = self
+                .config
+                .service_options(&service)
+                .and_then(|o| o.stream_trim())
+            {
+                let stream = ServiceAddress::new(&service).full().to_string();
+                self.bus.set_stream_trim(&stream, policy);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle_unregister(
+        &mut self,
+        address: &ClientAddress,
+        service: String,
+    ) -> Result<(), String> {
+        info!("{self} unregistering service={service} address={address}");
+
+        if let Some(entry) = self.services.iter_mut().find(|s| s.name() == service) {
+            entry.remove_controller(address);
+        }
+
+        // Drop service entries with no remaining controllers.
+        let had = self.services.len();
+        self.services.retain(|s| !s.controllers().is_empty());
+
+        if self.services.len() < had {
+            self.publish_service_event(&service, false);
+        }
+
+        Ok(())
+    }
+
+    /// Unregisters one controller of a service and relays a drain
+    /// notice to it so the server can finish outstanding sessions
+    /// and exit cleanly, e.g. during a rolling restart.
+    fn handle_drain(&mut self, service: String, address: String) -> Result<(), String> {
+        info!("{self} draining service={service} address={address}");
+
+        let addr = ClientAddress::from_string(&address)?;
+        self.handle_unregister(&addr, service.clone())?;
+
+        let mut notice = TransportMessage::new(
+            &address,
+            &self.listen_address(),
+            &opensrf::util::random_number(16),
+        );
+
+        notice.set_router_command("drain");
+        notice.set_router_class(&service);
+
+        self.bus.send(&notice)
+    }
+
+    /// Adds a client to the watcher list for a service.
+    fn handle_watch(&mut self, address: ClientAddress, service: String) -> Result<(), String> {
+        info!("{self} watch service={service} address={address}");
+
+        if !self
+            .watchers
+            .iter()
+            .any(|(s, a)| s == &service && a.full() == address.full())
+        {
+            self.watchers.push((service, address));
+        }
+
+        Ok(())
+    }
+
+    /// Tells every watcher of a service that it just came up or went
+    /// down.
+    fn publish_service_event(&mut self, service: &str, up: bool) {
+        let command = if up { "service_up" } else { "service_down" };
+
+        let addresses: Vec<String> = self
+            .watchers
+            .iter()
+            .filter(|(s, _)| s == service)
+            .map(|(_, a)| a.full().to_string())
+            .collect();
+
+        for address in addresses {
+            debug!("{self} sending {command} service={service} to={address}");
+
+            let mut tmsg = TransportMessage::new(
+                &address,
+                &self.listen_address(),
+                &opensrf::util::random_number(16),
+            );
+
+            tmsg.set_router_command(command);
+            tmsg.set_router_class(service);
+
+            if let Err(e) = self.bus.send(&tmsg) {
+                error!("{self} cannot notify watcher address={address}: {e}");
+            }
+        }
+    }
+
+    fn summarize(&self) -> json::JsonValue {
+        json::object! {
+            domain: self.domain.as_str(),
+            services: self.services
+                .iter()
+                .map(|s| s.to_json_value())
+                .collect::<Vec<json::JsonValue>>(),
+        }
+    }
+
+    /// Bounces a request whose destination queue is over its cap,
+    /// telling the sender the service is unavailable.
+    fn reject_overflow(&mut self, tmsg: &TransportMessage, service: &str) -> Result<(), String> {
+        let thread_trace = tmsg
+            .body()
+            .first()
+            .map(|m| m.thread_trace())
+            .unwrap_or(0);
+
+        let status = Message::new(
+            MessageType::Status,
+            thread_trace,
+            Payload::Status(Status::new(
+                MessageStatus::ServiceUnavailable,
+                &format!("Service {service} queue is full"),
+            )),
+        );
+
+        let reply =
+            TransportMessage::with_body(tmsg.from(), &self.listen_address(), tmsg.thread(), status);
+
+        self.bus.send(&reply)
+    }
+
+    /// Forwards a non-command message to its destination service
+    /// stream, provided the service has at least one controller.
+    ///
+    /// Messages carrying an affinity key are instead hashed to a
+    /// specific controller and sent to its personal address, so
+    /// related requests consistently land on the same worker.
+    fn route_message(&mut self, tmsg: TransportMessage) -> Result<(), String> {
+        let service_addr = ServiceAddress::from_string(tmsg.to())?;
+        let service = service_addr.service().to_string();
+
+        let entry = match self
+            .services
+            .iter()
+            .find(|s| s.name() == service)
+            .or_else(|| {
+                // No exact registration; fall back to the longest
+                // matching wildcard registration, e.g. a catch-all
+                // registered as "open-ils.fielder.*".
+                self.services
+                    .iter()
+                    .filter(|s| {
+                        s.name()
+                            .strip_suffix('*')
+                            .map(|prefix| service.starts_with(prefix))
+                            .unwrap_or(false)
+                    })
+                    .max_by_key(|s| s.name().len())
+            }) {
+            Some(e) => e,
+            None => {
+                warn!("{self} no controllers for service={service}");
+                return Err(format!("Service not found: {service}"));
+            }
+        };
+
+        // Enforce the service's queue cap, if any, so overloaded
+        // services produce immediate failures instead of unbounded
+        // queueing.
+        if let Some(cap) = self
+            .config
+            .service_options(&service)
+            .map(|o| o.max_queued())
+            .filter(|cap| *cap > 0)
+        {
+            let stream = ServiceAddress::new(entry.name()).full().to_string();
+            let depth = self.bus.xlen(&stream).unwrap_or(0) as usize;
+
+            if depth >= cap {
+                warn!("{self} service={service} at queue cap ({depth}/{cap}); rejecting");
+                return self.reject_overflow(&tmsg, &service);
+            }
+        }
+
+        if tmsg.broadcast() {
+            debug!(
+                "{self} broadcasting to {} controllers of service={service}",
+                entry.controllers().len()
+            );
+
+            let addresses: Vec<String> = entry
+                .controllers()
+                .iter()
+                .map(|c| c.address().full().to_string())
+                .collect();
+
+            for address in addresses {
+                let mut copy = tmsg.clone();
+                copy.set_to(&address);
+                if let Err(e) = self.bus.send(&copy) {
+                    error!("{self} cannot broadcast to address={address}: {e}");
+                }
+            }
+
+            return Ok(());
+        }
+
+        if let Some(key) = tmsg.affinity_key() {
+            let controllers = entry.controllers();
+
+            let index = affinity_controller_index(key, controllers.len());
+
+            let address = controllers[index].address().full().to_string();
+
+            debug!("{self} affinity key={key} routes to address={address}");
+
+            let mut tmsg = tmsg;
+            tmsg.set_to(&address);
+
+            return self.bus.send(&tmsg);
+        }
+
+        if entry.name() != service {
+            // Wildcard match.  Deliver to the catch-all's registered
+            // stream; the exact service stream has no consumers.
+            let stream = ServiceAddress::new(entry.name()).full().to_string();
+            debug!("{self} wildcard route service={service} stream={stream}");
+            return self.bus.send_to(&tmsg, &stream);
+        }
+
+        self.bus.send(&tmsg)
+    }
+}
+
+/// Index of the controller an affinity key hashes to, for a
+/// service with the provided controller count.
+///
+/// The mapping is deterministic, so every message carrying the
+/// same key lands on the same controller for as long as the
+/// controller set is stable.
+fn affinity_controller_index(key: &str, controllers: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % controllers as u64) as usize
+}
+
+fn main() {
+    let (mut config, _params) = init::init().expect("Error in router init");
+
+    // The router is a per-domain process; run one on our hostname's
+    // node, falling back to the first configured node.
+    let domain = match config.get_node(config.hostname()) {
+        Some(n) => n.name().to_string(),
+        None => config.nodes()[0].name().to_string(),
+    };
+
+    config
+        .set_primary_connection("router", &domain)
+        .expect("Router requires a 'router' connection");
+
+    let mut router = Router::new(&config, &domain).expect("Error creating router");
+
+    info!("Router listening on domain {domain}");
+
+    router.listen();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_affinity_controller_index() {
+        let index = affinity_controller_index("patron-42", 4);
+        assert!(index < 4);
+
+        // Two sends with the same key reach the same controller.
+        assert_eq!(index, affinity_controller_index("patron-42", 4));
+        assert_eq!(index, affinity_controller_index("patron-42", 4));
+
+        // A single controller takes every key.
+        assert_eq!(affinity_controller_index("patron-42", 1), 0);
+        assert_eq!(affinity_controller_index("patron-43", 1), 0);
+    }
+}
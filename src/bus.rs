@@ -0,0 +1,2301 @@
+use super::addr;
+use super::addr::ClientAddress;
+use super::conf;
+use super::message::TransportMessage;
+use super::util;
+use log::{debug, error, trace, warn};
+use rand::Rng;
+use redis::cluster::{ClusterClient, ClusterConnection};
+use redis::streams::{StreamMaxlen, StreamReadOptions, StreamReadReply};
+use redis::{Commands, Connection, ConnectionAddr, ConnectionInfo, ConnectionLike, RedisConnectionInfo};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Sorted set holding messages scheduled for future delivery,
+/// scored by delivery time.
+fn schedule_key() -> String {
+    format!("{}:schedule", addr::bus_namespace())
+}
+
+/// Max scheduled messages claimed per drain pass.
+const SCHEDULE_BATCH_SIZE: isize = 100;
+
+/// The stream key for a broadcast topic; see Bus::publish().
+fn topic_stream(topic: &str) -> String {
+    format!("{}:topic:{topic}", addr::bus_namespace())
+}
+
+/// Splits a string into pieces of at most size bytes, splitting
+/// only on char boundaries.
+pub(crate) fn chunk_str(value: &str, size: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = value;
+
+    while rest.len() > size {
+        let mut end = size;
+
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+
+    chunks.push(rest);
+
+    chunks
+}
+
+/// Minimum time between consumer-lag measurements.
+const LAG_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long each connection attempt may take before we move on to
+/// the node's next configured address.
+const CONNECT_ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// First reconnect attempt waits this long; each subsequent attempt
+/// doubles the delay.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(250);
+
+/// Ceiling on the reconnect backoff delay.
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(15);
+
+/// Default cap on consecutive reconnect attempts; see
+/// Bus::set_max_reconnect_attempts().
+const DEFAULT_MAX_RECONNECT_ATTEMPTS: usize = 5;
+
+/// Default cap on send retries after a retryable error; see
+/// Bus::set_max_send_retries().
+const DEFAULT_MAX_SEND_RETRIES: usize = 3;
+
+/// First send retry waits about this long; each subsequent retry
+/// doubles the delay.
+const SEND_RETRY_BASE_DELAY: Duration = Duration::from_millis(100);
+
+/// Ceiling on the send retry backoff delay.
+const SEND_RETRY_MAX_DELAY: Duration = Duration::from_secs(2);
+
+/// Port assumed for sentinel addresses configured without one.
+const DEFAULT_SENTINEL_PORT: u16 = 26379;
+
+/// How many stream entries to pull per XREADGROUP by default; see
+/// Bus::set_read_batch_size().
+const DEFAULT_READ_BATCH_SIZE: usize = 10;
+
+/// How long recv_from_any() camps on its primary stream before
+/// rescanning the others.
+const MULTI_RECV_POLL: Duration = Duration::from_millis(250);
+
+/// How often a backpressured send re-checks the destination depth.
+const SEND_BACKPRESSURE_POLL: Duration = Duration::from_millis(100);
+
+/// Total messages recv_for_thread() may spool for other threads
+/// before it starts shedding the oldest.
+const THREAD_SPOOL_MAX: usize = 1024;
+
+/// Chunked messages we'll reassemble concurrently before evicting
+/// the oldest partial; see Bus::set_max_chunk_size().
+const CHUNK_BUFFER_MAX: usize = 32;
+
+/// Invoked when consumer-group lag on a stream exceeds the
+/// configured threshold; see Bus::set_lag_alarm().
+pub type LagAlarmCallback = fn(stream: &str, lag: usize);
+
+/// Counters tracking transport behavior for one Bus.
+///
+/// See Bus::stats().
+#[derive(Debug, Default, Clone)]
+pub struct BusStats {
+    msgs_sent: usize,
+    msgs_received: usize,
+    bytes_sent: usize,
+    bytes_received: usize,
+    serialization_errors: usize,
+    redis_errors: usize,
+    stale_dropped: usize,
+    blocking_time: Duration,
+}
+
+impl BusStats {
+    pub fn msgs_sent(&self) -> usize {
+        self.msgs_sent
+    }
+
+    pub fn msgs_received(&self) -> usize {
+        self.msgs_received
+    }
+
+    pub fn bytes_sent(&self) -> usize {
+        self.bytes_sent
+    }
+
+    pub fn bytes_received(&self) -> usize {
+        self.bytes_received
+    }
+
+    /// Messages that could not be serialized or parsed.
+    pub fn serialization_errors(&self) -> usize {
+        self.serialization_errors
+    }
+
+    /// Redis commands that returned an error.
+    pub fn redis_errors(&self) -> usize {
+        self.redis_errors
+    }
+
+    /// Messages discarded for exceeding the max message age.
+    pub fn stale_dropped(&self) -> usize {
+        self.stale_dropped
+    }
+
+    /// Total time spent waiting on stream reads.
+    pub fn blocking_time(&self) -> Duration {
+        self.blocking_time
+    }
+
+    pub fn to_json_value(&self) -> json::JsonValue {
+        json::object! {
+            msgs_sent: self.msgs_sent,
+            msgs_received: self.msgs_received,
+            bytes_sent: self.bytes_sent,
+            bytes_received: self.bytes_received,
+            serialization_errors: self.serialization_errors,
+            redis_errors: self.redis_errors,
+            stale_dropped: self.stale_dropped,
+            blocking_time_ms: self.blocking_time.as_millis() as usize,
+        }
+    }
+}
+
+/// Snapshot of one stream's health, combining XLEN, XINFO STREAM,
+/// and XINFO GROUPS data for our consumer group.
+///
+/// See Bus::stream_info().
+#[derive(Debug, Default, Clone)]
+pub struct StreamInfo {
+    length: usize,
+    last_generated_id: String,
+    consumer_count: usize,
+    pending: usize,
+    lag: usize,
+    last_delivered_id: String,
+}
+
+impl StreamInfo {
+    /// Number of entries currently held in the stream.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// Id of the most recently added entry.
+    pub fn last_generated_id(&self) -> &str {
+        &self.last_generated_id
+    }
+
+    /// Number of consumers registered with the stream's group.
+    pub fn consumer_count(&self) -> usize {
+        self.consumer_count
+    }
+
+    /// Entries delivered to a consumer but not yet acknowledged.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Entries the group has not yet delivered to any consumer.
+    pub fn lag(&self) -> usize {
+        self.lag
+    }
+
+    /// Id of the last entry delivered to the stream's group.
+    pub fn last_delivered_id(&self) -> &str {
+        &self.last_delivered_id
+    }
+
+    pub fn to_json_value(&self) -> json::JsonValue {
+        json::object! {
+            length: self.length,
+            last_generated_id: self.last_generated_id.as_str(),
+            consumer_count: self.consumer_count,
+            pending: self.pending,
+            lag: self.lag,
+            last_delivered_id: self.last_delivered_id.as_str(),
+        }
+    }
+}
+
+/// One consumer of a stream's group, from XINFO CONSUMERS.
+///
+/// See Bus::consumer_info().
+#[derive(Debug, Default, Clone)]
+pub struct ConsumerInfo {
+    name: String,
+    pending: usize,
+    idle: Duration,
+}
+
+impl ConsumerInfo {
+    /// Consumer name, e.g. "service:worker-id:pid" for workers.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Entries delivered to this consumer but not yet acknowledged.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Time since this consumer last attempted a read.
+    pub fn idle(&self) -> Duration {
+        self.idle
+    }
+
+    pub fn to_json_value(&self) -> json::JsonValue {
+        json::object! {
+            name: self.name.as_str(),
+            pending: self.pending,
+            idle_ms: self.idle.as_millis() as usize,
+        }
+    }
+}
+
+/// The underlying Redis connection: a single server or a cluster.
+///
+/// Delegating ConnectionLike lets the rest of the Bus issue commands
+/// without caring which one it has.
+enum BusChannel {
+    Single(Connection),
+    Cluster(Box<ClusterConnection>),
+}
+
+impl redis::ConnectionLike for BusChannel {
+    fn req_packed_command(&mut self, cmd: &[u8]) -> redis::RedisResult<redis::Value> {
+        match self {
+            BusChannel::Single(c) => c.req_packed_command(cmd),
+            BusChannel::Cluster(c) => c.req_packed_command(cmd),
+        }
+    }
+
+    fn req_packed_commands(
+        &mut self,
+        cmd: &[u8],
+        offset: usize,
+        count: usize,
+    ) -> redis::RedisResult<Vec<redis::Value>> {
+        match self {
+            BusChannel::Single(c) => c.req_packed_commands(cmd, offset, count),
+            BusChannel::Cluster(c) => c.req_packed_commands(cmd, offset, count),
+        }
+    }
+
+    fn get_db(&self) -> i64 {
+        match self {
+            BusChannel::Single(c) => c.get_db(),
+            BusChannel::Cluster(c) => c.get_db(),
+        }
+    }
+
+    fn check_connection(&mut self) -> bool {
+        match self {
+            BusChannel::Single(c) => c.check_connection(),
+            BusChannel::Cluster(c) => c.check_connection(),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        match self {
+            BusChannel::Single(c) => c.is_open(),
+            BusChannel::Cluster(c) => c.is_open(),
+        }
+    }
+}
+
+/// Manages the Redis connection for a single bus participant.
+///
+/// Every Bus gets a unique client address and consumes from the
+/// stream whose key matches that address.  Servers additionally
+/// consume from their service-level stream.
+pub struct Bus {
+    connection: BusChannel,
+
+    /// Connection config retained so we can reconnect after a
+    /// connection-level failure.
+    config: conf::BusConnection,
+
+    /// How many times reconnect() tries before giving up.
+    max_reconnect_attempts: usize,
+
+    /// How many times a failed send is retried before the error is
+    /// returned to the caller.
+    max_send_retries: usize,
+
+    /// Outbound messages larger than this many bytes are split
+    /// into multiple stream entries.  None disables chunking.
+    max_chunk_size: Option<usize>,
+
+    /// Partially reassembled chunked messages by chunk id: when we
+    /// first saw the message, the next expected sequence number,
+    /// and the body accumulated so far.
+    chunks: HashMap<String, (Instant, usize, String)>,
+
+    /// Topic subscription cursors: topic name mapped to the last
+    /// entry id we've seen on its stream.
+    subscriptions: HashMap<String, String>,
+
+    /// Our unique bus address.
+    address: ClientAddress,
+
+    /// Domain, i.e. bus node name, we're connected to.
+    domain: String,
+
+    /// Name we present to consumer groups, so operators can tell
+    /// which worker of a service consumed a message.
+    consumer_name: String,
+
+    /// Lag threshold and optional alarm callback; see
+    /// set_lag_alarm().
+    lag_alarm: Option<(usize, Option<LagAlarmCallback>)>,
+
+    /// When we last measured consumer lag.
+    last_lag_check: Instant,
+
+    /// Stream entries read per XREADGROUP round trip.
+    read_batch_size: usize,
+
+    /// Received messages older than this are discarded; see
+    /// set_max_message_age().
+    max_message_age: Option<Duration>,
+
+    /// Destination depth limit and how long send() may wait for
+    /// room; see set_send_backpressure().
+    send_backpressure: Option<(usize, Duration)>,
+
+    /// How destination streams are trimmed when we add messages.
+    trim_policy: conf::TrimPolicy,
+
+    /// Per-stream trim policies overriding trim_policy, keyed by
+    /// raw (untagged) stream name.
+    stream_trim_overrides: HashMap<String, conf::TrimPolicy>,
+
+    /// Entries read in a batch but not yet handed to the caller,
+    /// per stream, as (entry id, payload) pairs.
+    unread: HashMap<String, VecDeque<(String, String)>>,
+
+    /// Messages set aside by recv_for_thread() for other threads,
+    /// keyed by thread.  Bounded by THREAD_SPOOL_MAX.
+    thread_spool: HashMap<String, VecDeque<TransportMessage>>,
+
+    /// Total messages across all thread_spool queues.
+    thread_spool_count: usize,
+
+    /// True means reads skip NOACK and callers acknowledge each
+    /// message via ack_last() once it's fully processed.
+    reliable: bool,
+
+    /// Stream and entry id of the last message handed out in
+    /// reliable mode, pending acknowledgment.
+    last_delivered: Option<(String, String)>,
+
+    /// Transport counters; see stats().
+    stats: BusStats,
+}
+
+impl fmt::Display for Bus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bus {}", self.address.full())
+    }
+}
+
+impl Bus {
+    pub fn new(config: &conf::BusConnection) -> Result<Self, String> {
+        let connection = Bus::connect(config)?;
+
+        let address = ClientAddress::new(config.domain());
+
+        // Unique by construction; workers override this with their
+        // service and worker id via set_consumer_name().
+        let consumer_name = address.full().to_string();
+
+        let mut bus = Bus {
+            connection,
+            config: config.clone(),
+            max_reconnect_attempts: DEFAULT_MAX_RECONNECT_ATTEMPTS,
+            max_send_retries: DEFAULT_MAX_SEND_RETRIES,
+            max_chunk_size: None,
+            chunks: HashMap::new(),
+            subscriptions: HashMap::new(),
+            address,
+            domain: config.domain().to_string(),
+            consumer_name,
+            lag_alarm: None,
+            last_lag_check: Instant::now(),
+            read_batch_size: DEFAULT_READ_BATCH_SIZE,
+            max_message_age: config.node().max_message_age(),
+            send_backpressure: None,
+            trim_policy: config.node().trim_policy(),
+            stream_trim_overrides: HashMap::new(),
+            unread: HashMap::new(),
+            thread_spool: HashMap::new(),
+            thread_spool_count: 0,
+            reliable: false,
+            last_delivered: None,
+            stats: BusStats::default(),
+        };
+
+        bus.setup_stream(None)?;
+
+        Ok(bus)
+    }
+
+    /// Opens a connection to the node, trying each of its configured
+    /// addresses in order with a per-attempt timeout.
+    ///
+    /// Nodes fronted by Sentinel are resolved to the current master
+    /// first, so a failover is picked up on the next (re)connect.
+    /// Nodes with cluster seeds get a cluster connection instead.
+    fn connect(config: &conf::BusConnection) -> Result<BusChannel, String> {
+        if !config.node().cluster_nodes().is_empty() {
+            return Bus::connect_cluster(config).map(|c| BusChannel::Cluster(Box::new(c)));
+        }
+
+        if let Some(master) = config.node().sentinel_master() {
+            return Bus::connect_via_sentinel(config, master);
+        }
+
+        let mut last_err = format!("No addresses for node {}", config.node().name());
+
+        for address in config.node().addresses() {
+            debug!("Attempting bus connection to {address}");
+
+            let info = Bus::connection_info(config, &address);
+
+            let client = match Bus::open_client(config, info) {
+                Ok(c) => c,
+                Err(e) => {
+                    last_err = format!("Cannot open connection to {address}: {e}");
+                    continue;
+                }
+            };
+
+            match client.get_connection_with_timeout(CONNECT_ATTEMPT_TIMEOUT) {
+                Ok(c) => return Ok(BusChannel::Single(c)),
+                Err(e) => {
+                    last_err = format!("Bus connect error for {address}: {e}");
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Connects to the node's cluster via its configured seeds.
+    fn connect_cluster(config: &conf::BusConnection) -> Result<ClusterConnection, String> {
+        let mut seeds = Vec::new();
+
+        for seed in config.node().cluster_nodes() {
+            let (host, port) = match seed.rsplit_once(':') {
+                Some((h, p)) => match p.parse::<u16>() {
+                    Ok(p) => (h.to_string(), p),
+                    Err(_) => return Err(format!("Invalid cluster seed address: {seed}")),
+                },
+                None => (seed.to_string(), config.node().port()),
+            };
+
+            debug!("Adding cluster seed {host}:{port}");
+
+            let mut info = Bus::connection_info(config, &host);
+            info.addr = ConnectionAddr::Tcp(host, port);
+
+            seeds.push(info);
+        }
+
+        let client = match ClusterClient::new(seeds) {
+            Ok(c) => c,
+            Err(e) => return Err(format!("Error building cluster client: {e}")),
+        };
+
+        match client.get_connection() {
+            Ok(c) => Ok(c),
+            Err(e) => Err(format!(
+                "Cluster connect error for node {}: {e}",
+                config.node().name()
+            )),
+        }
+    }
+
+    /// Asks the node's sentinels for the current master address and
+    /// connects to it.
+    ///
+    /// Sentinels are tried in order; the first one that answers
+    /// decides.  Sentinels themselves are queried without
+    /// credentials, which only apply to the master.
+    fn connect_via_sentinel(
+        config: &conf::BusConnection,
+        master: &str,
+    ) -> Result<BusChannel, String> {
+        let mut last_err = format!("No sentinels for node {}", config.node().name());
+
+        for sentinel in config.node().sentinels() {
+            let (host, port) = match sentinel.rsplit_once(':') {
+                Some((h, p)) => match p.parse::<u16>() {
+                    Ok(p) => (h.to_string(), p),
+                    Err(_) => {
+                        last_err = format!("Invalid sentinel address: {sentinel}");
+                        continue;
+                    }
+                },
+                None => (sentinel.to_string(), DEFAULT_SENTINEL_PORT),
+            };
+
+            debug!("Asking sentinel {host}:{port} for master '{master}'");
+
+            let info = ConnectionInfo {
+                addr: ConnectionAddr::Tcp(host, port),
+                redis: RedisConnectionInfo {
+                    db: 0,
+                    username: None,
+                    password: None,
+                },
+            };
+
+            let client = match redis::Client::open(info) {
+                Ok(c) => c,
+                Err(e) => {
+                    last_err = format!("Error opening sentinel connection to {sentinel}: {e}");
+                    continue;
+                }
+            };
+
+            let mut scon = match client.get_connection_with_timeout(CONNECT_ATTEMPT_TIMEOUT) {
+                Ok(c) => c,
+                Err(e) => {
+                    last_err = format!("Sentinel connect error for {sentinel}: {e}");
+                    continue;
+                }
+            };
+
+            let addr: Vec<String> = match redis::cmd("SENTINEL")
+                .arg("get-master-addr-by-name")
+                .arg(master)
+                .query(&mut scon)
+            {
+                Ok(a) => a,
+                Err(e) => {
+                    last_err = format!("Sentinel {sentinel} query error: {e}");
+                    continue;
+                }
+            };
+
+            let (mhost, mport) = match (addr.first(), addr.get(1)) {
+                (Some(h), Some(p)) => match p.parse::<u16>() {
+                    Ok(p) => (h.to_string(), p),
+                    Err(_) => {
+                        last_err = format!("Sentinel {sentinel} returned bad port: {p}");
+                        continue;
+                    }
+                },
+                _ => {
+                    last_err = format!("Sentinel {sentinel} does not know master '{master}'");
+                    continue;
+                }
+            };
+
+            debug!("Sentinel reports master '{master}' at {mhost}:{mport}");
+
+            let mut info = Bus::connection_info(config, &mhost);
+
+            // Preserve any TLS settings from connection_info().
+            match &mut info.addr {
+                ConnectionAddr::TcpTls { host, port, .. } => {
+                    *host = mhost;
+                    *port = mport;
+                }
+                addr => *addr = ConnectionAddr::Tcp(mhost, mport),
+            }
+
+            let client = match Bus::open_client(config, info) {
+                Ok(c) => c,
+                Err(e) => {
+                    last_err = format!("Cannot open connection to master: {e}");
+                    continue;
+                }
+            };
+
+            match client.get_connection_with_timeout(CONNECT_ATTEMPT_TIMEOUT) {
+                Ok(c) => return Ok(BusChannel::Single(c)),
+                Err(e) => last_err = format!("Bus connect error for master '{master}': {e}"),
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Generates the Redis connection info from a bus connection
+    /// config and one of its node's addresses.
+    pub(crate) fn connection_info(config: &conf::BusConnection, address: &str) -> ConnectionInfo {
+        let creds = config.credentials();
+
+        let redis_info = RedisConnectionInfo {
+            db: config.node().db(),
+            username: Some(creds.username().to_string()),
+            password: Some(creds.password().to_string()),
+        };
+
+        let addr = if let Some(path) = config.node().socket_path() {
+            ConnectionAddr::Unix(path.into())
+        } else {
+            match config.node().tls() {
+                Some(tls) if tls.enabled() => ConnectionAddr::TcpTls {
+                    host: address.to_string(),
+                    port: config.node().port(),
+                    insecure: !tls.verify(),
+                    tls_params: None,
+                },
+                _ => ConnectionAddr::Tcp(address.to_string(), config.node().port()),
+            }
+        };
+
+        ConnectionInfo {
+            addr,
+            redis: redis_info,
+        }
+    }
+
+    /// Opens a Redis client for the provided connection info,
+    /// loading the node's TLS certificate files when configured.
+    pub(crate) fn open_client(
+        config: &conf::BusConnection,
+        info: ConnectionInfo,
+    ) -> Result<redis::Client, String> {
+        let tls = match config.node().tls() {
+            Some(tls)
+                if tls.enabled() && (tls.ca_file().is_some() || tls.cert_file().is_some()) =>
+            {
+                tls
+            }
+            _ => {
+                return redis::Client::open(info)
+                    .map_err(|e| format!("Error opening Redis connection: {e}"));
+            }
+        };
+
+        let root_cert = match tls.ca_file() {
+            Some(f) => {
+                Some(std::fs::read(f).map_err(|e| format!("Cannot read TLS CA file {f}: {e}"))?)
+            }
+            None => None,
+        };
+
+        let client_tls = match (tls.cert_file(), tls.key_file()) {
+            (Some(cert), Some(key)) => Some(redis::ClientTlsConfig {
+                client_cert: std::fs::read(cert)
+                    .map_err(|e| format!("Cannot read TLS cert file {cert}: {e}"))?,
+                client_key: std::fs::read(key)
+                    .map_err(|e| format!("Cannot read TLS key file {key}: {e}"))?,
+            }),
+            (None, None) => None,
+            _ => return Err("TLS cert-file and key-file must be configured together".to_string()),
+        };
+
+        redis::Client::build_with_tls(
+            info,
+            redis::TlsCertificates {
+                client_tls,
+                root_cert,
+            },
+        )
+        .map_err(|e| format!("Error building TLS Redis client: {e}"))
+    }
+
+    pub fn address(&self) -> &ClientAddress {
+        &self.address
+    }
+
+    pub fn set_address(&mut self, address: &ClientAddress) {
+        self.address = address.clone();
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Name we present to consumer groups.
+    pub fn consumer_name(&self) -> &str {
+        &self.consumer_name
+    }
+
+    pub fn set_consumer_name(&mut self, name: &str) {
+        self.consumer_name = name.to_string();
+    }
+
+    fn connection(&mut self) -> &mut BusChannel {
+        &mut self.connection
+    }
+
+    /// Returns the Redis key for the provided stream name.
+    ///
+    /// In cluster mode the variable portion of the name becomes a
+    /// hash tag, e.g. "opensrf:service:foo" keys on
+    /// "opensrf:{service:foo}", giving each stream -- and the
+    /// consumer group that shares its key -- a stable slot.
+    fn stream_key(&self, stream: &str) -> String {
+        if let BusChannel::Cluster(_) = self.connection {
+            let namespace = addr::bus_namespace();
+
+            if let Some(rest) = stream.strip_prefix(&format!("{namespace}:")) {
+                return format!("{namespace}:{{{rest}}}");
+            }
+        }
+
+        stream.to_string()
+    }
+
+    /// Caps how many times reconnect() tries before giving up.
+    pub fn set_max_reconnect_attempts(&mut self, attempts: usize) {
+        self.max_reconnect_attempts = attempts;
+    }
+
+    /// Caps how many times a failed send is retried before the
+    /// error is returned to the caller.  Zero disables retries.
+    pub fn set_max_send_retries(&mut self, retries: usize) {
+        self.max_send_retries = retries;
+    }
+
+    /// Splits outbound messages larger than this many bytes into
+    /// multiple stream entries, since very large XADD values
+    /// degrade Redis performance.  None, the default, disables
+    /// chunking.
+    ///
+    /// Every receiver reassembles chunks regardless of this
+    /// setting, so it only needs to be enabled on senders.
+    pub fn set_max_chunk_size(&mut self, size: Option<usize>) {
+        // Floor at the widest UTF-8 char so chunking always makes
+        // progress.
+        self.max_chunk_size = size.map(|s| std::cmp::max(s, 4));
+    }
+
+    /// Sets how many stream entries each XREADGROUP may return.
+    ///
+    /// Extras are buffered and handed out by subsequent recv()
+    /// calls, cutting round trips for busy consumers.  A size of 1
+    /// restores the one-trip-per-message behavior.
+    pub fn set_read_batch_size(&mut self, size: usize) {
+        self.read_batch_size = std::cmp::max(size, 1);
+    }
+
+    /// Transport counters accumulated since this Bus connected.
+    pub fn stats(&self) -> &BusStats {
+        &self.stats
+    }
+
+    /// Resets all transport counters to zero.
+    pub fn clear_stats(&mut self) {
+        self.stats = BusStats::default();
+    }
+
+    /// Enables reliable delivery.
+    ///
+    /// Messages are left in the consumer group's pending list until
+    /// acknowledged via ack_last(), so a consumer that dies
+    /// mid-request leaves the message claimable by
+    /// reclaim_pending() instead of silently losing it.
+    pub fn set_reliable(&mut self, reliable: bool) {
+        self.reliable = reliable;
+    }
+
+    /// Acknowledges the last message handed out in reliable mode.
+    pub fn ack_last(&mut self) -> Result<(), String> {
+        let (stream, id) = match self.last_delivered.take() {
+            Some(pair) => pair,
+            None => return Ok(()),
+        };
+
+        trace!("{self} acking id={id} stream={stream}");
+
+        // Group name == stream name per setup_stream().
+        let res: Result<i32, _> = self.connection().xack(&stream, &stream, &[&id]);
+
+        match res {
+            Ok(_) => Ok(()),
+            Err(e) => Err(format!("Error in ack_last(): {e}")),
+        }
+    }
+
+    /// Claims messages another consumer left pending for at least
+    /// min_idle_ms, making them available to our recv() calls.
+    ///
+    /// Returns the number of messages claimed.
+    pub fn reclaim_pending(&mut self, stream: &str, min_idle_ms: usize) -> Result<usize, String> {
+        let stream = self.stream_key(stream);
+
+        let consumer = self.consumer_name.clone();
+
+        let reply: redis::Value = match redis::cmd("XAUTOCLAIM")
+            .arg(&stream)
+            .arg(&stream) // group name == stream name
+            .arg(&consumer)
+            .arg(min_idle_ms)
+            .arg("0-0")
+            .query(self.connection())
+        {
+            Ok(r) => r,
+            Err(e) => return Err(format!("Error in reclaim_pending(): {e}")),
+        };
+
+        // Reply is [next-cursor, [[id, [field, value, ...]], ...]]
+        // plus a list of deleted ids on Redis 7+.
+        let entries = match reply {
+            redis::Value::Bulk(mut parts) if parts.len() >= 2 => parts.swap_remove(1),
+            _ => return Ok(0),
+        };
+
+        let entries = match entries {
+            redis::Value::Bulk(entries) => entries,
+            _ => return Ok(0),
+        };
+
+        let mut claimed = 0;
+
+        for entry in entries {
+            let mut parts = match entry {
+                redis::Value::Bulk(parts) if parts.len() >= 2 => parts,
+                _ => continue,
+            };
+
+            let id = match parts.first() {
+                Some(redis::Value::Data(bytes)) => String::from_utf8_lossy(bytes).to_string(),
+                _ => continue,
+            };
+
+            let fields = match parts.swap_remove(1) {
+                redis::Value::Bulk(fields) => fields,
+                _ => continue,
+            };
+
+            // Fields are a flat key/value list.
+            let mut map = HashMap::new();
+
+            for pair in fields.chunks(2) {
+                if let (Some(redis::Value::Data(key)), Some(value)) =
+                    (pair.first(), pair.get(1))
+                {
+                    map.insert(String::from_utf8_lossy(key).to_string(), value.clone());
+                }
+            }
+
+            if self.message_is_stale(&map) {
+                warn!("{self} discarding stale reclaimed message id={id}");
+                self.stats.stale_dropped += 1;
+
+                let res: Result<i32, _> = self.connection().xack(&stream, &stream, &[&id]);
+
+                if let Err(e) = res {
+                    warn!("{self} cannot ack stale message: {e}");
+                }
+
+                continue;
+            }
+
+            if let Some(redis::Value::Data(value)) = map.get("message") {
+                let payload = String::from_utf8_lossy(value).to_string();
+
+                self.unread
+                    .entry(stream.clone())
+                    .or_default()
+                    .push_back((id.clone(), payload));
+
+                claimed += 1;
+            } else if map.contains_key("chunk") {
+                // The reassembly state for a chunked message died
+                // with its original consumer; drop the fragment.
+                warn!("{self} discarding reclaimed message fragment id={id}");
+
+                let res: Result<i32, _> = self.connection().xack(&stream, &stream, &[&id]);
+
+                if let Err(e) = res {
+                    warn!("{self} cannot ack reclaimed fragment: {e}");
+                }
+            }
+        }
+
+        if claimed > 0 {
+            debug!("{self} reclaimed {claimed} pending messages from stream={stream}");
+        }
+
+        Ok(claimed)
+    }
+
+    /// Issues a Redis PING and returns the round-trip latency.
+    ///
+    /// A cheap health check for callers that want to detect a dead
+    /// broker proactively instead of on the next failed recv().
+    /// Does not attempt to reconnect on failure.
+    pub fn ping(&mut self) -> Result<Duration, String> {
+        let start = Instant::now();
+
+        let res: Result<String, _> = redis::cmd("PING").query(self.connection());
+
+        match res {
+            Ok(_) => Ok(start.elapsed()),
+            Err(e) => {
+                self.stats.redis_errors += 1;
+                Err(format!("Error in ping(): {e}"))
+            }
+        }
+    }
+
+    /// True if the underlying connection believes it's still open.
+    ///
+    /// This reflects local socket state only; use ping() to verify
+    /// the server is actually responding.
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_open()
+    }
+
+    /// Enables outbound backpressure.
+    ///
+    /// Before adding a message, send() checks the destination
+    /// stream depth; at or above the limit it re-checks until the
+    /// wait expires, then fails with a busy error instead of
+    /// letting a dead service's stream grow until buswatch expires
+    /// the key.  A zero wait fails fast.
+    pub fn set_send_backpressure(&mut self, limit: usize, wait: Duration) {
+        self.send_backpressure = Some((std::cmp::max(limit, 1), wait));
+    }
+
+    /// Disables outbound backpressure.
+    pub fn clear_send_backpressure(&mut self) {
+        self.send_backpressure = None;
+    }
+
+    /// Blocks until the destination stream depth falls below the
+    /// limit or the wait expires, whichever comes first.
+    fn await_queue_room(
+        &mut self,
+        stream: &str,
+        limit: usize,
+        wait: Duration,
+    ) -> Result<(), String> {
+        let timer = util::Timer::new(wait);
+
+        loop {
+            let depth = self.xlen(stream)? as usize;
+
+            if depth < limit {
+                return Ok(());
+            }
+
+            if timer.done() {
+                return Err(format!(
+                    "{self} stream={stream} is busy: depth={depth} limit={limit}"
+                ));
+            }
+
+            std::thread::sleep(std::cmp::min(timer.remaining(), SEND_BACKPRESSURE_POLL));
+        }
+    }
+
+    /// Sets the age beyond which received messages are discarded.
+    ///
+    /// Stale CONNECT or Request messages delivered minutes late
+    /// cause confusing worker behavior; with a max age set, they
+    /// are dropped (and acked, in reliable mode) instead of being
+    /// handed to the application.
+    pub fn set_max_message_age(&mut self, age: Option<Duration>) {
+        self.max_message_age = age;
+    }
+
+    /// True if the entry's send-time stamp exceeds the configured
+    /// max message age.  Entries without a stamp are never stale.
+    fn message_is_stale(&self, map: &HashMap<String, redis::Value>) -> bool {
+        let max_age = match self.max_message_age {
+            Some(age) => age,
+            None => return false,
+        };
+
+        let sent = match map.get("ts") {
+            Some(redis::Value::Data(bytes)) => {
+                match String::from_utf8_lossy(bytes).parse::<u64>() {
+                    Ok(t) => t,
+                    Err(_) => return false,
+                }
+            }
+            _ => return false,
+        };
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+
+        now.saturating_sub(sent) > max_age.as_secs()
+    }
+
+    /// Replaces the trim policy applied when we add messages.
+    pub fn set_trim_policy(&mut self, policy: conf::TrimPolicy) {
+        self.trim_policy = policy;
+    }
+
+    /// Overrides the trim policy for one destination stream, e.g.
+    /// per-service policies applied by the router.
+    pub fn set_stream_trim(&mut self, stream: &str, policy: conf::TrimPolicy) {
+        self.stream_trim_overrides.insert(stream.to_string(), policy);
+    }
+
+    /// True if the error means we lost our connection, as opposed to
+    /// a server-side error for a request it received fine.
+    fn is_connection_error(err: &redis::RedisError) -> bool {
+        err.is_connection_dropped()
+            || err.is_connection_refusal()
+            || err.is_io_error()
+            || err.is_timeout()
+    }
+
+    /// True if a retried send may succeed: connection loss, plus
+    /// server-side conditions that clear on their own, like a
+    /// replica still loading its dataset or a cluster mid-failover.
+    fn is_retryable_error(err: &redis::RedisError) -> bool {
+        Bus::is_connection_error(err)
+            || matches!(
+                err.kind(),
+                redis::ErrorKind::TryAgain
+                    | redis::ErrorKind::BusyLoadingError
+                    | redis::ErrorKind::ClusterDown
+                    | redis::ErrorKind::MasterDown
+            )
+    }
+
+    /// Re-establishes our Redis connection with exponential backoff,
+    /// then re-creates our stream and consumer group, which may have
+    /// been lost along with the server.
+    fn reconnect(&mut self) -> Result<(), String> {
+        let mut delay = RECONNECT_BASE_DELAY;
+        let mut last_err = String::new();
+
+        for attempt in 1..=self.max_reconnect_attempts {
+            warn!(
+                "{self} reconnect attempt {attempt} of {} in {delay:?}",
+                self.max_reconnect_attempts
+            );
+
+            std::thread::sleep(delay);
+            delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+
+            match Bus::connect(&self.config) {
+                Ok(connection) => {
+                    self.connection = connection;
+                    self.setup_stream(None)?;
+                    warn!("{self} reconnected to the bus");
+                    return Ok(());
+                }
+                Err(e) => last_err = e,
+            }
+        }
+
+        Err(format!(
+            "{self} gave up reconnecting after {} attempts: {last_err}",
+            self.max_reconnect_attempts
+        ))
+    }
+
+    /// Creates the stream and consumer group for the provided stream
+    /// name, defaulting to our bus address.
+    ///
+    /// The stream and group will generally already exist for service
+    /// streams; the BUSYGROUP error that results is ignored.
+    pub fn setup_stream(&mut self, name: Option<&str>) -> Result<(), String> {
+        let sname = self.stream_key(name.unwrap_or(self.address.full()));
+
+        debug!("{self} setting up stream={sname}");
+
+        let created: Result<(), _> = redis::cmd("XGROUP")
+            .arg("CREATE")
+            .arg(&sname)
+            .arg(&sname) // group name == stream name
+            .arg("$")
+            .arg("MKSTREAM")
+            .query(self.connection());
+
+        if let Err(e) = created {
+            if !e.to_string().contains("BUSYGROUP") {
+                return Err(format!("Error creating stream={sname}: {e}"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Attempts a single non-repeating read of our stream.
+    ///
+    /// Reads come in batches of up to read_batch_size; one entry is
+    /// returned and the rest are buffered for subsequent calls.
+    ///
+    /// A zero timeout means return immediately if no messages are
+    /// available; Duration::MAX means block indefinitely.
+    fn recv_one_chunk(&mut self, timeout: Duration, stream: &str) -> Result<Option<String>, String> {
+        trace!("{self} recv_one_chunk() timeout={timeout:?} stream={stream}");
+
+        let stream = &self.stream_key(stream);
+
+        if let Some(unread) = self.unread.get_mut(stream) {
+            if let Some((id, payload)) = unread.pop_front() {
+                if self.reliable {
+                    self.last_delivered = Some((stream.to_string(), id));
+                }
+                return Ok(Some(payload));
+            }
+        }
+
+        let mut read_opts = StreamReadOptions::default()
+            .count(self.read_batch_size)
+            .group(stream, &self.consumer_name);
+
+        if !self.reliable {
+            read_opts = read_opts.noack();
+        }
+
+        if !timeout.is_zero() {
+            if timeout == Duration::MAX {
+                // block() of 0 means block indefinitely.
+                read_opts = read_opts.block(0);
+            } else {
+                // BLOCK 0 means forever, so floor at one ms.
+                read_opts = read_opts.block(std::cmp::max(timeout.as_millis() as usize, 1));
+            }
+        }
+
+        let read_start = Instant::now();
+
+        let read_result = self
+            .connection()
+            .xread_options(&[stream], &[">"], &read_opts);
+
+        self.stats.blocking_time += read_start.elapsed();
+
+        let reply: StreamReadReply = match read_result {
+            Ok(r) => r,
+            Err(e) if Bus::is_connection_error(&e) => {
+                self.stats.redis_errors += 1;
+                warn!("{self} lost connection during recv: {e}");
+                self.reconnect()?;
+
+                match self.connection().xread_options(&[stream], &[">"], &read_opts) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        self.stats.redis_errors += 1;
+                        return Err(format!("{self} recv error after reconnect: {e}"));
+                    }
+                }
+            }
+            Err(e) => {
+                self.stats.redis_errors += 1;
+                return Err(format!("{self} recv error: {e}"));
+            }
+        };
+
+        let mut value: Option<String> = None;
+        let mut ack_ids: Vec<String> = Vec::new();
+
+        for stream_key in reply.keys {
+            for entry in stream_key.ids {
+                if self.message_is_stale(&entry.map) {
+                    warn!("{self} discarding stale message id={}", entry.id);
+                    self.stats.stale_dropped += 1;
+                    ack_ids.push(entry.id.clone());
+                    continue;
+                }
+
+                let payload = if let Some(redis::Value::Data(bytes)) = entry.map.get("message") {
+                    match String::from_utf8(bytes.to_vec()) {
+                        Ok(s) => Some(s),
+                        Err(e) => {
+                            return Err(format!("{self} received non-utf8 data: {e}"));
+                        }
+                    }
+                } else if let Some(redis::Value::Data(bytes)) = entry.map.get("chunk") {
+                    let complete = self.collect_chunk(&entry.map, bytes)?;
+
+                    if complete.is_none() && self.reliable {
+                        // Intermediate chunks are consumed into the
+                        // reassembly buffer; ack them now since only
+                        // the final chunk's id is tracked for
+                        // ack_last().
+                        ack_ids.push(entry.id.clone());
+                    }
+
+                    complete
+                } else {
+                    None
+                };
+
+                if let Some(s) = payload {
+                    trace!("{self} read json: {s}");
+
+                    self.stats.msgs_received += 1;
+                    self.stats.bytes_received += s.len();
+
+                    if value.is_none() {
+                        if self.reliable {
+                            self.last_delivered = Some((stream.to_string(), entry.id.clone()));
+                        }
+                        value = Some(s);
+                    } else {
+                        self.unread
+                            .entry(stream.to_string())
+                            .or_default()
+                            .push_back((entry.id.clone(), s));
+                    }
+                }
+            }
+        }
+
+        if self.reliable && !ack_ids.is_empty() {
+            // Ack discarded and buffered entries so they don't
+            // linger in the pending list awaiting reclamation.
+            let ids: Vec<&str> = ack_ids.iter().map(|s| s.as_str()).collect();
+            let res: Result<i32, _> = self.connection().xack(stream, stream, &ids);
+
+            if let Err(e) = res {
+                warn!("{self} cannot ack consumed messages: {e}");
+            }
+        }
+
+        Ok(value)
+    }
+
+    /// Folds one chunked stream entry into the reassembly buffer,
+    /// returning the complete message body once its final chunk
+    /// arrives.
+    ///
+    /// Entries within a stream arrive in send order, so a sequence
+    /// gap means we lost chunks -- e.g. to trimming -- and the
+    /// partial body is discarded.
+    fn collect_chunk(
+        &mut self,
+        map: &HashMap<String, redis::Value>,
+        bytes: &[u8],
+    ) -> Result<Option<String>, String> {
+        let piece = match std::str::from_utf8(bytes) {
+            Ok(s) => s,
+            Err(e) => return Err(format!("{self} received non-utf8 data: {e}")),
+        };
+
+        let chunk_id = match map.get("chunk-id") {
+            Some(redis::Value::Data(b)) => String::from_utf8_lossy(b).to_string(),
+            _ => return Err(format!("{self} received chunk without a chunk-id")),
+        };
+
+        let seq = match map.get("chunk-seq") {
+            Some(redis::Value::Data(b)) => String::from_utf8_lossy(b).parse::<usize>().ok(),
+            _ => None,
+        }
+        .ok_or_else(|| format!("{self} received chunk without a sequence number"))?;
+
+        let expected = match self.chunks.get(&chunk_id) {
+            Some((_, next, _)) => *next,
+            None => 0,
+        };
+
+        if seq != expected {
+            warn!("{self} chunk-id={chunk_id} expected seq={expected} got seq={seq}; discarding partial");
+            self.chunks.remove(&chunk_id);
+            return Ok(None);
+        }
+
+        let entry = self
+            .chunks
+            .entry(chunk_id.clone())
+            .or_insert_with(|| (Instant::now(), 0, String::new()));
+
+        entry.1 += 1;
+        entry.2.push_str(piece);
+
+        if map.contains_key("chunk-final") {
+            // Unwrap is safe; the entry was just inserted/updated.
+            let (_, _, body) = self.chunks.remove(&chunk_id).unwrap();
+            return Ok(Some(body));
+        }
+
+        // Cap reassembly memory; a sender that died mid-message
+        // would otherwise leak its partial body forever.
+        while self.chunks.len() > CHUNK_BUFFER_MAX {
+            let oldest = self
+                .chunks
+                .iter()
+                .min_by_key(|(_, (start, _, _))| *start)
+                .map(|(key, _)| key.clone());
+
+            if let Some(key) = oldest {
+                warn!("{self} evicting stalled partial message chunk-id={key}");
+                self.chunks.remove(&key);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns at most one JSON value pulled from the stream.
+    pub fn recv_json_value(
+        &mut self,
+        timeout: Duration,
+        stream: Option<&str>,
+    ) -> Result<Option<json::JsonValue>, String> {
+        let stream = stream.unwrap_or(self.address.full()).to_string();
+
+        self.check_lag(&stream);
+
+        let json_string = match self.recv_one_chunk(timeout, &stream)? {
+            Some(s) => s,
+            None => return Ok(None),
+        };
+
+        match json::parse(&json_string) {
+            Ok(json_val) => Ok(Some(json_val)),
+            Err(e) => {
+                self.stats.serialization_errors += 1;
+                Err(format!("{self} received unparseable JSON: {e} : {json_string}"))
+            }
+        }
+    }
+
+    /// Returns at most one TransportMessage pulled from the stream.
+    pub fn recv(
+        &mut self,
+        timeout: Duration,
+        stream: Option<&str>,
+    ) -> Result<Option<TransportMessage>, String> {
+        let json_op = self.recv_json_value(timeout, stream)?;
+
+        match json_op {
+            Some(json_val) => match TransportMessage::from_json_value(json_val) {
+                Some(msg) => Ok(Some(msg)),
+                None => {
+                    self.stats.serialization_errors += 1;
+                    Err(format!("{self} received malformed TransportMessage"))
+                }
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Returns at most one TransportMessage for the provided
+    /// session thread, reading from our own stream.
+    ///
+    /// Messages for other threads are spooled internally and handed
+    /// out by later calls for their threads, keeping a busy shared
+    /// client from growing an unbounded mixed backlog: the spool is
+    /// bounded, shedding its oldest entries once full.
+    ///
+    /// Same timeout semantics as recv().
+    pub fn recv_for_thread(
+        &mut self,
+        timeout: Duration,
+        thread: &str,
+    ) -> Result<Option<TransportMessage>, String> {
+        if let Some(queue) = self.thread_spool.get_mut(thread) {
+            if let Some(tmsg) = queue.pop_front() {
+                self.thread_spool_count -= 1;
+                return Ok(Some(tmsg));
+            }
+        }
+
+        let timer = util::Timer::new(timeout);
+
+        loop {
+            let tmsg = match self.recv(timer.remaining(), None)? {
+                Some(t) => t,
+                None => return Ok(None),
+            };
+
+            if tmsg.thread() == thread {
+                return Ok(Some(tmsg));
+            }
+
+            trace!("{self} spooling message for thread {}", tmsg.thread());
+
+            if self.thread_spool_count >= THREAD_SPOOL_MAX {
+                self.shed_spooled_message();
+            }
+
+            self.thread_spool
+                .entry(tmsg.thread().to_string())
+                .or_default()
+                .push_back(tmsg);
+
+            self.thread_spool_count += 1;
+
+            if timer.done() {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Drops the oldest message from the fullest thread-spool
+    /// queue, on the theory that its session is the most likely to
+    /// have been abandoned.
+    fn shed_spooled_message(&mut self) {
+        let thread = match self
+            .thread_spool
+            .iter()
+            .max_by_key(|(_, queue)| queue.len())
+            .map(|(thread, _)| thread.clone())
+        {
+            Some(t) => t,
+            None => return,
+        };
+
+        warn!("{self} thread spool full; shedding a message for thread {thread}");
+
+        if let Some(queue) = self.thread_spool.get_mut(&thread) {
+            if queue.pop_front().is_some() {
+                self.thread_spool_count -= 1;
+            }
+
+            if queue.is_empty() {
+                self.thread_spool.remove(&thread);
+            }
+        }
+    }
+
+    /// Attempts to read several streams, returning the first message
+    /// that arrives along with the name of the stream it arrived on.
+    ///
+    /// Our consumer groups are named after their streams, and a
+    /// single XREADGROUP call can only span streams sharing one
+    /// group, so instead each stream gets a non-blocking read, then
+    /// we camp on the final stream -- for workers, the busy shared
+    /// service stream -- in short slices until the timeout expires.
+    ///
+    /// Same timeout semantics as recv().
+    pub fn recv_from_any(
+        &mut self,
+        timeout: Duration,
+        streams: &[&str],
+    ) -> Result<Option<(String, TransportMessage)>, String> {
+        let last = match streams.last() {
+            Some(s) => *s,
+            None => return Ok(None),
+        };
+
+        let timer = util::Timer::new(timeout);
+
+        loop {
+            for stream in streams {
+                if let Some(tmsg) = self.recv(Duration::ZERO, Some(stream))? {
+                    return Ok(Some((stream.to_string(), tmsg)));
+                }
+            }
+
+            if timer.done() {
+                return Ok(None);
+            }
+
+            let slice = std::cmp::min(timer.remaining(), MULTI_RECV_POLL);
+
+            if let Some(tmsg) = self.recv(slice, Some(last))? {
+                return Ok(Some((last.to_string(), tmsg)));
+            }
+        }
+    }
+
+    /// Arms the consumer-lag watchdog.
+    ///
+    /// Whenever the number of unread entries behind our consumer
+    /// group on a stream we're receiving from reaches the threshold,
+    /// a warning is logged and the callback, if any, is invoked, so
+    /// services can self-report when they can't keep up.
+    pub fn set_lag_alarm(&mut self, threshold: usize, callback: Option<LagAlarmCallback>) {
+        self.lag_alarm = Some((threshold, callback));
+    }
+
+    /// Measures consumer lag on the provided stream and fires the
+    /// alarm if the threshold is exceeded.
+    ///
+    /// Measurements are throttled so busy receive loops don't pay
+    /// for an XINFO on every read.
+    fn check_lag(&mut self, stream: &str) {
+        let (threshold, callback) = match self.lag_alarm {
+            Some(alarm) => alarm,
+            None => return,
+        };
+
+        if self.last_lag_check.elapsed() < LAG_CHECK_INTERVAL {
+            return;
+        }
+
+        self.last_lag_check = Instant::now();
+
+        let lag = match self.group_lag(stream) {
+            Ok(l) => l,
+            Err(e) => {
+                debug!("{self} cannot measure lag on stream={stream}: {e}");
+                return;
+            }
+        };
+
+        if lag >= threshold {
+            warn!("{self} consumer lag on stream={stream} is {lag} (threshold {threshold})");
+
+            if let Some(callback) = callback {
+                callback(stream, lag);
+            }
+        }
+    }
+
+    /// Returns how far behind our consumer group is on the provided
+    /// stream: pending entries plus entries not yet delivered.
+    pub fn group_lag(&mut self, stream: &str) -> Result<usize, String> {
+        let stream = &self.stream_key(stream);
+
+        let reply: redis::Value = match redis::cmd("XINFO")
+            .arg("GROUPS")
+            .arg(stream)
+            .query(self.connection())
+        {
+            Ok(r) => r,
+            Err(e) => return Err(format!("Error in group_lag(): {e}")),
+        };
+
+        let groups = match reply {
+            redis::Value::Bulk(groups) => groups,
+            _ => return Ok(0),
+        };
+
+        for group in groups {
+            let fields = match group {
+                redis::Value::Bulk(fields) => fields,
+                _ => continue,
+            };
+
+            let mut name_matches = false;
+            let mut lag: usize = 0;
+
+            // XINFO GROUPS replies are flat key/value lists.
+            for pair in fields.chunks(2) {
+                let key = match pair.first() {
+                    Some(redis::Value::Data(bytes)) => {
+                        String::from_utf8_lossy(bytes).to_string()
+                    }
+                    _ => continue,
+                };
+
+                match (key.as_str(), pair.get(1)) {
+                    // Group name == stream name per setup_stream().
+                    ("name", Some(redis::Value::Data(bytes))) => {
+                        name_matches = String::from_utf8_lossy(bytes) == *stream;
+                    }
+                    ("pending", Some(redis::Value::Int(count))) => {
+                        lag += *count as usize;
+                    }
+                    ("lag", Some(redis::Value::Int(count))) => {
+                        lag += *count as usize;
+                    }
+                    _ => {}
+                }
+            }
+
+            if name_matches {
+                return Ok(lag);
+            }
+        }
+
+        Ok(0)
+    }
+
+    /// Returns a health snapshot of the provided stream: queue
+    /// depth, consumer count, pending and undelivered entries, and
+    /// delivery progress.
+    ///
+    /// Lets tooling like buswatch and the router report on backlog
+    /// health without shelling out to redis-cli.
+    pub fn stream_info(&mut self, stream: &str) -> Result<StreamInfo, String> {
+        let stream = &self.stream_key(stream);
+
+        let mut info = StreamInfo::default();
+
+        let reply: redis::Value = match redis::cmd("XINFO")
+            .arg("STREAM")
+            .arg(stream)
+            .query(self.connection())
+        {
+            Ok(r) => r,
+            Err(e) => return Err(format!("Error in stream_info(): {e}")),
+        };
+
+        // XINFO STREAM replies are flat key/value lists.
+        if let redis::Value::Bulk(fields) = reply {
+            for pair in fields.chunks(2) {
+                let key = match pair.first() {
+                    Some(redis::Value::Data(bytes)) => {
+                        String::from_utf8_lossy(bytes).to_string()
+                    }
+                    _ => continue,
+                };
+
+                match (key.as_str(), pair.get(1)) {
+                    ("length", Some(redis::Value::Int(count))) => {
+                        info.length = *count as usize;
+                    }
+                    ("last-generated-id", Some(redis::Value::Data(bytes))) => {
+                        info.last_generated_id = String::from_utf8_lossy(bytes).to_string();
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let reply: redis::Value = match redis::cmd("XINFO")
+            .arg("GROUPS")
+            .arg(stream)
+            .query(self.connection())
+        {
+            Ok(r) => r,
+            Err(e) => return Err(format!("Error in stream_info(): {e}")),
+        };
+
+        let groups = match reply {
+            redis::Value::Bulk(groups) => groups,
+            _ => return Ok(info),
+        };
+
+        for group in groups {
+            let fields = match group {
+                redis::Value::Bulk(fields) => fields,
+                _ => continue,
+            };
+
+            let mut name_matches = false;
+            let mut consumer_count = 0;
+            let mut pending = 0;
+            let mut lag = 0;
+            let mut last_delivered_id = String::new();
+
+            for pair in fields.chunks(2) {
+                let key = match pair.first() {
+                    Some(redis::Value::Data(bytes)) => {
+                        String::from_utf8_lossy(bytes).to_string()
+                    }
+                    _ => continue,
+                };
+
+                match (key.as_str(), pair.get(1)) {
+                    // Group name == stream name per setup_stream().
+                    ("name", Some(redis::Value::Data(bytes))) => {
+                        name_matches = String::from_utf8_lossy(bytes) == *stream;
+                    }
+                    ("consumers", Some(redis::Value::Int(count))) => {
+                        consumer_count = *count as usize;
+                    }
+                    ("pending", Some(redis::Value::Int(count))) => {
+                        pending = *count as usize;
+                    }
+                    ("lag", Some(redis::Value::Int(count))) => {
+                        lag = *count as usize;
+                    }
+                    ("last-delivered-id", Some(redis::Value::Data(bytes))) => {
+                        last_delivered_id = String::from_utf8_lossy(bytes).to_string();
+                    }
+                    _ => {}
+                }
+            }
+
+            if name_matches {
+                info.consumer_count = consumer_count;
+                info.pending = pending;
+                info.lag = lag;
+                info.last_delivered_id = last_delivered_id;
+                break;
+            }
+        }
+
+        Ok(info)
+    }
+
+    /// Returns the consumers of the provided stream's group, so
+    /// operators can see per-worker pending counts and idle time.
+    pub fn consumer_info(&mut self, stream: &str) -> Result<Vec<ConsumerInfo>, String> {
+        let stream = &self.stream_key(stream);
+
+        let reply: redis::Value = match redis::cmd("XINFO")
+            .arg("CONSUMERS")
+            .arg(stream)
+            .arg(stream) // group name == stream name
+            .query(self.connection())
+        {
+            Ok(r) => r,
+            Err(e) => return Err(format!("Error in consumer_info(): {e}")),
+        };
+
+        let consumers = match reply {
+            redis::Value::Bulk(consumers) => consumers,
+            _ => return Ok(Vec::new()),
+        };
+
+        let mut list = Vec::new();
+
+        for consumer in consumers {
+            let fields = match consumer {
+                redis::Value::Bulk(fields) => fields,
+                _ => continue,
+            };
+
+            let mut info = ConsumerInfo::default();
+
+            // XINFO CONSUMERS replies are flat key/value lists.
+            for pair in fields.chunks(2) {
+                let key = match pair.first() {
+                    Some(redis::Value::Data(bytes)) => {
+                        String::from_utf8_lossy(bytes).to_string()
+                    }
+                    _ => continue,
+                };
+
+                match (key.as_str(), pair.get(1)) {
+                    ("name", Some(redis::Value::Data(bytes))) => {
+                        info.name = String::from_utf8_lossy(bytes).to_string();
+                    }
+                    ("pending", Some(redis::Value::Int(count))) => {
+                        info.pending = *count as usize;
+                    }
+                    ("idle", Some(redis::Value::Int(ms))) => {
+                        info.idle = Duration::from_millis(*ms as u64);
+                    }
+                    _ => {}
+                }
+            }
+
+            list.push(info);
+        }
+
+        Ok(list)
+    }
+
+    /// Sends a TransportMessage to its "to" address.
+    pub fn send(&mut self, msg: &TransportMessage) -> Result<(), String> {
+        self.send_to(msg, msg.to())
+    }
+
+    /// Sends a TransportMessage to the provided stream, regardless
+    /// of its "to" address.
+    ///
+    /// Retryable errors are retried up to max_send_retries times
+    /// with jittered exponential backoff; connection losses also
+    /// trigger a reconnect first.  Permanent errors -- bad
+    /// arguments, wrong key types -- fail immediately.
+    ///
+    /// XADD offers no idempotent form, so a send whose reply was
+    /// lost in transit may be delivered twice when retried;
+    /// receivers that care dedupe via the message idempotency key.
+    ///
+    /// Messages larger than max_chunk_size, when set, are split
+    /// into multiple stream entries and reassembled by the
+    /// receiving Bus; see set_max_chunk_size().
+    pub fn send_to(&mut self, msg: &TransportMessage, recipient: &str) -> Result<(), String> {
+        let json_str = msg.to_json_value().dump();
+
+        trace!("{self} sending to={recipient}: {json_str}");
+
+        let policy = match self.stream_trim_overrides.get(recipient) {
+            Some(p) => *p,
+            None => self.trim_policy,
+        };
+
+        if let Some((limit, wait)) = self.send_backpressure {
+            self.await_queue_room(recipient, limit, wait)?;
+        }
+
+        let recipient = &self.stream_key(recipient);
+
+        match self.max_chunk_size {
+            Some(size) if json_str.len() > size => {
+                let chunk_id = util::random_number(12);
+                let pieces = chunk_str(&json_str, size);
+                let last = pieces.len() - 1;
+
+                for (seq, piece) in pieces.iter().enumerate() {
+                    let seq_str = seq.to_string();
+
+                    let mut fields = vec![
+                        ("chunk", *piece),
+                        ("chunk-id", chunk_id.as_str()),
+                        ("chunk-seq", seq_str.as_str()),
+                    ];
+
+                    if seq == last {
+                        fields.push(("chunk-final", "1"));
+                    }
+
+                    self.xadd_with_retry(recipient, policy, &fields)?;
+                }
+            }
+            _ => self.xadd_with_retry(recipient, policy, &[("message", &json_str)])?,
+        }
+
+        self.stats.msgs_sent += 1;
+        self.stats.bytes_sent += json_str.len();
+
+        Ok(())
+    }
+
+    /// Adds one entry to a stream, retrying retryable errors with
+    /// jittered exponential backoff.
+    fn xadd_with_retry(
+        &mut self,
+        stream: &str,
+        policy: conf::TrimPolicy,
+        fields: &[(&str, &str)],
+    ) -> Result<(), String> {
+        let mut delay = SEND_RETRY_BASE_DELAY;
+        let mut last_err = String::new();
+
+        for attempt in 0..=self.max_send_retries {
+            if attempt > 0 {
+                // Jitter the delay by +/- 50% so senders that
+                // failed together don't retry in lockstep.
+                let jittered = delay.mul_f64(rand::thread_rng().gen_range(0.5..1.5));
+
+                warn!(
+                    "{self} send retry {attempt} of {} in {jittered:?}",
+                    self.max_send_retries
+                );
+
+                std::thread::sleep(jittered);
+                delay = (delay * 2).min(SEND_RETRY_MAX_DELAY);
+            }
+
+            let err = match self.xadd(stream, policy, fields) {
+                Ok(_) => return Ok(()),
+                Err(e) => e,
+            };
+
+            self.stats.redis_errors += 1;
+
+            if Bus::is_connection_error(&err) {
+                warn!("{self} lost connection during send: {err}");
+                self.reconnect()?;
+            } else if !Bus::is_retryable_error(&err) {
+                return Err(format!("Error in send() {err}"));
+            }
+
+            last_err = err.to_string();
+        }
+
+        Err(format!(
+            "{self} send failed after {} retries: {last_err}",
+            self.max_send_retries
+        ))
+    }
+
+    /// Publishes an event to a broadcast topic stream.
+    ///
+    /// Unlike service streams, topic streams have no consumer
+    /// group; every subscriber sees every event.  Events are
+    /// trimmed per our trim policy, so slow subscribers eventually
+    /// lose old events rather than growing the stream forever.
+    pub fn publish(&mut self, topic: &str, value: &json::JsonValue) -> Result<(), String> {
+        let stream = self.stream_key(&topic_stream(topic));
+        let json_str = value.dump();
+
+        trace!("{self} publishing to topic={topic}: {json_str}");
+
+        let policy = self.trim_policy;
+        self.xadd_with_retry(&stream, policy, &[("event", &json_str)])?;
+
+        self.stats.msgs_sent += 1;
+        self.stats.bytes_sent += json_str.len();
+
+        Ok(())
+    }
+
+    /// Subscribes to a broadcast topic.
+    ///
+    /// Events published after this call are returned by
+    /// recv_topic(); prior history is skipped.
+    pub fn subscribe(&mut self, topic: &str) -> Result<(), String> {
+        if self.subscriptions.contains_key(topic) {
+            return Ok(());
+        }
+
+        let stream = self.stream_key(&topic_stream(topic));
+
+        // Start the cursor at the newest existing entry so only
+        // events published from now on are delivered.
+        let reply: redis::Value = redis::cmd("XREVRANGE")
+            .arg(&stream)
+            .arg("+")
+            .arg("-")
+            .arg("COUNT")
+            .arg(1)
+            .query(self.connection())
+            .map_err(|e| format!("Error subscribing to topic={topic}: {e}"))?;
+
+        let last_id = match reply {
+            redis::Value::Bulk(entries) => entries.first().and_then(|entry| match entry {
+                redis::Value::Bulk(parts) => match parts.first() {
+                    Some(redis::Value::Data(bytes)) => {
+                        Some(String::from_utf8_lossy(bytes).to_string())
+                    }
+                    _ => None,
+                },
+                _ => None,
+            }),
+            _ => None,
+        }
+        .unwrap_or_else(|| "0-0".to_string());
+
+        debug!("{self} subscribed to topic={topic} from id={last_id}");
+
+        self.subscriptions.insert(topic.to_string(), last_id);
+
+        Ok(())
+    }
+
+    /// Drops a topic subscription.
+    pub fn unsubscribe(&mut self, topic: &str) {
+        self.subscriptions.remove(topic);
+    }
+
+    /// Drops all topic subscriptions.
+    pub fn unsubscribe_all(&mut self) {
+        self.subscriptions.clear();
+    }
+
+    /// Returns the next event published to a subscribed topic.
+    ///
+    /// Same timeout semantics as recv(): zero returns immediately
+    /// if no events are waiting, Duration::MAX blocks
+    /// indefinitely.
+    pub fn recv_topic(
+        &mut self,
+        timeout: Duration,
+        topic: &str,
+    ) -> Result<Option<json::JsonValue>, String> {
+        let last_id = match self.subscriptions.get(topic) {
+            Some(id) => id.clone(),
+            None => return Err(format!("{self} not subscribed to topic={topic}")),
+        };
+
+        let stream = self.stream_key(&topic_stream(topic));
+
+        let mut read_opts = StreamReadOptions::default().count(1);
+
+        if !timeout.is_zero() {
+            if timeout == Duration::MAX {
+                // block() of 0 means block indefinitely.
+                read_opts = read_opts.block(0);
+            } else {
+                // BLOCK 0 means forever, so floor at one ms.
+                read_opts = read_opts.block(std::cmp::max(timeout.as_millis() as usize, 1));
+            }
+        }
+
+        let reply: StreamReadReply = self
+            .connection()
+            .xread_options(&[&stream], &[&last_id], &read_opts)
+            .map_err(|e| format!("{self} recv_topic error: {e}"))?;
+
+        for stream_key in reply.keys {
+            for entry in stream_key.ids {
+                self.subscriptions.insert(topic.to_string(), entry.id.clone());
+
+                if let Some(redis::Value::Data(bytes)) = entry.map.get("event") {
+                    let json_string = String::from_utf8(bytes.to_vec())
+                        .map_err(|e| format!("{self} received non-utf8 data: {e}"))?;
+
+                    trace!("{self} topic={topic} event: {json_string}");
+
+                    self.stats.msgs_received += 1;
+                    self.stats.bytes_received += json_string.len();
+
+                    return json::parse(&json_string).map(Some).map_err(|e| {
+                        format!("{self} received unparseable JSON: {e} : {json_string}")
+                    });
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Adds one message to a stream, trimming it per the provided
+    /// policy.
+    ///
+    /// Each entry is stamped with its send time so receivers can
+    /// discard messages past the max message age.
+    fn xadd(
+        &mut self,
+        stream: &str,
+        policy: conf::TrimPolicy,
+        fields: &[(&str, &str)],
+    ) -> Result<String, redis::RedisError> {
+        let ts = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+            .to_string();
+
+        let maxlen = match policy {
+            conf::TrimPolicy::MaxlenApprox(n) => StreamMaxlen::Approx(n),
+            conf::TrimPolicy::MaxlenExact(n) => StreamMaxlen::Equals(n),
+            conf::TrimPolicy::MaxAge(secs) => {
+                // Entry ids are millisecond timestamps; trim
+                // everything older than the cutoff.
+                let cutoff = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|d| d.as_millis() as u64)
+                    .unwrap_or(0)
+                    .saturating_sub(secs * 1000);
+
+                let mut cmd = redis::cmd("XADD");
+
+                cmd.arg(stream).arg("MINID").arg("~").arg(cutoff).arg("*");
+
+                for (key, value) in fields {
+                    cmd.arg(key).arg(value);
+                }
+
+                return cmd.arg("ts").arg(&ts).query(self.connection());
+            }
+        };
+
+        let mut fields = fields.to_vec();
+        fields.push(("ts", &ts));
+
+        self.connection().xadd_maxlen(stream, maxlen, "*", &fields)
+    }
+
+    /// Removes entries older than the provided age from the
+    /// requested stream, returning the number trimmed.
+    ///
+    /// Used by buswatch to enforce the max message age on streams
+    /// whose consumers have vanished.
+    pub fn trim_stream_older_than(&mut self, stream: &str, age: Duration) -> Result<i32, String> {
+        let stream = self.stream_key(stream);
+
+        // Entry ids are millisecond timestamps; trim everything
+        // older than the cutoff.
+        let cutoff = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0)
+            .saturating_sub(age.as_millis() as u64);
+
+        let res: Result<i32, _> = redis::cmd("XTRIM")
+            .arg(&stream)
+            .arg("MINID")
+            .arg(cutoff)
+            .query(self.connection());
+
+        match res {
+            Ok(n) => Ok(n),
+            Err(e) => Err(format!("Error in trim_stream_older_than(): {e}")),
+        }
+    }
+
+    /// Removes all pending entries from our stream.
+    pub fn clear_stream(&mut self) -> Result<(), String> {
+        let sname = self.stream_key(self.address.full());
+
+        self.unread.remove(&sname);
+        self.thread_spool.clear();
+        self.thread_spool_count = 0;
+        self.chunks.clear();
+
+        let res: Result<i32, _> = self.connection().xtrim(&sname, StreamMaxlen::Equals(0));
+
+        if let Err(e) = res {
+            return Err(format!("Error in clear_stream(): {e}"));
+        }
+
+        Ok(())
+    }
+
+    /// Removes the provided stream and its consumer group entirely,
+    /// defaulting to our bus address.
+    pub fn delete_stream(&mut self, name: Option<&str>) -> Result<(), String> {
+        let sname = self.stream_key(name.unwrap_or(self.address.full()));
+
+        self.unread.remove(&sname);
+
+        debug!("{self} deleting stream");
+
+        let res: Result<i32, _> = self.connection().del(&sname);
+
+        if let Err(e) = res {
+            return Err(format!("Error in delete_stream(): {e}"));
+        }
+
+        Ok(())
+    }
+
+    /// Removes our stream, dropping any unprocessed messages.
+    pub fn disconnect(&mut self) -> Result<(), String> {
+        self.delete_stream(None)
+    }
+
+    /// Drains our stream, then removes it.
+    ///
+    /// Queued entries are re-routed to the provided stream -- e.g.
+    /// the service-level stream, so another worker picks them up --
+    /// or dropped when None.  Callers should stop new deliveries
+    /// first, e.g. by unregistering from the router.  Used in place
+    /// of disconnect() during graceful shutdown.
+    pub fn drain(&mut self, reroute_to: Option<&str>) -> Result<(), String> {
+        let mut rerouted = 0;
+        let mut dropped = 0;
+
+        while let Some(tmsg) = self.recv(Duration::ZERO, None)? {
+            match reroute_to {
+                Some(stream) => {
+                    self.send_to(&tmsg, stream)?;
+
+                    if self.reliable {
+                        self.ack_last()?;
+                    }
+
+                    rerouted += 1;
+                }
+                None => dropped += 1,
+            }
+        }
+
+        if rerouted > 0 || dropped > 0 {
+            debug!("{self} drained stream: rerouted={rerouted} dropped={dropped}");
+        }
+
+        self.delete_stream(None)
+    }
+
+    /// Schedules a message for future delivery.
+    ///
+    /// The message lands in the shared schedule sorted set, scored
+    /// by its delivery time, and is moved to its destination stream
+    /// by whichever process drains the schedule (normally the
+    /// router).
+    pub fn schedule_send(&mut self, tmsg: &TransportMessage, deliver_at: u64) -> Result<(), String> {
+        // A random id keeps identical messages from collapsing into
+        // one sorted set member.
+        let member = json::object! {
+            id: util::random_number(12),
+            msg: tmsg.to_json_value(),
+        }
+        .dump();
+
+        trace!("{self} scheduling message for delivery at {deliver_at}");
+
+        let res: Result<i32, _> = self
+            .connection()
+            .zadd(schedule_key(), member, deliver_at as f64);
+
+        if let Err(e) = res {
+            return Err(format!("Error in schedule_send(): {e}"));
+        }
+
+        Ok(())
+    }
+
+    /// Removes and returns all scheduled messages that are now due
+    /// for delivery, up to a sane batch size.
+    pub fn drain_schedule(&mut self) -> Result<Vec<TransportMessage>, String> {
+        let now = match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as f64,
+            Err(e) => return Err(format!("System clock error: {e}")),
+        };
+
+        let skey = schedule_key();
+
+        let members: Vec<String> = match self.connection().zrangebyscore_limit(
+            &skey,
+            "-inf",
+            now,
+            0,
+            SCHEDULE_BATCH_SIZE,
+        ) {
+            Ok(m) => m,
+            Err(e) => return Err(format!("Error in drain_schedule(): {e}")),
+        };
+
+        let mut messages = Vec::new();
+
+        for member in members {
+            // Claim the member; another drainer may have beat us to it.
+            let removed: i32 = match self.connection().zrem(&skey, &member) {
+                Ok(r) => r,
+                Err(e) => return Err(format!("Error in drain_schedule(): {e}")),
+            };
+
+            if removed == 0 {
+                continue;
+            }
+
+            let wrapper = match json::parse(&member) {
+                Ok(w) => w,
+                Err(e) => {
+                    error!("{self} dropping unparseable scheduled message: {e}");
+                    continue;
+                }
+            };
+
+            match TransportMessage::from_json_value(wrapper["msg"].clone()) {
+                Some(tmsg) => messages.push(tmsg),
+                None => error!("{self} dropping malformed scheduled message"),
+            }
+        }
+
+        Ok(messages)
+    }
+
+    /// Returns all keys matching the requested pattern.
+    ///
+    /// Primarily for use by tooling like buswatch.
+    pub fn keys(&mut self, pattern: &str) -> Result<Vec<String>, String> {
+        let res: Result<Vec<String>, _> = self.connection().keys(pattern);
+
+        match res {
+            Ok(keys) => Ok(keys),
+            Err(e) => Err(format!("Error in keys(): {e}")),
+        }
+    }
+
+    /// Returns the number of entries in the requested stream.
+    pub fn xlen(&mut self, key: &str) -> Result<i32, String> {
+        let key = self.stream_key(key);
+        let res: Result<i32, _> = self.connection().xlen(key);
+
+        match res {
+            Ok(l) => Ok(l),
+            Err(e) => Err(format!("Error in xlen(): {e}")),
+        }
+    }
+
+    /// True if the provided stream exists on the bus.
+    pub fn stream_exists(&mut self, stream: &str) -> Result<bool, String> {
+        let key = self.stream_key(stream);
+        let res: Result<bool, _> = self.connection().exists(key);
+
+        match res {
+            Ok(b) => Ok(b),
+            Err(e) => Err(format!("Error in stream_exists(): {e}")),
+        }
+    }
+
+    /// Sets a timeout (in seconds) on the requested key.
+    pub fn set_key_timeout(&mut self, key: &str, timeout: i64) -> Result<i32, String> {
+        let res: Result<i32, _> = self.connection().expire(key, timeout);
+
+        match res {
+            Ok(v) => Ok(v),
+            Err(e) => Err(format!("Error in set_key_timeout(): {e}")),
+        }
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        if let Err(e) = self.disconnect() {
+            error!("{self} error during disconnect: {e}");
+        }
+    }
+}
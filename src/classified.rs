@@ -0,0 +1,90 @@
+use json::JsonValue;
+
+const JSON_CLASS_KEY: &str = "__c";
+const JSON_PAYLOAD_KEY: &str = "__p";
+
+/// Trait for plugging alternate pack/unpack logic into the data
+/// portion of OpenSRF messages, e.g. Evergreen Fieldmapper handling.
+pub trait DataSerializer {
+    /// Translate a pre-send value into its over-the-wire form.
+    fn pack(&self, value: &JsonValue) -> JsonValue;
+
+    /// Translate a just-received wire value into its local form.
+    fn unpack(&self, value: &JsonValue) -> JsonValue;
+}
+
+/// A JSON value whose class designation has been stripped off and
+/// stored separately, i.e. {"__c": <class>, "__p": <payload>}.
+pub struct ClassifiedJson {
+    json: JsonValue,
+    class: String,
+}
+
+impl ClassifiedJson {
+    pub fn json(&self) -> &JsonValue {
+        &self.json
+    }
+
+    /// Takes ownership of the contained JSON value.
+    pub fn take_json(self) -> JsonValue {
+        self.json
+    }
+
+    pub fn class(&self) -> &str {
+        &self.class
+    }
+
+    /// True if the provided value looks like a classed value.
+    pub fn can_declassify(obj: &JsonValue) -> bool {
+        obj.is_object()
+            && obj.has_key(JSON_CLASS_KEY)
+            && obj.has_key(JSON_PAYLOAD_KEY)
+            && obj[JSON_CLASS_KEY].is_string()
+    }
+
+    /// Turns a JSON value into a ClassifiedJson if it's a hash
+    /// with class and payload keys.
+    pub fn declassify(mut obj: JsonValue) -> Option<ClassifiedJson> {
+        if ClassifiedJson::can_declassify(&obj) {
+            Some(ClassifiedJson {
+                class: obj[JSON_CLASS_KEY].as_str().unwrap().to_string(),
+                json: obj[JSON_PAYLOAD_KEY].take(),
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Wraps a JSON value in a class and payload envelope.
+    pub fn classify(json: JsonValue, class: &str) -> JsonValue {
+        let mut hash = json::object! {};
+
+        hash.insert(JSON_CLASS_KEY, class).ok();
+        hash.insert(JSON_PAYLOAD_KEY, json).ok();
+
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_round_trip() {
+        let v = json::object! {hello: "errbody"};
+        let classed = ClassifiedJson::classify(v, "aClass");
+
+        assert!(ClassifiedJson::can_declassify(&classed));
+
+        let declassed = ClassifiedJson::declassify(classed).unwrap();
+        assert_eq!(declassed.class(), "aClass");
+        assert_eq!(declassed.json()["hello"], "errbody");
+    }
+
+    #[test]
+    fn test_cannot_declassify() {
+        assert!(!ClassifiedJson::can_declassify(&json::object! {foo: "bar"}));
+        assert!(ClassifiedJson::declassify(json::array! [1, 2]).is_none());
+    }
+}
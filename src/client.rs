@@ -0,0 +1,1744 @@
+use super::addr::{ClientAddress, RouterAddress};
+use super::addr::ServiceAddress;
+use super::bus;
+use super::classified::DataSerializer;
+use super::conf;
+use super::message;
+use super::message::TransportMessage;
+use super::params::ApiParams;
+use super::session::{Session, SessionHandle, SessionPool};
+use super::util;
+use json::JsonValue;
+use log::{debug, error, info, trace, warn};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::{Rc, Weak};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Remote-node connections unused this long are closed; see
+/// evict_idle_connections().
+const REMOTE_BUS_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Default cap on concurrently open remote-node connections; see
+/// ClientSingleton::set_max_remote_connections().
+const DEFAULT_MAX_REMOTE_CONNECTIONS: usize = 8;
+
+/// Default cap on backlogged messages; see
+/// ClientSingleton::set_backlog_limits().
+const DEFAULT_BACKLOG_MAX_SIZE: usize = 1024;
+
+/// Default age beyond which backlogged messages are evicted; see
+/// ClientSingleton::set_backlog_limits().
+const DEFAULT_BACKLOG_MAX_AGE: Duration = Duration::from_secs(300);
+
+/// Bound on the in-memory offline buffer when none is provided.
+const DEFAULT_OFFLINE_BUFFER_MAX: usize = 1024;
+
+/// Invoked when a watched service registers (up=true) or loses its
+/// last controller (up=false).
+pub type ServiceWatchCallback = Box<dyn Fn(&str, bool)>;
+
+/// Upper bounds for the call latency histogram buckets; a final
+/// overflow bucket catches anything slower.
+const LATENCY_BUCKETS: [Duration; 6] = [
+    Duration::from_millis(10),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+];
+
+/// Counters and a latency histogram for one call target.
+#[derive(Default)]
+struct CallStats {
+    /// Requests issued.
+    calls: usize,
+
+    /// Requests that have produced at least one response.
+    responses: usize,
+
+    /// Cumulative time to first response.
+    total_time: Duration,
+
+    /// Slowest time to first response.
+    max_time: Duration,
+
+    /// Time-to-first-response counts per LATENCY_BUCKETS entry,
+    /// plus a trailing overflow bucket.
+    buckets: [usize; LATENCY_BUCKETS.len() + 1],
+}
+
+impl CallStats {
+    fn record_latency(&mut self, elapsed: Duration) {
+        self.responses += 1;
+        self.total_time += elapsed;
+        self.max_time = std::cmp::max(self.max_time, elapsed);
+
+        let index = LATENCY_BUCKETS
+            .iter()
+            .position(|b| elapsed <= *b)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        self.buckets[index] += 1;
+    }
+
+    fn to_json_value(&self) -> json::JsonValue {
+        let mut histogram = json::JsonValue::new_object();
+
+        for (bound, count) in LATENCY_BUCKETS.iter().zip(self.buckets.iter()) {
+            histogram.insert(&format!("le_{}ms", bound.as_millis()), *count).ok();
+        }
+
+        histogram
+            .insert("overflow", self.buckets[LATENCY_BUCKETS.len()])
+            .ok();
+
+        let avg_ms = (self.total_time.as_millis() as usize)
+            .checked_div(self.responses)
+            .unwrap_or(0);
+
+        json::object! {
+            calls: self.calls,
+            responses: self.responses,
+            avg_ms: avg_ms,
+            max_ms: self.max_time.as_millis() as usize,
+            latency: histogram,
+        }
+    }
+}
+
+/// Call counts and time-to-first-response latency, tracked per
+/// service and per method; see Client::stats().
+///
+/// Makes slow downstream services visible without instrumenting
+/// individual call sites.
+#[derive(Default)]
+pub struct ClientStats {
+    services: HashMap<String, CallStats>,
+    methods: HashMap<String, CallStats>,
+}
+
+impl ClientStats {
+    fn record_call(&mut self, service: &str, method: &str) {
+        self.services.entry(service.to_string()).or_default().calls += 1;
+        self.methods.entry(method.to_string()).or_default().calls += 1;
+    }
+
+    fn record_latency(&mut self, service: &str, method: &str, elapsed: Duration) {
+        self.services
+            .entry(service.to_string())
+            .or_default()
+            .record_latency(elapsed);
+
+        self.methods
+            .entry(method.to_string())
+            .or_default()
+            .record_latency(elapsed);
+    }
+
+    pub fn to_json_value(&self) -> json::JsonValue {
+        let mut services = json::JsonValue::new_object();
+        for (name, stats) in self.services.iter() {
+            services.insert(name, stats.to_json_value()).ok();
+        }
+
+        let mut methods = json::JsonValue::new_object();
+        for (name, stats) in self.methods.iter() {
+            methods.insert(name, stats.to_json_value()).ok();
+        }
+
+        json::object! {
+            services: services,
+            methods: methods,
+        }
+    }
+
+    /// Resets all counters.
+    pub fn clear(&mut self) {
+        self.services.clear();
+        self.methods.clear();
+    }
+}
+
+/// One registered listener for a service, as reported by a router
+/// summary.
+pub struct RouterController {
+    address: String,
+    register_time: u64,
+}
+
+impl RouterController {
+    /// The listener's bus address.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// When the listener registered, in epoch seconds.
+    pub fn register_time(&self) -> u64 {
+        self.register_time
+    }
+
+    fn from_json_value(value: &json::JsonValue) -> Result<RouterController, String> {
+        let address = value["address"]
+            .as_str()
+            .ok_or_else(|| format!("Router controller has no address: {value}"))?;
+
+        Ok(RouterController {
+            address: address.to_string(),
+            register_time: value["register_time"].as_u64().unwrap_or(0),
+        })
+    }
+}
+
+/// One service and its registered controllers, as reported by a
+/// router summary.
+pub struct RouterService {
+    name: String,
+    controllers: Vec<RouterController>,
+}
+
+impl RouterService {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn controllers(&self) -> &Vec<RouterController> {
+        &self.controllers
+    }
+
+    fn from_json_value(value: &json::JsonValue) -> Result<RouterService, String> {
+        let name = value["name"]
+            .as_str()
+            .ok_or_else(|| format!("Router service has no name: {value}"))?;
+
+        let mut controllers = Vec::new();
+
+        for controller in value["controllers"].members() {
+            controllers.push(RouterController::from_json_value(controller)?);
+        }
+
+        Ok(RouterService {
+            name: name.to_string(),
+            controllers,
+        })
+    }
+}
+
+/// A router's registration state, parsed from its "summarize"
+/// command reply; see Client::router_summary().
+pub struct RouterSummary {
+    domain: String,
+    services: Vec<RouterService>,
+}
+
+impl RouterSummary {
+    /// The domain the router is listening on.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn services(&self) -> &Vec<RouterService> {
+        &self.services
+    }
+
+    fn from_json_value(value: &json::JsonValue) -> Result<RouterSummary, String> {
+        let domain = value["domain"]
+            .as_str()
+            .ok_or_else(|| format!("Router summary has no domain: {value}"))?;
+
+        let mut services = Vec::new();
+
+        for service in value["services"].members() {
+            services.push(RouterService::from_json_value(service)?);
+        }
+
+        Ok(RouterSummary {
+            domain: domain.to_string(),
+            services,
+        })
+    }
+}
+
+/// Hooks into the client's message flow; see
+/// Client::add_middleware().
+///
+/// Hooks see whole TransportMessages, so one implementation can
+/// time requests, inject auth tokens, or record custom metrics
+/// without changes to the session layer.
+pub trait ClientMiddleware {
+    /// Invoked just before an outbound message is sent; changes
+    /// made here go out on the wire.
+    fn pre_send(&self, _tmsg: &mut TransportMessage) {}
+
+    /// Invoked for each inbound message as it's pulled off the
+    /// bus, before it's routed to its session.
+    fn post_receive(&self, _tmsg: &mut TransportMessage) {}
+}
+
+/// The underlying, shared client state.
+///
+/// Wrapped in an Rc/RefCell by Client so the client may be cloned
+/// and handed to multiple sessions within a thread.
+pub struct ClientSingleton {
+    config: Arc<conf::Config>,
+
+    /// Domain of our primary bus connection.
+    domain: String,
+
+    /// Connection to our primary bus node.
+    bus: bus::Bus,
+
+    /// Connections to remote bus nodes, keyed by domain, along
+    /// with the time each was last used so idle ones can be
+    /// evicted.
+    remote_bus_map: HashMap<String, (bus::Bus, Instant)>,
+
+    /// Cap on concurrently open remote-node connections.
+    max_remote_connections: usize,
+
+    /// In-memory queue of messages awaiting a bus reconnect; None
+    /// until offline buffering is enabled.
+    offline_buffer: Option<Vec<TransportMessage>>,
+
+    /// Cap on the offline buffer; oldest messages are dropped once
+    /// it fills.
+    offline_buffer_max: usize,
+
+    /// Messages dropped from a full offline buffer.
+    offline_buffer_dropped: usize,
+
+    /// Weak handles to the sessions created through this client,
+    /// so shutdown() can disconnect any still-connected
+    /// conversations.  Dead entries are pruned as sessions are
+    /// added.
+    sessions: Vec<Weak<RefCell<Session>>>,
+
+    /// Transport messages that have been pulled off the bus but not
+    /// yet claimed by their sessions, each paired with its arrival
+    /// time.
+    backlog: Vec<(Instant, TransportMessage)>,
+
+    /// Caps how many messages the backlog may hold.
+    backlog_max_size: usize,
+
+    /// Service streams confirmed to exist on the bus, so direct
+    /// delivery skips the existence probe after the first send.
+    live_service_streams: HashSet<String>,
+
+    /// Backlogged messages older than this are evicted; they are
+    /// orphaned replies whose request gave up waiting.
+    backlog_max_age: Duration,
+
+    /// Messages evicted from the backlog since this client
+    /// connected.
+    backlog_dropped: usize,
+
+    /// Default locale applied to outbound requests; see
+    /// Client::set_locale().
+    locale: Option<String>,
+
+    /// Per-service and per-method call metrics.
+    stats: ClientStats,
+
+    /// When set, call metrics are logged at most this often; see
+    /// set_stats_log_interval().
+    stats_log_interval: Option<Duration>,
+
+    /// When metrics were last logged.
+    stats_last_logged: Instant,
+
+    /// Optional pack/unpack layer for message content.
+    serializer: Option<Arc<dyn DataSerializer>>,
+
+    /// Hooks applied to outbound and inbound messages, in
+    /// installation order.
+    middleware: Vec<Arc<dyn ClientMiddleware>>,
+
+    /// Callbacks for service up/down events, keyed by service name.
+    service_watchers: HashMap<String, Vec<ServiceWatchCallback>>,
+
+    /// When set, fire-and-forget messages that cannot reach the bus
+    /// are persisted here and flushed once the bus is reachable
+    /// again; see set_offline_queue().
+    offline_queue: Option<PathBuf>,
+}
+
+impl fmt::Display for ClientSingleton {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Client({})", self.bus.address().full())
+    }
+}
+
+impl ClientSingleton {
+    fn new(config: Arc<conf::Config>) -> Result<ClientSingleton, String> {
+        let con = config
+            .primary_connection()
+            .expect("Client::connect() requires a primary bus connection");
+
+        let bus = bus::Bus::new(con)?;
+        let domain = con.domain().to_string();
+
+        Ok(ClientSingleton {
+            config,
+            domain,
+            bus,
+            remote_bus_map: HashMap::new(),
+            max_remote_connections: DEFAULT_MAX_REMOTE_CONNECTIONS,
+            offline_buffer: None,
+            offline_buffer_max: DEFAULT_OFFLINE_BUFFER_MAX,
+            offline_buffer_dropped: 0,
+            sessions: Vec::new(),
+            backlog: Vec::new(),
+            backlog_max_size: DEFAULT_BACKLOG_MAX_SIZE,
+            live_service_streams: HashSet::new(),
+            backlog_max_age: DEFAULT_BACKLOG_MAX_AGE,
+            backlog_dropped: 0,
+            locale: None,
+            stats: ClientStats::default(),
+            stats_log_interval: None,
+            stats_last_logged: Instant::now(),
+            serializer: None,
+            middleware: Vec::new(),
+            service_watchers: HashMap::new(),
+            offline_queue: None,
+        })
+    }
+
+    pub fn config(&self) -> &Arc<conf::Config> {
+        &self.config
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn address(&self) -> &ClientAddress {
+        self.bus.address()
+    }
+
+    pub fn bus(&self) -> &bus::Bus {
+        &self.bus
+    }
+
+    pub fn bus_mut(&mut self) -> &mut bus::Bus {
+        &mut self.bus
+    }
+
+    pub fn serializer(&self) -> Option<&Arc<dyn DataSerializer>> {
+        self.serializer.as_ref()
+    }
+
+    /// Installs a middleware hook; hooks run in installation order.
+    pub fn add_middleware(&mut self, middleware: Arc<dyn ClientMiddleware>) {
+        self.middleware.push(middleware);
+    }
+
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    pub fn set_locale(&mut self, locale: &str) {
+        self.locale = Some(locale.to_string());
+    }
+
+    /// Call metrics accumulated since this client connected or
+    /// the stats were last cleared.
+    pub fn stats(&self) -> &ClientStats {
+        &self.stats
+    }
+
+    pub fn clear_stats(&mut self) {
+        self.stats.clear();
+    }
+
+    /// Logs the call metrics (at info level) at most this often,
+    /// piggybacking on request activity.  None disables logging.
+    pub fn set_stats_log_interval(&mut self, interval: Option<Duration>) {
+        self.stats_log_interval = interval;
+    }
+
+    /// Counts one issued request.
+    pub(crate) fn record_call(&mut self, service: &str, method: &str) {
+        self.stats.record_call(service, method);
+    }
+
+    /// Records the time from request submission to its first
+    /// response, logging the accumulated metrics when due.
+    pub(crate) fn record_latency(&mut self, service: &str, method: &str, elapsed: Duration) {
+        self.stats.record_latency(service, method, elapsed);
+
+        if let Some(interval) = self.stats_log_interval {
+            if self.stats_last_logged.elapsed() >= interval {
+                self.stats_last_logged = Instant::now();
+                info!("{self} call metrics: {}", self.stats.to_json_value().dump());
+            }
+        }
+    }
+
+    pub(crate) fn backlog_max_size(&self) -> usize {
+        self.backlog_max_size
+    }
+
+    pub(crate) fn backlog_max_age(&self) -> Duration {
+        self.backlog_max_age
+    }
+
+    /// Messages evicted from the backlog since this client
+    /// connected.
+    pub fn backlog_dropped(&self) -> usize {
+        self.backlog_dropped
+    }
+
+    /// Summarizes the transport messages received but not yet
+    /// claimed by a session; see Client::backlog_summary().
+    pub fn backlog_summary(&self) -> JsonValue {
+        let entries = self
+            .backlog
+            .iter()
+            .map(|(added, tmsg)| {
+                json::object! {
+                    "thread": tmsg.thread(),
+                    "from": tmsg.from(),
+                    "types": tmsg
+                        .body()
+                        .iter()
+                        .map(|m| m.mtype().to_string())
+                        .collect::<Vec<String>>(),
+                    "age_ms": added.elapsed().as_millis() as usize,
+                }
+            })
+            .collect::<Vec<JsonValue>>();
+
+        JsonValue::Array(entries)
+    }
+
+    /// Caps the backlog size and the age of its entries; sessions
+    /// apply the same limits to their per-request backlogs.
+    pub fn set_backlog_limits(&mut self, max_size: usize, max_age: Duration) {
+        self.backlog_max_size = std::cmp::max(max_size, 1);
+        self.backlog_max_age = max_age;
+    }
+
+    /// Evicts expired or excess backlog entries, oldest first.
+    ///
+    /// Orphaned replies -- e.g. responses arriving after their
+    /// request gave up waiting -- would otherwise accumulate for
+    /// the life of the client.
+    fn evict_backlog(&mut self) {
+        let before = self.backlog.len();
+
+        let max_age = self.backlog_max_age;
+        self.backlog.retain(|(added, _)| added.elapsed() < max_age);
+
+        while self.backlog.len() > self.backlog_max_size {
+            // Entries are stored in arrival order.
+            self.backlog.remove(0);
+        }
+
+        let dropped = before - self.backlog.len();
+
+        if dropped > 0 {
+            self.backlog_dropped += dropped;
+            warn!("{self} evicted {dropped} backlogged messages");
+        }
+    }
+
+    pub fn set_serializer(&mut self, serializer: Arc<dyn DataSerializer>) {
+        self.serializer = Some(serializer);
+    }
+
+    /// Caps how many remote-node connections we keep open at once.
+    ///
+    /// When the pool is full, the least recently used connection is
+    /// closed to make room for a new domain.
+    pub fn set_max_remote_connections(&mut self, max: usize) {
+        self.max_remote_connections = std::cmp::max(max, 1);
+    }
+
+    /// Returns the bus connection for the provided domain, which
+    /// may be our primary bus or a pooled remote one.
+    pub fn get_domain_bus(&mut self, domain: &str) -> Result<&mut bus::Bus, String> {
+        trace!("Loading bus connection for domain: {domain}");
+
+        if domain.eq(self.domain()) {
+            return Ok(&mut self.bus);
+        }
+
+        self.evict_idle_connections();
+
+        if self.remote_bus_map.contains_key(domain) {
+            let (bus, last_used) = self.remote_bus_map.get_mut(domain).unwrap();
+            *last_used = Instant::now();
+            Ok(bus)
+        } else {
+            self.add_connection(domain)
+        }
+    }
+
+    /// Closes pooled connections that have sat unused longer than
+    /// the idle timeout, so long-running services talking to many
+    /// domains don't accumulate stale Redis connections.
+    fn evict_idle_connections(&mut self) {
+        self.remote_bus_map.retain(|domain, (_, last_used)| {
+            if last_used.elapsed() < REMOTE_BUS_IDLE_TIMEOUT {
+                true
+            } else {
+                debug!("Closing idle connection to domain: {domain}");
+                false
+            }
+        });
+    }
+
+    /// Opens and pools a connection to a remote bus node, reusing
+    /// our primary connection's credentials.
+    ///
+    /// If the pool is at capacity, the least recently used
+    /// connection is closed first.
+    fn add_connection(&mut self, domain: &str) -> Result<&mut bus::Bus, String> {
+        while self.remote_bus_map.len() >= self.max_remote_connections {
+            let oldest = self
+                .remote_bus_map
+                .iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(domain, _)| domain.clone());
+
+            match oldest {
+                Some(domain) => {
+                    debug!("Connection pool full; closing connection to domain: {domain}");
+                    self.remote_bus_map.remove(&domain);
+                }
+                None => break,
+            }
+        }
+
+        let node = self
+            .config
+            .get_node(domain)
+            .ok_or_else(|| format!("No configuration for domain {domain}"))?;
+
+        let mut con = self
+            .config
+            .primary_connection()
+            .unwrap() // verified at connect time
+            .clone();
+
+        con.set_node(node.clone());
+
+        let bus = bus::Bus::new(&con)?;
+
+        debug!("Opened connection to new domain: {domain}");
+
+        self.remote_bus_map
+            .insert(domain.to_string(), (bus, Instant::now()));
+        self.get_domain_bus(domain)
+    }
+
+    /// Sends a TransportMessage, routing to the correct bus
+    /// connection based on the destination address domain.
+    pub fn send(&mut self, tmsg: &TransportMessage) -> Result<(), String> {
+        if self.middleware.is_empty() {
+            return self.deliver(tmsg);
+        }
+
+        // Hooks may modify the message; work on a copy so callers
+        // keep an unaltered view of what they sent.
+        let mut tmsg = tmsg.clone();
+
+        for hook in self.middleware.iter() {
+            hook.pre_send(&mut tmsg);
+        }
+
+        self.deliver(&tmsg)
+    }
+
+    /// Delivers a message, buffering it in memory instead of
+    /// failing when offline buffering is enabled and the bus is
+    /// down.
+    fn deliver(&mut self, tmsg: &TransportMessage) -> Result<(), String> {
+        if self.offline_buffer.is_some() {
+            self.flush_offline_buffer();
+
+            // Anything still buffered failed to flush and must stay
+            // ahead of this message to preserve ordering.
+            if self.offline_buffered() > 0 {
+                self.buffer_offline(tmsg.clone());
+                return Ok(());
+            }
+        }
+
+        match self.deliver_now(tmsg) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                if self.offline_buffer.is_none() {
+                    return Err(e);
+                }
+
+                warn!("{self} bus unreachable; buffering message: {e}");
+                self.buffer_offline(tmsg.clone());
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Sends a message on the bus connection for its destination
+    /// domain.
+    fn deliver_now(&mut self, tmsg: &TransportMessage) -> Result<(), String> {
+        let domain = match ClientAddress::from_string(tmsg.to()) {
+            Ok(a) => a.domain().to_string(),
+            // Service-level and router addresses are always sent to
+            // our primary domain, where the router picks them up.
+            Err(_) => {
+                if let Ok(service_addr) = ServiceAddress::from_string(tmsg.to()) {
+                    return self.deliver_to_service(tmsg, &service_addr);
+                }
+
+                self.domain().to_string()
+            }
+        };
+
+        let bus = self.get_domain_bus(&domain)?;
+        bus.send(tmsg)
+    }
+
+    /// Sends a service-addressed message, detouring through the
+    /// router when direct delivery cannot work.
+    ///
+    /// A service covered only by a wildcard registration -- e.g.
+    /// "open-ils.fielder.flattener" under "open-ils.fielder.*" --
+    /// has no stream of its own, so a direct send would queue the
+    /// message where no worker is listening.  The router resolves
+    /// such names against its registry, so messages for services
+    /// without a local stream land on the router stream instead,
+    /// as broadcasts already do.
+    fn deliver_to_service(
+        &mut self,
+        tmsg: &TransportMessage,
+        service_addr: &ServiceAddress,
+    ) -> Result<(), String> {
+        let stream = service_addr.full();
+
+        // Affinity-keyed messages also need the router: only its
+        // registry can hash the key to one specific controller,
+        // while the shared service stream hands messages to
+        // whichever worker claims them first.
+        if tmsg.affinity_key().is_some() {
+            debug!("{self} affinity-keyed message for {stream} routes via router");
+
+            let router_stream = RouterAddress::new(&self.domain).full().to_string();
+            return self.bus.send_to(tmsg, &router_stream);
+        }
+
+        if !self.live_service_streams.contains(stream) {
+            if self.bus.stream_exists(stream)? {
+                // A service's stream outlives individual workers;
+                // one positive sighting covers the life of this
+                // client.
+                self.live_service_streams.insert(stream.to_string());
+            } else {
+                debug!("{self} no local stream for {stream}; routing via router");
+
+                let router_stream = RouterAddress::new(&self.domain).full().to_string();
+                return self.bus.send_to(tmsg, &router_stream);
+            }
+        }
+
+        // Direct sends bypass the router's queue-cap check, so the
+        // cap is enforced here where the message actually enters
+        // the stream; an overloaded service fails fast instead of
+        // queueing unboundedly.
+        let service = service_addr.service();
+
+        if let Some(cap) = self
+            .config
+            .service_options(service)
+            .map(|o| o.max_queued())
+            .filter(|cap| *cap > 0)
+        {
+            let depth = self.bus.xlen(stream)? as usize;
+
+            if depth >= cap {
+                warn!("{self} service={service} at queue cap ({depth}/{cap}); rejecting");
+
+                return Err(format!(
+                    "Service {service} unavailable: queue is full ({depth}/{cap})"
+                ));
+            }
+        }
+
+        self.bus.send(tmsg)
+    }
+
+    /// Appends a message to the offline buffer, dropping the
+    /// oldest entry if the buffer is full.
+    fn buffer_offline(&mut self, tmsg: TransportMessage) {
+        if self.offline_buffered() >= self.offline_buffer_max {
+            warn!("{self} offline buffer full; dropping oldest message");
+
+            if let Some(buffer) = self.offline_buffer.as_mut() {
+                buffer.remove(0);
+            }
+
+            self.offline_buffer_dropped += 1;
+        }
+
+        if let Some(buffer) = self.offline_buffer.as_mut() {
+            buffer.push(tmsg);
+        }
+    }
+
+    /// Attempts to deliver buffered messages in order, leaving any
+    /// that still cannot be sent (plus those after them) buffered
+    /// for the next attempt.
+    pub fn flush_offline_buffer(&mut self) {
+        let mut pending = match self.offline_buffer.take() {
+            Some(p) => p,
+            None => return,
+        };
+
+        let mut delivered = 0;
+
+        while !pending.is_empty() {
+            if self.deliver_now(&pending[0]).is_err() {
+                break;
+            }
+
+            pending.remove(0);
+            delivered += 1;
+        }
+
+        if delivered > 0 {
+            debug!("{self} flushed {delivered} messages from the offline buffer");
+        }
+
+        self.offline_buffer = Some(pending);
+    }
+
+    /// Messages currently awaiting a bus reconnect.
+    pub fn offline_buffered(&self) -> usize {
+        self.offline_buffer.as_ref().map(|b| b.len()).unwrap_or(0)
+    }
+
+    /// Messages dropped from a full offline buffer.
+    pub fn offline_buffer_dropped(&self) -> usize {
+        self.offline_buffer_dropped
+    }
+
+    /// Enables offline buffering with the provided cap on queued
+    /// messages, or disables it -- discarding anything still
+    /// buffered -- with None.
+    pub fn set_offline_buffering(&mut self, max_messages: Option<usize>) {
+        match max_messages {
+            Some(max) => {
+                self.offline_buffer_max = std::cmp::max(max, 1);
+
+                if self.offline_buffer.is_none() {
+                    self.offline_buffer = Some(Vec::new());
+                }
+            }
+            None => self.offline_buffer = None,
+        }
+    }
+
+    /// Returns the next TransportMessage for the requested session
+    /// thread, pulling messages off the bus as needed.
+    ///
+    /// Messages for other threads are stashed in the backlog for
+    /// their sessions to collect.
+    pub fn recv_session(
+        &mut self,
+        timer: &mut util::Timer,
+        thread: &str,
+    ) -> Result<Option<TransportMessage>, String> {
+        loop {
+            if let Some(index) = self.backlog.iter().position(|(_, t)| t.thread() == thread) {
+                trace!("Found a backlog message for thread {thread}");
+                return Ok(Some(self.backlog.remove(index).1));
+            }
+
+            // The bus is read at least once per call, even with no
+            // time left: a zero-remaining timer means one
+            // non-blocking read, so polling callers still see
+            // responses already waiting on the bus.
+            if let Some(mut tmsg) = self.bus.recv(timer.remaining(), None)? {
+                for hook in self.middleware.iter() {
+                    hook.post_receive(&mut tmsg);
+                }
+
+                if tmsg.router_command().is_some() {
+                    // Unsolicited router event, e.g. a service
+                    // up/down notification.
+                    self.handle_router_event(&tmsg);
+                } else {
+                    self.backlog.push((Instant::now(), tmsg));
+                    self.evict_backlog();
+                }
+            } else if timer.done() {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Dispatches a router-generated event to any matching service
+    /// watchers.
+    fn handle_router_event(&mut self, tmsg: &TransportMessage) {
+        let command = tmsg.router_command().unwrap_or("");
+
+        let up = match command {
+            "service_up" => true,
+            "service_down" => false,
+            _ => {
+                debug!("{self} ignoring router event: {command}");
+                return;
+            }
+        };
+
+        let service = match tmsg.router_class() {
+            Some(s) => s,
+            None => return,
+        };
+
+        debug!("{self} service={service} is {}", if up { "up" } else { "down" });
+
+        if let Some(callbacks) = self.service_watchers.get(service) {
+            for callback in callbacks {
+                callback(service, up);
+            }
+        }
+    }
+
+    /// Asks the router to send us up/down events for a service and
+    /// registers a callback for them.
+    pub fn watch_service(
+        &mut self,
+        service: &str,
+        callback: ServiceWatchCallback,
+    ) -> Result<(), String> {
+        let domain = self.domain().to_string();
+        self.send_router_command(&domain, "watch", Some(service), false)?;
+
+        self.service_watchers
+            .entry(service.to_string())
+            .or_default()
+            .push(callback);
+
+        Ok(())
+    }
+
+    /// Sends a command to the router on the provided domain,
+    /// optionally waiting for its reply.
+    pub fn send_router_command(
+        &mut self,
+        domain: &str,
+        router_command: &str,
+        router_class: Option<&str>,
+        expect_reply: bool,
+    ) -> Result<Option<json::JsonValue>, String> {
+        let addr = RouterAddress::new(domain);
+
+        // Always route router commands through the our primary
+        // domain -- the router watches every domain's router stream.
+        let mut tmsg = TransportMessage::new(
+            addr.full(),
+            self.bus.address().full(),
+            &util::random_number(16),
+        );
+
+        tmsg.set_router_command(router_command);
+        if let Some(rc) = router_class {
+            tmsg.set_router_class(rc);
+        }
+
+        let bus = self.get_domain_bus(domain)?;
+        bus.send(&tmsg)?;
+
+        if !expect_reply {
+            return Ok(None);
+        }
+
+        match bus.recv_json_value(super::session::DEFAULT_REQUEST_TIMEOUT, None)? {
+            Some(jv) => Ok(Some(jv)),
+            None => Err(format!("Timeout waiting on router command: {router_command}")),
+        }
+    }
+
+    /// Sends a request to every registered controller of a service
+    /// via the router, returning the thread of the broadcast so the
+    /// caller may collect replies.
+    pub fn broadcast(
+        &mut self,
+        service: &str,
+        method: &str,
+        params: Vec<json::JsonValue>,
+    ) -> Result<String, String> {
+        let payload = message::Payload::Method(message::Method::new(method, params));
+
+        let mut tmsg = TransportMessage::with_body(
+            ServiceAddress::new(service).full(),
+            self.bus.address().full(),
+            &util::random_number(16),
+            message::Message::new(message::MessageType::Request, 1, payload),
+        );
+
+        tmsg.set_broadcast(true);
+
+        let thread = tmsg.thread().to_string();
+
+        // Broadcasts require the router's service registry, so they
+        // land on the router stream instead of the service stream.
+        let router_stream = RouterAddress::new(&self.domain).full().to_string();
+        self.bus.send_to(&tmsg, &router_stream)?;
+
+        Ok(thread)
+    }
+
+    /// Collects responses to a broadcast thread until the timeout
+    /// expires.
+    ///
+    /// The number of responding controllers is unknown to us, so we
+    /// simply gather every Result that arrives within the window.
+    pub fn collect_broadcast(
+        &mut self,
+        thread: &str,
+        timeout: Duration,
+    ) -> Result<Vec<json::JsonValue>, String> {
+        let mut timer = util::Timer::new(timeout);
+        let mut replies = Vec::new();
+
+        while let Some(mut tmsg) = self.recv_session(&mut timer, thread)? {
+            for mut msg in tmsg.body_mut().drain(..) {
+                if let message::Payload::Result(mut res) = msg.take_payload() {
+                    replies.push(res.take_content());
+                }
+            }
+        }
+
+        Ok(replies)
+    }
+
+    /// Publishes an event to a broadcast topic; see Bus::publish().
+    pub fn publish(&mut self, topic: &str, value: &json::JsonValue) -> Result<(), String> {
+        self.bus.publish(topic, value)
+    }
+
+    /// Subscribes to a broadcast topic; events published after
+    /// this call are returned by recv_topic().
+    pub fn subscribe(&mut self, topic: &str) -> Result<(), String> {
+        self.bus.subscribe(topic)
+    }
+
+    /// Drops a topic subscription.
+    pub fn unsubscribe(&mut self, topic: &str) {
+        self.bus.unsubscribe(topic)
+    }
+
+    /// Returns the next event published to a subscribed topic.
+    pub fn recv_topic(
+        &mut self,
+        timeout: Duration,
+        topic: &str,
+    ) -> Result<Option<json::JsonValue>, String> {
+        self.bus.recv_topic(timeout, topic)
+    }
+
+    /// Enables the offline store-and-forward queue at the provided
+    /// file path.
+    pub fn set_offline_queue(&mut self, path: &str) {
+        self.offline_queue = Some(PathBuf::from(path));
+    }
+
+    /// Sends a fire-and-forget message, spooling it to the offline
+    /// queue instead of failing when the bus is unreachable.
+    ///
+    /// Returns true if the message was delivered to the bus and
+    /// false if it was queued locally.  Queued messages from earlier
+    /// outages are flushed first so ordering is preserved.
+    pub fn send_or_queue(&mut self, tmsg: &TransportMessage) -> Result<bool, String> {
+        if self.offline_queue.is_some() {
+            self.flush_offline_queue()?;
+        }
+
+        match self.send(tmsg) {
+            Ok(()) => Ok(true),
+            Err(e) => {
+                let path = match self.offline_queue.as_ref() {
+                    Some(p) => p.clone(),
+                    None => return Err(e),
+                };
+
+                warn!("{self} bus unreachable; queueing message: {e}");
+
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                    .map_err(|e| format!("Cannot open offline queue: {e}"))?;
+
+                writeln!(file, "{}", tmsg.to_json_value().dump())
+                    .map_err(|e| format!("Cannot write offline queue: {e}"))?;
+
+                Ok(false)
+            }
+        }
+    }
+
+    /// Attempts to deliver every message in the offline queue,
+    /// leaving any that still cannot be sent (plus those after them)
+    /// queued for the next attempt.
+    pub fn flush_offline_queue(&mut self) -> Result<(), String> {
+        let path = match self.offline_queue.as_ref() {
+            Some(p) => p.clone(),
+            None => return Ok(()),
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(t) => t,
+            // No queue file means nothing is waiting.
+            Err(_) => return Ok(()),
+        };
+
+        let mut remaining: Vec<&str> = Vec::new();
+        let mut failed = false;
+
+        for line in text.lines().filter(|l| !l.trim().is_empty()) {
+            if failed {
+                remaining.push(line);
+                continue;
+            }
+
+            let tmsg = json::parse(line)
+                .ok()
+                .and_then(TransportMessage::from_json_value);
+
+            let tmsg = match tmsg {
+                Some(t) => t,
+                None => {
+                    warn!("{self} dropping unparseable queued message");
+                    continue;
+                }
+            };
+
+            if self.send(&tmsg).is_err() {
+                // Preserve ordering: keep this and all later lines.
+                failed = true;
+                remaining.push(line);
+            } else {
+                debug!("{self} delivered queued message to {}", tmsg.to());
+            }
+        }
+
+        if remaining.is_empty() {
+            fs::remove_file(&path).ok();
+        } else {
+            fs::write(&path, remaining.join("\n") + "\n")
+                .map_err(|e| format!("Cannot rewrite offline queue: {e}"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Asks the router on a domain to drain one controller of a
+    /// service: unregister it, let it finish outstanding sessions,
+    /// and have it exit cleanly.
+    pub fn drain_service(
+        &mut self,
+        domain: &str,
+        service: &str,
+        address: &str,
+    ) -> Result<(), String> {
+        let addr = RouterAddress::new(domain);
+
+        let mut tmsg = TransportMessage::new(
+            addr.full(),
+            self.bus.address().full(),
+            &util::random_number(16),
+        );
+
+        tmsg.set_router_command("drain");
+        tmsg.set_router_class(service);
+        tmsg.set_router_argument(address);
+
+        let bus = self.get_domain_bus(domain)?;
+        bus.send(&tmsg)
+    }
+
+    /// Schedules a fire-and-forget request for future delivery.
+    pub fn send_after(
+        &mut self,
+        service: &str,
+        method: &str,
+        params: Vec<json::JsonValue>,
+        delay: Duration,
+    ) -> Result<(), String> {
+        let payload = message::Payload::Method(message::Method::new(method, params));
+
+        let tmsg = TransportMessage::with_body(
+            ServiceAddress::new(service).full(),
+            self.bus.address().full(),
+            &util::random_number(16),
+            message::Message::new(message::MessageType::Request, 1, payload),
+        );
+
+        let deliver_at = match std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+        {
+            Ok(d) => d.as_secs() + delay.as_secs(),
+            Err(e) => return Err(format!("System clock error: {e}")),
+        };
+
+        self.bus.schedule_send(&tmsg, deliver_at)
+    }
+
+    /// Discards all pending bus messages and the local backlog.
+    pub fn clear(&mut self) -> Result<(), String> {
+        self.backlog.clear();
+        self.bus.clear_stream()
+    }
+
+    /// Registers a session created through this client; see
+    /// Client::shutdown().
+    pub(crate) fn track_session(&mut self, session: Weak<RefCell<Session>>) {
+        self.sessions.retain(|s| s.strong_count() > 0);
+        self.sessions.push(session);
+    }
+
+    /// Tears down our bus presence: drops the message backlog,
+    /// topic subscriptions, and pooled remote-node connections,
+    /// then removes our stream from Redis.
+    ///
+    /// Runs automatically when the last clone of the owning Client
+    /// is dropped; see Client::shutdown() for the variant that also
+    /// disconnects open sessions.
+    pub fn shutdown(&mut self) {
+        debug!("{self} shutting down");
+
+        self.backlog.clear();
+        self.sessions.clear();
+        self.bus.unsubscribe_all();
+
+        // Dropping a Bus removes its stream from its node.
+        self.remote_bus_map.clear();
+
+        if let Err(e) = self.bus.disconnect() {
+            error!("{self} bus disconnect error: {e}");
+        }
+    }
+}
+
+impl Drop for ClientSingleton {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Assembles a Client from explicit options.
+///
+/// Replaces the mutate-the-config-then-connect dance: the builder
+/// applies the primary connection itself and surfaces a Result
+/// where Client::connect() would panic with no primary connection
+/// configured.
+///
+/// The connection type defaults to "client" and the domain to the
+/// first configured bus node.
+pub struct ClientBuilder {
+    config: conf::Config,
+    connection_type: String,
+    domain: Option<String>,
+    serializer: Option<Arc<dyn DataSerializer>>,
+    backlog_limits: Option<(usize, Duration)>,
+    max_remote_connections: Option<usize>,
+    stats_log_interval: Option<Duration>,
+}
+
+impl ClientBuilder {
+    pub fn new(config: conf::Config) -> ClientBuilder {
+        ClientBuilder {
+            config,
+            connection_type: "client".to_string(),
+            domain: None,
+            serializer: None,
+            backlog_limits: None,
+            max_remote_connections: None,
+            stats_log_interval: None,
+        }
+    }
+
+    /// Uses the named connection type from the "connections"
+    /// config section.
+    pub fn connection_type(mut self, connection_type: &str) -> ClientBuilder {
+        self.connection_type = connection_type.to_string();
+        self
+    }
+
+    /// Connects to the named bus node instead of the first one
+    /// configured.
+    pub fn domain(mut self, domain: &str) -> ClientBuilder {
+        self.domain = Some(domain.to_string());
+        self
+    }
+
+    /// Applies a pack/unpack layer for message content.
+    pub fn serializer(mut self, serializer: Arc<dyn DataSerializer>) -> ClientBuilder {
+        self.serializer = Some(serializer);
+        self
+    }
+
+    /// Caps the message backlog size and the age of its entries.
+    pub fn backlog_limits(mut self, max_size: usize, max_age: Duration) -> ClientBuilder {
+        self.backlog_limits = Some((max_size, max_age));
+        self
+    }
+
+    /// Caps concurrently open remote-node connections.
+    pub fn max_remote_connections(mut self, max: usize) -> ClientBuilder {
+        self.max_remote_connections = Some(max);
+        self
+    }
+
+    /// Logs call metrics at most this often.
+    pub fn stats_log_interval(mut self, interval: Duration) -> ClientBuilder {
+        self.stats_log_interval = Some(interval);
+        self
+    }
+
+    /// Connects to the bus and applies the collected options.
+    pub fn build(mut self) -> Result<Client, String> {
+        let domain = match self.domain.as_deref() {
+            Some(d) => d.to_string(),
+            None => self
+                .config
+                .nodes()
+                .first()
+                .map(|n| n.name().to_string())
+                .ok_or("Configuration defines no bus nodes")?,
+        };
+
+        self.config
+            .set_primary_connection(&self.connection_type, &domain)?;
+
+        let client = Client::connect(self.config.into_shared())?;
+
+        if let Some(serializer) = self.serializer {
+            client.set_serializer(serializer);
+        }
+
+        if let Some((max_size, max_age)) = self.backlog_limits {
+            client.set_backlog_limits(max_size, max_age);
+        }
+
+        if let Some(max) = self.max_remote_connections {
+            client.set_max_remote_connections(max);
+        }
+
+        if let Some(interval) = self.stats_log_interval {
+            client.set_stats_log_interval(Some(interval));
+        }
+
+        Ok(client)
+    }
+}
+
+/// Cloneable handle to the ClientSingleton.
+pub struct Client {
+    singleton: Rc<RefCell<ClientSingleton>>,
+
+    /// Duplicated here for lock-free access.
+    address: ClientAddress,
+    domain: String,
+}
+
+impl Client {
+    /// Starts a ClientBuilder for connecting with explicit
+    /// options.
+    pub fn builder(config: conf::Config) -> ClientBuilder {
+        ClientBuilder::new(config)
+    }
+
+    /// Connects to the bus using the primary connection defined on
+    /// the provided config.
+    pub fn connect(config: Arc<conf::Config>) -> Result<Client, String> {
+        let singleton = ClientSingleton::new(config)?;
+
+        let address = singleton.address().clone();
+        let domain = singleton.domain().to_string();
+
+        Ok(Client {
+            singleton: Rc::new(RefCell::new(singleton)),
+            address,
+            domain,
+        })
+    }
+
+    pub fn singleton(&self) -> &Rc<RefCell<ClientSingleton>> {
+        &self.singleton
+    }
+
+    pub fn address(&self) -> &ClientAddress {
+        &self.address
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Installs a middleware hook that sees every outbound and
+    /// inbound message; hooks run in installation order.
+    pub fn add_middleware(&self, middleware: Arc<dyn ClientMiddleware>) {
+        self.singleton.borrow_mut().add_middleware(middleware);
+    }
+
+    /// Sets the default locale carried on outbound requests, e.g.
+    /// "en-US", so internationalized services see the caller's
+    /// locale.  Sessions may override it; see
+    /// SessionHandle::set_locale().
+    pub fn set_locale(&self, locale: &str) {
+        self.singleton.borrow_mut().set_locale(locale);
+    }
+
+    /// Call metrics accumulated since this client connected, as
+    /// JSON; see ClientStats.
+    pub fn stats(&self) -> json::JsonValue {
+        self.singleton.borrow().stats().to_json_value()
+    }
+
+    pub fn clear_stats(&self) {
+        self.singleton.borrow_mut().clear_stats();
+    }
+
+    /// Logs the call metrics at most this often, piggybacking on
+    /// request activity.  None disables logging.
+    pub fn set_stats_log_interval(&self, interval: Option<Duration>) {
+        self.singleton.borrow_mut().set_stats_log_interval(interval);
+    }
+
+    /// Caps the message backlog size and the age of its entries.
+    pub fn set_backlog_limits(&self, max_size: usize, max_age: Duration) {
+        self.singleton
+            .borrow_mut()
+            .set_backlog_limits(max_size, max_age);
+    }
+
+    /// Messages evicted from the backlog since this client
+    /// connected.
+    pub fn backlog_dropped(&self) -> usize {
+        self.singleton.borrow().backlog_dropped()
+    }
+
+    /// Summarizes the transport messages received but not yet
+    /// claimed by a session: one object per message with its
+    /// thread, sender, body message types, and age in
+    /// milliseconds.
+    ///
+    /// A diagnostic aid for responses that appear to vanish; see
+    /// also SessionHandle::backlog_summary() for messages routed
+    /// to a session but not yet consumed.
+    pub fn backlog_summary(&self) -> JsonValue {
+        self.singleton.borrow().backlog_summary()
+    }
+
+    pub fn set_serializer(&self, serializer: Arc<dyn DataSerializer>) {
+        self.singleton.borrow_mut().set_serializer(serializer);
+    }
+
+    /// Caps how many remote-node connections the underlying pool
+    /// keeps open at once.
+    pub fn set_max_remote_connections(&self, max: usize) {
+        self.singleton
+            .borrow_mut()
+            .set_max_remote_connections(max);
+    }
+
+    /// Orderly teardown for short-lived clients: disconnects any
+    /// still-connected sessions, then clears the backlog, topic
+    /// subscriptions, and remote connections, and removes our bus
+    /// stream so nothing is left behind in Redis.
+    ///
+    /// The bus-level teardown also runs when the last clone of
+    /// this client is dropped; calling shutdown() explicitly
+    /// additionally DISCONNECTs open sessions at a predictable
+    /// point.
+    pub fn shutdown(&self) {
+        let sessions = std::mem::take(&mut self.singleton().borrow_mut().sessions);
+
+        for weak in sessions {
+            if let Some(session) = weak.upgrade() {
+                let mut session = session.borrow_mut();
+
+                if session.connected() {
+                    if let Err(e) = session.disconnect() {
+                        warn!("Error disconnecting session at shutdown: {e}");
+                    }
+                }
+            }
+        }
+
+        self.singleton().borrow_mut().shutdown();
+    }
+
+    /// Creates a new client session for communicating with the
+    /// provided service.
+    pub fn session(&self, service: &str) -> SessionHandle {
+        SessionHandle::new(self.clone(), service)
+    }
+
+    /// Creates a pool of connected sessions for a frequently
+    /// called service; see SessionPool.
+    pub fn session_pool(&self, service: &str) -> SessionPool {
+        SessionPool::new(self.clone(), service)
+    }
+
+    /// One-shot request: opens a session for the service, sends
+    /// the request, and returns its first response.
+    ///
+    /// Errors if the request completes with no response; see
+    /// SessionHandle::send_recv_one().
+    pub fn send_recv_one(
+        &self,
+        service: &str,
+        method: &str,
+        params: impl Into<ApiParams>,
+        timeout: Duration,
+    ) -> Result<json::JsonValue, String> {
+        self.session(service).send_recv_one(method, params, timeout)
+    }
+
+    /// One-shot fire-and-forget request; see
+    /// SessionHandle::send_noreply().
+    pub fn send_noreply(
+        &self,
+        service: &str,
+        method: &str,
+        params: impl Into<ApiParams>,
+    ) -> Result<(), String> {
+        self.session(service).send_noreply(method, params)
+    }
+
+    pub fn send_router_command(
+        &self,
+        domain: &str,
+        router_command: &str,
+        router_class: Option<&str>,
+        expect_reply: bool,
+    ) -> Result<Option<json::JsonValue>, String> {
+        self.singleton.borrow_mut().send_router_command(
+            domain,
+            router_command,
+            router_class,
+            expect_reply,
+        )
+    }
+
+    /// Fetches and parses the registration summary from the router
+    /// on the provided domain.
+    pub fn router_summary(&self, domain: &str) -> Result<RouterSummary, String> {
+        match self.send_router_command(domain, "summarize", None, true)? {
+            Some(value) => RouterSummary::from_json_value(&value),
+            None => Err(format!("Router on domain {domain} returned no summary")),
+        }
+    }
+
+    /// The services registered with the router on the provided
+    /// domain.
+    pub fn router_services(&self, domain: &str) -> Result<Vec<RouterService>, String> {
+        Ok(self.router_summary(domain)?.services)
+    }
+
+    /// Watches a service for availability changes.
+    ///
+    /// The callback fires when the router reports the service
+    /// gaining its first controller or losing its last one, letting
+    /// dependents fail fast instead of discovering outages via
+    /// timeouts.  Events are delivered opportunistically while this
+    /// client is receiving from the bus.
+    pub fn watch_service(
+        &self,
+        service: &str,
+        callback: ServiceWatchCallback,
+    ) -> Result<(), String> {
+        self.singleton.borrow_mut().watch_service(service, callback)
+    }
+
+    /// Like watch_service(), but delivers availability events on a
+    /// channel instead of through a callback.
+    ///
+    /// Each event pairs the service name with true (up) or false
+    /// (down).  Suits callers with their own event loop; as with
+    /// watch_service(), events only arrive while this client is
+    /// receiving from the bus.
+    pub fn watch_service_channel(
+        &self,
+        service: &str,
+    ) -> Result<mpsc::Receiver<(String, bool)>, String> {
+        let (tx, rx) = mpsc::channel();
+
+        self.watch_service(
+            service,
+            Box::new(move |service: &str, up: bool| {
+                tx.send((service.to_string(), up)).ok();
+            }),
+        )?;
+
+        Ok(rx)
+    }
+
+    /// Sends a request to every registered controller of a service,
+    /// e.g. for cache-flush or config-reload commands.
+    ///
+    /// Returns the broadcast thread, which may be handed to
+    /// collect_broadcast() to aggregate replies.
+    pub fn broadcast(
+        &self,
+        service: &str,
+        method: &str,
+        params: Vec<json::JsonValue>,
+    ) -> Result<String, String> {
+        self.singleton.borrow_mut().broadcast(service, method, params)
+    }
+
+    /// Gathers all responses to a broadcast that arrive before the
+    /// timeout expires.
+    pub fn collect_broadcast(
+        &self,
+        thread: &str,
+        timeout: Duration,
+    ) -> Result<Vec<json::JsonValue>, String> {
+        self.singleton.borrow_mut().collect_broadcast(thread, timeout)
+    }
+
+    /// Publishes an event to a broadcast topic, outside the
+    /// request/response model.
+    ///
+    /// Suits service-emitted events -- cache invalidation, config
+    /// changes -- that many listeners consume; see
+    /// subscribe()/recv_topic() on the consuming side.
+    pub fn publish(&self, topic: &str, value: &json::JsonValue) -> Result<(), String> {
+        self.singleton.borrow_mut().publish(topic, value)
+    }
+
+    /// Subscribes to a broadcast topic; events published after
+    /// this call are returned by recv_topic().
+    pub fn subscribe(&self, topic: &str) -> Result<(), String> {
+        self.singleton.borrow_mut().subscribe(topic)
+    }
+
+    /// Drops a topic subscription.
+    pub fn unsubscribe(&self, topic: &str) {
+        self.singleton.borrow_mut().unsubscribe(topic)
+    }
+
+    /// Returns the next event published to a subscribed topic,
+    /// waiting up to timeout for one to arrive.
+    pub fn recv_topic(
+        &self,
+        timeout: Duration,
+        topic: &str,
+    ) -> Result<Option<json::JsonValue>, String> {
+        self.singleton.borrow_mut().recv_topic(timeout, topic)
+    }
+
+    /// Enables in-memory buffering of outbound messages while the
+    /// bus is down, bounded at max_messages, or disables it with
+    /// None.
+    ///
+    /// With buffering enabled, sends that would otherwise fail are
+    /// queued and flushed -- in order, ahead of new traffic -- once
+    /// the bus is reachable again.  Suits telemetry/logging-style
+    /// traffic that tolerates delay but not loss; the oldest
+    /// messages are dropped if the buffer fills.
+    pub fn set_offline_buffering(&self, max_messages: Option<usize>) {
+        self.singleton
+            .borrow_mut()
+            .set_offline_buffering(max_messages)
+    }
+
+    /// Attempts to deliver any messages held in the offline buffer.
+    pub fn flush_offline_buffer(&self) {
+        self.singleton.borrow_mut().flush_offline_buffer()
+    }
+
+    /// Messages currently held in the offline buffer.
+    pub fn offline_buffered(&self) -> usize {
+        self.singleton.borrow().offline_buffered()
+    }
+
+    /// Enables offline store-and-forward mode.
+    ///
+    /// With a queue configured, messages sent via send_or_queue()
+    /// survive brief bus outages by spooling to local disk, which
+    /// suits edge utilities (SIP bridges, kiosks) that must not
+    /// lose events.
+    pub fn set_offline_queue(&self, path: &str) {
+        self.singleton.borrow_mut().set_offline_queue(path);
+    }
+
+    /// Sends a fire-and-forget message, spooling it locally if the
+    /// bus is unreachable.  Returns true if it reached the bus.
+    pub fn send_or_queue(&self, tmsg: &TransportMessage) -> Result<bool, String> {
+        self.singleton.borrow_mut().send_or_queue(tmsg)
+    }
+
+    /// Flushes any locally queued messages to the bus.
+    pub fn flush_offline_queue(&self) -> Result<(), String> {
+        self.singleton.borrow_mut().flush_offline_queue()
+    }
+
+    /// Instructs the router to drain a specific controller of a
+    /// service, enabling rolling restarts from admin tooling.
+    pub fn drain_service(
+        &self,
+        domain: &str,
+        service: &str,
+        address: &str,
+    ) -> Result<(), String> {
+        self.singleton
+            .borrow_mut()
+            .drain_service(domain, service, address)
+    }
+
+    /// Schedules a fire-and-forget request for delivery after the
+    /// provided delay.
+    ///
+    /// The message sits in the bus-level schedule until a drainer
+    /// (normally the router) moves it to the service stream, so
+    /// delivery survives this process exiting in the meantime.
+    pub fn send_after(
+        &self,
+        service: &str,
+        method: &str,
+        params: Vec<json::JsonValue>,
+        delay: Duration,
+    ) -> Result<(), String> {
+        self.singleton
+            .borrow_mut()
+            .send_after(service, method, params, delay)
+    }
+
+    /// Discards all unprocessed messages from our backlog and bus.
+    pub fn clear(&self) -> Result<(), String> {
+        self.singleton.borrow_mut().clear()
+    }
+}
+
+impl Clone for Client {
+    fn clone(&self) -> Client {
+        Client {
+            singleton: self.singleton.clone(),
+            address: self.address.clone(),
+            domain: self.domain.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Client {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Client({})", self.address.full())
+    }
+}
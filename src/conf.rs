@@ -0,0 +1,1070 @@
+use json::JsonValue;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use yaml_rust::{Yaml, YamlLoader};
+
+const DEFAULT_BUS_PORT: u16 = 6379;
+
+/// Entries kept per stream when no trim policy is configured.
+const DEFAULT_STREAM_MAXLEN: usize = 1000;
+
+/// A single node (Redis instance) on the message bus.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusNode {
+    name: String,
+    port: u16,
+    db: i64,
+    addresses: Vec<String>,
+    sentinels: Vec<String>,
+    sentinel_master: Option<String>,
+    cluster_nodes: Vec<String>,
+    tls: Option<BusNodeTls>,
+    socket_path: Option<String>,
+    trim_policy: Option<TrimPolicy>,
+    max_message_age: Option<u64>,
+}
+
+/// How streams are trimmed when messages are added.
+///
+/// Parsed from the "stream-maxlen", "stream-trim", and
+/// "stream-max-age" keys of a node or service config block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrimPolicy {
+    /// Keep roughly this many entries (MAXLEN ~).
+    MaxlenApprox(usize),
+    /// Keep exactly this many entries (MAXLEN =).
+    MaxlenExact(usize),
+    /// Drop entries older than this many seconds (MINID ~).
+    MaxAge(u64),
+}
+
+/// Builds a TrimPolicy from the relevant keys of a config block,
+/// if any are present.
+fn parse_trim_policy(block: &Yaml) -> Option<TrimPolicy> {
+    if let Some(age) = block["stream-max-age"].as_i64() {
+        return Some(TrimPolicy::MaxAge(age as u64));
+    }
+
+    let maxlen = block["stream-maxlen"].as_i64()? as usize;
+
+    match block["stream-trim"].as_str() {
+        Some("exact") => Some(TrimPolicy::MaxlenExact(maxlen)),
+        _ => Some(TrimPolicy::MaxlenApprox(maxlen)),
+    }
+}
+
+/// TLS options for connections to a bus node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusNodeTls {
+    enabled: bool,
+    ca_file: Option<String>,
+    cert_file: Option<String>,
+    key_file: Option<String>,
+    verify: bool,
+}
+
+impl BusNodeTls {
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// PEM file holding the CA certificate to trust, when the bus
+    /// certificate is not signed by one in the local truststore.
+    pub fn ca_file(&self) -> Option<&str> {
+        self.ca_file.as_deref()
+    }
+
+    /// PEM file holding our client certificate for mutual TLS.
+    pub fn cert_file(&self) -> Option<&str> {
+        self.cert_file.as_deref()
+    }
+
+    /// PEM file holding the key for cert_file().
+    pub fn key_file(&self) -> Option<&str> {
+        self.key_file.as_deref()
+    }
+
+    /// False disables hostname verification.  Only for testing.
+    pub fn verify(&self) -> bool {
+        self.verify
+    }
+}
+
+impl BusNode {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Addresses to try, in order, when connecting to this node.
+    ///
+    /// Nodes may list multiple resolved addresses (IPv6 and IPv4)
+    /// for dual-stack hosts; the node name is the fallback when
+    /// none are configured.
+    pub fn addresses(&self) -> Vec<String> {
+        if self.addresses.is_empty() {
+            vec![self.name.clone()]
+        } else {
+            self.addresses.clone()
+        }
+    }
+
+    /// Sentinel addresses ("host" or "host:port") fronting this
+    /// node, if any.
+    pub fn sentinels(&self) -> &Vec<String> {
+        &self.sentinels
+    }
+
+    /// Name of the Sentinel-monitored master to connect to.
+    ///
+    /// When set, connections ask the sentinels for the current
+    /// master address instead of using addresses().
+    pub fn sentinel_master(&self) -> Option<&str> {
+        self.sentinel_master.as_deref()
+    }
+
+    /// Cluster seed addresses ("host" or "host:port").
+    ///
+    /// When non-empty, connections treat this node as a Redis
+    /// Cluster instead of a single server.
+    pub fn cluster_nodes(&self) -> &Vec<String> {
+        &self.cluster_nodes
+    }
+
+    /// TLS options for this node, if configured.
+    pub fn tls(&self) -> Option<&BusNodeTls> {
+        self.tls.as_ref()
+    }
+
+    /// Unix socket path for connecting to a local server, bypassing
+    /// TCP entirely.
+    pub fn socket_path(&self) -> Option<&str> {
+        self.socket_path.as_deref()
+    }
+
+    /// Redis database index connections to this node select.
+    pub fn db(&self) -> i64 {
+        self.db
+    }
+
+    /// Max age a queued message may reach before receivers discard
+    /// it and buswatch trims it; None disables the check.
+    pub fn max_message_age(&self) -> Option<Duration> {
+        self.max_message_age.map(Duration::from_secs)
+    }
+
+    /// How streams on this node are trimmed when messages are
+    /// added.
+    pub fn trim_policy(&self) -> TrimPolicy {
+        self.trim_policy
+            .unwrap_or(TrimPolicy::MaxlenApprox(DEFAULT_STREAM_MAXLEN))
+    }
+}
+
+impl fmt::Display for BusNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "node={}:{}", self.name, self.port)
+    }
+}
+
+/// Bus login credentials.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusCredentials {
+    username: String,
+    password: String,
+}
+
+impl BusCredentials {
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn password(&self) -> &str {
+        &self.password
+    }
+
+    pub fn set_username(&mut self, username: &str) {
+        self.username = username.to_string();
+    }
+
+    pub fn set_password(&mut self, password: &str) {
+        self.password = password.to_string();
+    }
+}
+
+/// A fully resolved bus connection: a node plus the credentials
+/// used to log in to it.
+#[derive(Debug, Clone)]
+pub struct BusConnection {
+    node: BusNode,
+    credentials: BusCredentials,
+}
+
+impl BusConnection {
+    pub fn node(&self) -> &BusNode {
+        &self.node
+    }
+
+    pub fn credentials(&self) -> &BusCredentials {
+        &self.credentials
+    }
+
+    pub fn credentials_mut(&mut self) -> &mut BusCredentials {
+        &mut self.credentials
+    }
+
+    pub fn set_node(&mut self, node: BusNode) {
+        self.node = node;
+    }
+
+    /// The domain is how most of the code refers to the node name.
+    pub fn domain(&self) -> &str {
+        self.node.name()
+    }
+}
+
+/// A named connection type from the "connections" config section,
+/// linking a credentials account to a log setup.
+#[derive(Debug, Clone)]
+pub struct BusConnectionType {
+    credentials: String,
+}
+
+impl BusConnectionType {
+    pub fn credentials(&self) -> &str {
+        &self.credentials
+    }
+}
+
+/// Where log lines should land.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogFile {
+    Syslog,
+    Stderr,
+    Filename(String),
+}
+
+/// Logging options, typically pulled from the "logging" section of
+/// the config file.
+#[derive(Debug, Clone)]
+pub struct LogOptions {
+    log_level: log::LevelFilter,
+    log_file: LogFile,
+    syslog_facility: Option<String>,
+    activity_log_facility: Option<String>,
+}
+
+impl LogOptions {
+    pub fn log_level(&self) -> &log::LevelFilter {
+        &self.log_level
+    }
+
+    pub fn set_log_level(&mut self, level: log::LevelFilter) {
+        self.log_level = level;
+    }
+
+    pub fn log_file(&self) -> &LogFile {
+        &self.log_file
+    }
+
+    pub fn set_log_file(&mut self, log_file: LogFile) {
+        self.log_file = log_file;
+    }
+
+    pub fn syslog_facility(&self) -> Option<&str> {
+        self.syslog_facility.as_deref()
+    }
+
+    pub fn set_syslog_facility(&mut self, facility: &str) {
+        self.syslog_facility = Some(facility.to_string());
+    }
+
+    pub fn activity_log_facility(&self) -> Option<&str> {
+        self.activity_log_facility.as_deref()
+    }
+
+    /// Maps a log level string to a LevelFilter, defaulting to Info
+    /// for unrecognized values.
+    pub fn level_filter_from_str(level: &str) -> log::LevelFilter {
+        match level.to_lowercase().as_str() {
+            "error" => log::LevelFilter::Error,
+            "warn" => log::LevelFilter::Warn,
+            "info" => log::LevelFilter::Info,
+            "debug" => log::LevelFilter::Debug,
+            "trace" => log::LevelFilter::Trace,
+            _ => log::LevelFilter::Info,
+        }
+    }
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions {
+            log_level: log::LevelFilter::Info,
+            log_file: LogFile::Stderr,
+            syslog_facility: None,
+            activity_log_facility: None,
+        }
+    }
+}
+
+/// Worker/keepalive options for a locally hosted service.
+#[derive(Debug, Clone)]
+pub struct ServiceOptions {
+    min_workers: usize,
+    max_workers: usize,
+    min_idle: usize,
+    max_idle: usize,
+    max_requests: usize,
+    keepalive: i32,
+    cpus: Vec<usize>,
+    max_queued: usize,
+    stream_trim: Option<TrimPolicy>,
+    reliable: bool,
+    shutdown_grace: u64,
+    max_runtime: u64,
+    busy_threshold: usize,
+    use_settings_server: bool,
+    rate_limit_burst: u32,
+    rate_limit_per_sec: u32,
+}
+
+impl ServiceOptions {
+    pub fn min_workers(&self) -> usize {
+        self.min_workers
+    }
+
+    pub fn max_workers(&self) -> usize {
+        self.max_workers
+    }
+
+    /// Idle workers to keep ready for new requests; the pool is
+    /// topped up when idle capacity falls below this.
+    pub fn min_idle(&self) -> usize {
+        self.min_idle
+    }
+
+    /// Idle workers beyond this are asked to exit once they have
+    /// been idle a while; zero means never reap.
+    pub fn max_idle(&self) -> usize {
+        self.max_idle
+    }
+
+    pub fn max_requests(&self) -> usize {
+        self.max_requests
+    }
+
+    pub fn keepalive(&self) -> i32 {
+        self.keepalive
+    }
+
+    /// CPUs this service's workers should be pinned to; empty means
+    /// no pinning.
+    pub fn cpus(&self) -> &Vec<usize> {
+        &self.cpus
+    }
+
+    /// Max stream depth the router allows for this service before
+    /// bouncing new requests; zero means unbounded.
+    pub fn max_queued(&self) -> usize {
+        self.max_queued
+    }
+
+    /// Trim policy for this service's stream, overriding the
+    /// node-level policy.
+    pub fn stream_trim(&self) -> Option<TrimPolicy> {
+        self.stream_trim
+    }
+
+    /// True means workers acknowledge each request only after its
+    /// handler completes, so crashed workers don't lose messages.
+    pub fn reliable(&self) -> bool {
+        self.reliable
+    }
+
+    /// Seconds granted at shutdown for busy workers to finish
+    /// their current requests before the server gives up on them.
+    pub fn shutdown_grace(&self) -> u64 {
+        self.shutdown_grace
+    }
+
+    /// Seconds a handler may run before the worker's watchdog
+    /// intervenes; zero means unlimited.  Individual methods may
+    /// override this via Method::max_runtime.
+    pub fn max_runtime(&self) -> u64 {
+        self.max_runtime
+    }
+
+    /// Stream depth beyond which a fully saturated server bounces
+    /// queued requests with a BUSY status; zero disables load
+    /// shedding.
+    pub fn busy_threshold(&self) -> usize {
+        self.busy_threshold
+    }
+
+    /// Requests an origin may burst before rate limiting kicks in.
+    /// Limiting is active only when this and rate_limit_per_sec are
+    /// both nonzero.
+    pub fn rate_limit_burst(&self) -> u32 {
+        self.rate_limit_burst
+    }
+
+    /// Sustained per-origin request rate once the burst allowance is
+    /// spent, in requests per second.
+    pub fn rate_limit_per_sec(&self) -> u32 {
+        self.rate_limit_per_sec
+    }
+
+    /// True means the server fetches this service's stanza from
+    /// opensrf.settings at startup and merges it over these values.
+    /// If the settings service cannot be reached, startup proceeds
+    /// on the local values alone.
+    pub fn use_settings_server(&self) -> bool {
+        self.use_settings_server
+    }
+
+    /// Merges a service stanza fetched from opensrf.settings over
+    /// these values.  Keys mirror the local YAML so one vocabulary
+    /// covers both sources; absent keys leave the local values
+    /// untouched.
+    pub(crate) fn apply_host_settings(&mut self, settings: &JsonValue) {
+        if let Some(v) = settings["workers"]["min"].as_usize() {
+            self.min_workers = v;
+        }
+        if let Some(v) = settings["workers"]["max"].as_usize() {
+            self.max_workers = v;
+        }
+        if let Some(v) = settings["workers"]["min-idle"].as_usize() {
+            self.min_idle = v;
+        }
+        if let Some(v) = settings["workers"]["max-idle"].as_usize() {
+            self.max_idle = v;
+        }
+        if let Some(v) = settings["max-requests"].as_usize() {
+            self.max_requests = v;
+        }
+        if let Some(v) = settings["keepalive"].as_i32() {
+            self.keepalive = v;
+        }
+        if let Some(v) = settings["max-queued"].as_usize() {
+            self.max_queued = v;
+        }
+        if let Some(v) = settings["shutdown-grace"].as_u64() {
+            self.shutdown_grace = v;
+        }
+        if let Some(v) = settings["max-runtime"].as_u64() {
+            self.max_runtime = v;
+        }
+        if let Some(v) = settings["busy-threshold"].as_usize() {
+            self.busy_threshold = v;
+        }
+        if let Some(v) = settings["rate-limit"]["burst"].as_u32() {
+            self.rate_limit_burst = v;
+        }
+        if let Some(v) = settings["rate-limit"]["per-second"].as_u32() {
+            self.rate_limit_per_sec = v;
+        }
+    }
+}
+
+impl Default for ServiceOptions {
+    fn default() -> Self {
+        ServiceOptions {
+            min_workers: 1,
+            max_workers: 30,
+            min_idle: 1,
+            max_idle: 0,
+            max_requests: 1000,
+            keepalive: 5,
+            cpus: Vec::new(),
+            max_queued: 0,
+            stream_trim: None,
+            reliable: false,
+            shutdown_grace: 30,
+            max_runtime: 0,
+            busy_threshold: 0,
+            use_settings_server: false,
+            rate_limit_burst: 0,
+            rate_limit_per_sec: 0,
+        }
+    }
+}
+
+/// Baked-in configuration for Config::localhost_defaults().
+const LOCALHOST_YAML: &str = r#"
+message-bus:
+  nodes:
+    - name: localhost
+      port: 6379
+  credentials:
+    default:
+      username: opensrf
+      password: password
+  connections:
+    client:
+      credentials: default
+    service:
+      credentials: default
+    router:
+      credentials: default
+logging:
+  log-level: debug
+  destination: stderr
+"#;
+
+/// Parsed representation of the OpenSRF YAML configuration file.
+#[derive(Debug, Clone)]
+pub struct Config {
+    hostname: String,
+    nodes: Vec<BusNode>,
+    credentials: HashMap<String, BusCredentials>,
+    connections: HashMap<String, BusConnectionType>,
+    services: HashMap<String, ServiceOptions>,
+    log_protect: Vec<String>,
+    log_options: LogOptions,
+    primary_connection: Option<BusConnection>,
+    host_settings: Option<JsonValue>,
+}
+
+impl Config {
+    /// Builds a working single-domain configuration for local
+    /// development: localhost Redis on the default port, the
+    /// default opensrf credentials, and debug logging to STDERR.
+    ///
+    /// Lets examples and new contributors run the router and demo
+    /// services without writing a YAML file first.
+    pub fn localhost_defaults() -> Result<Config, String> {
+        let mut conf = Config::from_string(LOCALHOST_YAML)?;
+        conf.set_hostname("localhost");
+        Ok(conf)
+    }
+
+    /// Loads and parses the YAML file at the provided path.
+    pub fn from_file(filename: &str) -> Result<Config, String> {
+        match fs::read_to_string(filename) {
+            Ok(text) => Config::from_string(&text),
+            Err(e) => Err(format!("Error reading configuration file: file={filename} {e}")),
+        }
+    }
+
+    pub fn from_string(yaml_text: &str) -> Result<Config, String> {
+        let op = YamlLoader::load_from_str(yaml_text);
+
+        if let Err(e) = op {
+            return Err(format!("Error parsing configuration file: {e}"));
+        }
+
+        let docs = op.unwrap();
+        let root = match docs.first() {
+            Some(doc) => doc,
+            None => return Err("Empty configuration file".to_string()),
+        };
+
+        let mut conf = Config {
+            hostname: Config::default_hostname(),
+            nodes: Vec::new(),
+            credentials: HashMap::new(),
+            connections: HashMap::new(),
+            services: HashMap::new(),
+            log_protect: Vec::new(),
+            log_options: LogOptions::default(),
+            primary_connection: None,
+            host_settings: None,
+        };
+
+        conf.apply_message_bus(&root["message-bus"])?;
+        conf.apply_logging(&root["logging"])?;
+        conf.apply_services(&root["services"])?;
+
+        if let Yaml::Array(arr) = &root["log-protect"] {
+            for api in arr {
+                if let Some(api) = api.as_str() {
+                    conf.log_protect.push(api.to_string());
+                }
+            }
+        }
+
+        Ok(conf)
+    }
+
+    fn default_hostname() -> String {
+        gethostname::gethostname()
+            .into_string()
+            .unwrap_or_else(|_| "localhost".to_string())
+    }
+
+    fn apply_message_bus(&mut self, bus: &Yaml) -> Result<(), String> {
+        if bus.is_badvalue() {
+            return Err("Configuration requires a message-bus section".to_string());
+        }
+
+        // The namespace is process-wide state -- every address
+        // embeds it -- so it's applied globally rather than stored.
+        if let Some(namespace) = bus["namespace"].as_str() {
+            super::addr::set_bus_namespace(namespace)?;
+        }
+
+        if let Yaml::Array(arr) = &bus["nodes"] {
+            for node in arr {
+                let name = node["name"]
+                    .as_str()
+                    .ok_or("message-bus node requires a name")?;
+
+                let port = match node["port"].as_i64() {
+                    Some(p) => p as u16,
+                    None => DEFAULT_BUS_PORT,
+                };
+
+                let db = node["db"].as_i64().unwrap_or(0);
+
+                let mut addresses = Vec::new();
+                if let Yaml::Array(arr) = &node["addresses"] {
+                    for addr in arr {
+                        if let Some(addr) = addr.as_str() {
+                            addresses.push(addr.to_string());
+                        }
+                    }
+                }
+
+                let mut sentinels = Vec::new();
+                if let Yaml::Array(arr) = &node["sentinels"] {
+                    for addr in arr {
+                        if let Some(addr) = addr.as_str() {
+                            sentinels.push(addr.to_string());
+                        }
+                    }
+                }
+
+                let sentinel_master = node["sentinel-master"].as_str().map(|s| s.to_string());
+
+                let mut cluster_nodes = Vec::new();
+                if let Yaml::Array(arr) = &node["cluster-nodes"] {
+                    for addr in arr {
+                        if let Some(addr) = addr.as_str() {
+                            cluster_nodes.push(addr.to_string());
+                        }
+                    }
+                }
+
+                let tls = match &node["tls"] {
+                    Yaml::Hash(_) => Some(BusNodeTls {
+                        enabled: node["tls"]["enabled"].as_bool().unwrap_or(true),
+                        ca_file: node["tls"]["ca-file"].as_str().map(|s| s.to_string()),
+                        cert_file: node["tls"]["cert-file"].as_str().map(|s| s.to_string()),
+                        key_file: node["tls"]["key-file"].as_str().map(|s| s.to_string()),
+                        verify: node["tls"]["verify"].as_bool().unwrap_or(true),
+                    }),
+                    _ => None,
+                };
+
+                let socket_path = node["socket-path"].as_str().map(|s| s.to_string());
+
+                let max_message_age = node["max-message-age"].as_i64().map(|v| v as u64);
+
+                self.nodes.push(BusNode {
+                    name: name.to_string(),
+                    port,
+                    db,
+                    addresses,
+                    sentinels,
+                    sentinel_master,
+                    cluster_nodes,
+                    tls,
+                    socket_path,
+                    trim_policy: parse_trim_policy(node),
+                    max_message_age,
+                });
+            }
+        }
+
+        if self.nodes.is_empty() {
+            return Err("message-bus requires at least one node".to_string());
+        }
+
+        if let Yaml::Hash(hash) = &bus["credentials"] {
+            for (name, creds) in hash {
+                let name = name.as_str().ok_or("Invalid credentials name")?;
+
+                let username = creds["username"]
+                    .as_str()
+                    .ok_or_else(|| format!("Credentials '{name}' require a username"))?;
+                let password = creds["password"]
+                    .as_str()
+                    .ok_or_else(|| format!("Credentials '{name}' require a password"))?;
+
+                self.credentials.insert(
+                    name.to_string(),
+                    BusCredentials {
+                        username: username.to_string(),
+                        password: password.to_string(),
+                    },
+                );
+            }
+        }
+
+        if let Yaml::Hash(hash) = &bus["connections"] {
+            for (name, con) in hash {
+                let name = name.as_str().ok_or("Invalid connection name")?;
+
+                let credentials = con["credentials"]
+                    .as_str()
+                    .ok_or_else(|| format!("Connection '{name}' requires credentials"))?;
+
+                if !self.credentials.contains_key(credentials) {
+                    return Err(format!(
+                        "Connection '{name}' references unknown credentials '{credentials}'"
+                    ));
+                }
+
+                self.connections.insert(
+                    name.to_string(),
+                    BusConnectionType {
+                        credentials: credentials.to_string(),
+                    },
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_logging(&mut self, logging: &Yaml) -> Result<(), String> {
+        if logging.is_badvalue() {
+            return Ok(());
+        }
+
+        if let Some(level) = logging["log-level"].as_str() {
+            self.log_options.log_level = LogOptions::level_filter_from_str(level);
+        }
+
+        if let Some(dest) = logging["destination"].as_str() {
+            self.log_options.log_file = match dest {
+                "syslog" => LogFile::Syslog,
+                "stderr" => LogFile::Stderr,
+                filename => LogFile::Filename(filename.to_string()),
+            };
+        }
+
+        if let Some(facility) = logging["syslog-facility"].as_str() {
+            self.log_options.syslog_facility = Some(facility.to_string());
+        }
+
+        if let Some(facility) = logging["activity-log-facility"].as_str() {
+            self.log_options.activity_log_facility = Some(facility.to_string());
+        }
+
+        Ok(())
+    }
+
+    fn apply_services(&mut self, services: &Yaml) -> Result<(), String> {
+        if let Yaml::Hash(hash) = services {
+            for (name, svc) in hash {
+                let name = name.as_str().ok_or("Invalid service name")?;
+
+                let mut options = ServiceOptions::default();
+
+                if let Some(v) = svc["workers"]["min"].as_i64() {
+                    options.min_workers = v as usize;
+                }
+                if let Some(v) = svc["workers"]["max"].as_i64() {
+                    options.max_workers = v as usize;
+                }
+                if let Some(v) = svc["workers"]["min-idle"].as_i64() {
+                    options.min_idle = v as usize;
+                }
+                if let Some(v) = svc["workers"]["max-idle"].as_i64() {
+                    options.max_idle = v as usize;
+                }
+                if let Some(v) = svc["max-requests"].as_i64() {
+                    options.max_requests = v as usize;
+                }
+                if let Some(v) = svc["keepalive"].as_i64() {
+                    options.keepalive = v as i32;
+                }
+                if let Some(v) = svc["max-queued"].as_i64() {
+                    options.max_queued = v as usize;
+                }
+                if let Some(v) = svc["shutdown-grace"].as_i64() {
+                    options.shutdown_grace = v as u64;
+                }
+                if let Some(v) = svc["max-runtime"].as_i64() {
+                    options.max_runtime = v as u64;
+                }
+                if let Some(v) = svc["busy-threshold"].as_i64() {
+                    options.busy_threshold = v as usize;
+                }
+                if let Some(v) = svc["use-settings-server"].as_bool() {
+                    options.use_settings_server = v;
+                }
+                if let Some(v) = svc["rate-limit"]["burst"].as_i64() {
+                    options.rate_limit_burst = v as u32;
+                }
+                if let Some(v) = svc["rate-limit"]["per-second"].as_i64() {
+                    options.rate_limit_per_sec = v as u32;
+                }
+                if let Yaml::Array(arr) = &svc["cpus"] {
+                    for cpu in arr {
+                        if let Some(cpu) = cpu.as_i64() {
+                            options.cpus.push(cpu as usize);
+                        }
+                    }
+                }
+                options.stream_trim = parse_trim_policy(svc);
+                if let Some(v) = svc["reliable"].as_bool() {
+                    options.reliable = v;
+                }
+
+                self.services.insert(name.to_string(), options);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wraps the config in an Arc for sharing across threads.
+    pub fn into_shared(self) -> Arc<Config> {
+        Arc::new(self)
+    }
+
+    pub fn hostname(&self) -> &str {
+        &self.hostname
+    }
+
+    pub fn set_hostname(&mut self, hostname: &str) {
+        self.hostname = hostname.to_string();
+    }
+
+    pub fn nodes(&self) -> &Vec<BusNode> {
+        &self.nodes
+    }
+
+    pub fn get_node(&self, name: &str) -> Option<&BusNode> {
+        self.nodes.iter().find(|n| n.name() == name)
+    }
+
+    pub fn log_protect(&self) -> &Vec<String> {
+        &self.log_protect
+    }
+
+    pub fn log_options(&self) -> &LogOptions {
+        &self.log_options
+    }
+
+    pub fn log_options_mut(&mut self) -> &mut LogOptions {
+        &mut self.log_options
+    }
+
+    /// Applies username and/or password overrides to every
+    /// configured credentials account, including any active primary
+    /// connection, e.g. from command line options or environment
+    /// variables.
+    pub fn override_bus_credentials(
+        &mut self,
+        username: Option<&str>,
+        password: Option<&str>,
+    ) {
+        for creds in self.credentials.values_mut() {
+            if let Some(username) = username {
+                creds.set_username(username);
+            }
+            if let Some(password) = password {
+                creds.set_password(password);
+            }
+        }
+
+        if let Some(con) = self.primary_connection.as_mut() {
+            if let Some(username) = username {
+                con.credentials_mut().set_username(username);
+            }
+            if let Some(password) = password {
+                con.credentials_mut().set_password(password);
+            }
+        }
+    }
+
+    pub fn services(&self) -> &HashMap<String, ServiceOptions> {
+        &self.services
+    }
+
+    pub fn service_options(&self, service: &str) -> Option<&ServiceOptions> {
+        self.services.get(service)
+    }
+
+    pub fn service_options_mut(&mut self, service: &str) -> Option<&mut ServiceOptions> {
+        self.services.get_mut(service)
+    }
+
+    /// Host settings blob fetched from opensrf.settings at startup,
+    /// if any -- the place applications look for their app-specific
+    /// settings.
+    pub fn host_settings(&self) -> Option<&JsonValue> {
+        self.host_settings.as_ref()
+    }
+
+    pub fn set_host_settings(&mut self, settings: JsonValue) {
+        self.host_settings = Some(settings);
+    }
+
+    /// The connection the process uses to talk to the bus, selected
+    /// via set_primary_connection().
+    pub fn primary_connection(&self) -> Option<&BusConnection> {
+        self.primary_connection.as_ref()
+    }
+
+    pub fn primary_connection_mut(&mut self) -> Option<&mut BusConnection> {
+        self.primary_connection.as_mut()
+    }
+
+    /// Selects the connection type and node this process should use
+    /// when connecting to the bus.
+    pub fn set_primary_connection(
+        &mut self,
+        connection_type: &str,
+        node_name: &str,
+    ) -> Result<&BusConnection, String> {
+        let con = self.new_bus_connection(connection_type, node_name)?;
+        self.primary_connection = Some(con);
+        Ok(self.primary_connection.as_ref().unwrap())
+    }
+
+    /// Builds a BusConnection for the provided connection type and
+    /// node name.
+    pub fn new_bus_connection(
+        &self,
+        connection_type: &str,
+        node_name: &str,
+    ) -> Result<BusConnection, String> {
+        let con_type = self
+            .connections
+            .get(connection_type)
+            .ok_or_else(|| format!("No such connection type: {connection_type}"))?;
+
+        let node = self
+            .get_node(node_name)
+            .ok_or_else(|| format!("No such bus node: {node_name}"))?;
+
+        let creds = self
+            .credentials
+            .get(con_type.credentials())
+            .ok_or_else(|| format!("No such credentials: {}", con_type.credentials()))?;
+
+        Ok(BusConnection {
+            node: node.clone(),
+            credentials: creds.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub const TEST_YAML: &str = r#"
+message-bus:
+  nodes:
+    - name: localhost
+      port: 6379
+  credentials:
+    default:
+      username: opensrf
+      password: password
+  connections:
+    service:
+      credentials: default
+    client:
+      credentials: default
+logging:
+  log-level: debug
+  destination: stderr
+log-protect:
+  - open-ils.auth.authenticate.verify
+services:
+  opensrf.rsdemo:
+    workers:
+      min: 2
+      max: 20
+      min-idle: 2
+      max-idle: 5
+    keepalive: 6
+    shutdown-grace: 15
+    max-runtime: 90
+    busy-threshold: 100
+    use-settings-server: true
+    rate-limit:
+      burst: 20
+      per-second: 5
+"#;
+
+    #[test]
+    fn test_parse_config() {
+        let conf = Config::from_string(TEST_YAML).unwrap();
+
+        assert_eq!(conf.nodes().len(), 1);
+        assert_eq!(conf.nodes()[0].name(), "localhost");
+        assert_eq!(conf.nodes()[0].port(), 6379);
+        assert_eq!(conf.log_options().log_level(), &log::LevelFilter::Debug);
+        assert_eq!(conf.log_protect().len(), 1);
+
+        let svc = conf.service_options("opensrf.rsdemo").unwrap();
+        assert_eq!(svc.min_workers(), 2);
+        assert_eq!(svc.max_workers(), 20);
+        assert_eq!(svc.min_idle(), 2);
+        assert_eq!(svc.max_idle(), 5);
+        assert_eq!(svc.keepalive(), 6);
+        assert_eq!(svc.shutdown_grace(), 15);
+        assert_eq!(svc.max_runtime(), 90);
+        assert_eq!(svc.busy_threshold(), 100);
+        assert!(svc.use_settings_server());
+        assert_eq!(svc.rate_limit_burst(), 20);
+        assert_eq!(svc.rate_limit_per_sec(), 5);
+    }
+
+    #[test]
+    fn test_apply_host_settings() {
+        let mut conf = Config::from_string(TEST_YAML).unwrap();
+
+        let stanza = json::object! {
+            workers: { max: 50, "max-idle": 10 },
+            keepalive: 12
+        };
+
+        let svc = conf.service_options_mut("opensrf.rsdemo").unwrap();
+        svc.apply_host_settings(&stanza);
+
+        assert_eq!(svc.min_workers(), 2);
+        assert_eq!(svc.max_workers(), 50);
+        assert_eq!(svc.max_idle(), 10);
+        assert_eq!(svc.keepalive(), 12);
+        assert_eq!(svc.max_runtime(), 90);
+    }
+
+    #[test]
+    fn test_localhost_defaults() {
+        let conf = Config::localhost_defaults().unwrap();
+
+        assert_eq!(conf.hostname(), "localhost");
+        assert_eq!(conf.nodes()[0].name(), "localhost");
+        assert_eq!(conf.log_options().log_file(), &LogFile::Stderr);
+        assert!(conf.new_bus_connection("client", "localhost").is_ok());
+    }
+
+    #[test]
+    fn test_primary_connection() {
+        let mut conf = Config::from_string(TEST_YAML).unwrap();
+
+        assert!(conf.primary_connection().is_none());
+        assert!(conf.set_primary_connection("client", "localhost").is_ok());
+        assert!(conf.set_primary_connection("client", "no-such-node").is_err());
+        assert!(conf.set_primary_connection("nope", "localhost").is_err());
+
+        let con = conf.primary_connection().unwrap();
+        assert_eq!(con.domain(), "localhost");
+        assert_eq!(con.credentials().username(), "opensrf");
+    }
+}
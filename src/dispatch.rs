@@ -0,0 +1,260 @@
+//! Shared-connection dispatcher for multi-threaded programs.
+//!
+//! One background thread owns the Bus; worker threads submit
+//! requests and collect responses over channels.  This provides
+//! Send-able request handles without opening a separate Redis
+//! connection per thread, which matters for large gateway
+//! processes with many short-lived worker threads.
+//!
+//! Only stateless (router-style) requests are supported; stateful
+//! CONNECT conversations still require a per-thread Client.
+use super::addr::ServiceAddress;
+use super::bus::Bus;
+use super::conf;
+use super::message;
+use super::message::{Message, MessageStatus, MessageType, Payload, TransportMessage};
+use super::util;
+use json::JsonValue;
+use log::{error, trace};
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How long (seconds) the dispatcher blocks on the bus per loop
+/// iteration before checking its command channel again.
+const POLL_TIME: Duration = Duration::from_secs(1);
+
+enum DispatchCommand {
+    Request {
+        service: String,
+        method: String,
+        params: Vec<JsonValue>,
+        reply_tx: mpsc::Sender<DispatchReply>,
+    },
+    Shutdown,
+}
+
+enum DispatchReply {
+    Value(JsonValue),
+    Complete,
+    Error(String),
+}
+
+/// The background thread: owns the bus connection and routes
+/// responses back to the requesting threads.
+pub struct Dispatcher {
+    bus: Bus,
+    command_rx: mpsc::Receiver<DispatchCommand>,
+
+    /// In-flight requests keyed by session thread.
+    pending: HashMap<String, mpsc::Sender<DispatchReply>>,
+}
+
+impl Dispatcher {
+    /// Starts the dispatcher thread, returning a cloneable handle
+    /// for submitting requests.
+    pub fn spawn(config: Arc<conf::Config>) -> Result<DispatcherClient, String> {
+        let con = config
+            .primary_connection()
+            .expect("Dispatcher::spawn() requires a primary bus connection");
+
+        let bus = Bus::new(con)?;
+
+        let (command_tx, command_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            Dispatcher {
+                bus,
+                command_rx,
+                pending: HashMap::new(),
+            }
+            .run()
+        });
+
+        Ok(DispatcherClient { commands: command_tx })
+    }
+
+    fn run(mut self) {
+        loop {
+            // Drain all queued commands first.
+            loop {
+                match self.command_rx.try_recv() {
+                    Ok(DispatchCommand::Shutdown) => return,
+                    Ok(command) => self.handle_command(command),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    // All client handles are gone; nothing left to do.
+                    Err(mpsc::TryRecvError::Disconnected) => return,
+                }
+            }
+
+            if self.pending.is_empty() {
+                // Nothing in flight; wait on the command channel
+                // instead of hammering the bus.
+                match self.command_rx.recv() {
+                    Ok(DispatchCommand::Shutdown) => return,
+                    Ok(command) => self.handle_command(command),
+                    Err(_) => return,
+                }
+                continue;
+            }
+
+            match self.bus.recv(POLL_TIME, None) {
+                Ok(Some(tmsg)) => self.route_reply(tmsg),
+                Ok(None) => {}
+                Err(e) => error!("Dispatcher recv error: {e}"),
+            }
+        }
+    }
+
+    fn handle_command(&mut self, command: DispatchCommand) {
+        let (service, method, params, reply_tx) = match command {
+            DispatchCommand::Request {
+                service,
+                method,
+                params,
+                reply_tx,
+            } => (service, method, params, reply_tx),
+            DispatchCommand::Shutdown => return,
+        };
+
+        let thread = util::random_number(16);
+
+        trace!("Dispatcher sending {method} to {service} on thread {thread}");
+
+        let payload = Payload::Method(message::Method::new(&method, params));
+
+        let tmsg = TransportMessage::with_body(
+            ServiceAddress::new(&service).full(),
+            self.bus.address().full(),
+            &thread,
+            Message::new(MessageType::Request, 1, payload),
+        );
+
+        if let Err(e) = self.bus.send(&tmsg) {
+            reply_tx.send(DispatchReply::Error(e)).ok();
+            return;
+        }
+
+        self.pending.insert(thread, reply_tx);
+    }
+
+    fn route_reply(&mut self, mut tmsg: TransportMessage) {
+        let thread = tmsg.thread().to_string();
+
+        let reply_tx = match self.pending.get(&thread) {
+            Some(tx) => tx.clone(),
+            None => {
+                error!("Dispatcher received reply for unknown thread {thread}");
+                return;
+            }
+        };
+
+        let mut done = false;
+
+        for mut msg in tmsg.body_mut().drain(..) {
+            match msg.take_payload() {
+                Payload::Result(mut res) => {
+                    reply_tx.send(DispatchReply::Value(res.take_content())).ok();
+                }
+                Payload::Status(stat) => match stat.status() {
+                    MessageStatus::Complete => {
+                        reply_tx.send(DispatchReply::Complete).ok();
+                        done = true;
+                    }
+                    MessageStatus::Continue | MessageStatus::Ok => {}
+                    _ => {
+                        reply_tx
+                            .send(DispatchReply::Error(format!("Request failed: {stat}")))
+                            .ok();
+                        done = true;
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        if done {
+            self.pending.remove(&thread);
+        }
+    }
+}
+
+/// Cloneable, Send-able handle for submitting requests to the
+/// dispatcher thread.
+#[derive(Clone)]
+pub struct DispatcherClient {
+    commands: mpsc::Sender<DispatchCommand>,
+}
+
+impl DispatcherClient {
+    /// Submits a stateless request, returning a handle for
+    /// response collection.
+    pub fn request(
+        &self,
+        service: &str,
+        method: &str,
+        params: Vec<JsonValue>,
+    ) -> Result<DispatcherRequest, String> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.commands
+            .send(DispatchCommand::Request {
+                service: service.to_string(),
+                method: method.to_string(),
+                params,
+                reply_tx,
+            })
+            .map_err(|e| format!("Dispatcher is gone: {e}"))?;
+
+        Ok(DispatcherRequest {
+            replies: reply_rx,
+            complete: false,
+        })
+    }
+
+    /// Asks the dispatcher thread to exit.
+    pub fn shutdown(&self) {
+        self.commands.send(DispatchCommand::Shutdown).ok();
+    }
+}
+
+/// An in-flight request submitted through the dispatcher.
+pub struct DispatcherRequest {
+    replies: mpsc::Receiver<DispatchReply>,
+    complete: bool,
+}
+
+impl DispatcherRequest {
+    /// Returns the next response, waiting up to timeout seconds.
+    ///
+    /// Returns None once the request is complete or the timeout is
+    /// exceeded.
+    pub fn recv(&mut self, timeout: Duration) -> Result<Option<JsonValue>, String> {
+        if self.complete {
+            return Ok(None);
+        }
+
+        match self.replies.recv_timeout(timeout) {
+            Ok(DispatchReply::Value(v)) => Ok(Some(v)),
+            Ok(DispatchReply::Complete) => {
+                self.complete = true;
+                Ok(None)
+            }
+            Ok(DispatchReply::Error(e)) => {
+                self.complete = true;
+                Err(e)
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                self.complete = true;
+                Err("Dispatcher is gone".to_string())
+            }
+        }
+    }
+
+    pub fn complete(&self) -> bool {
+        self.complete
+    }
+}
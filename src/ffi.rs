@@ -0,0 +1,228 @@
+//! Stable C ABI over the Client/Session layer.
+//!
+//! Intended for embedding the Rust bus implementation in C and
+//! Perl(XS) components during a gradual migration.  Handles are
+//! opaque pointers owned by the caller and released via the
+//! matching *_free / disconnect functions.  Response values are
+//! returned as JSON strings which must be released with
+//! osrf_free().
+//!
+//! All functions tolerate NULL inputs and report failure by
+//! returning NULL.
+use super::client::Client;
+use super::conf;
+use super::session::Request;
+use log::error;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+/// Opaque client handle for C callers.
+pub struct OsrfClient {
+    client: Client,
+}
+
+/// Opaque in-flight request handle for C callers.
+pub struct OsrfRequest {
+    request: Request,
+}
+
+/// Copies a C string into a Rust String.
+///
+/// # Safety
+///
+/// The pointer must be NULL or reference a NUL-terminated string.
+unsafe fn string_from_c(s: *const c_char) -> Option<String> {
+    if s.is_null() {
+        return None;
+    }
+
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Some(s.to_string()),
+        Err(e) => {
+            error!("Invalid UTF-8 in C string: {e}");
+            None
+        }
+    }
+}
+
+/// Connects to the bus, returning a new client handle.
+///
+/// The configuration is read from the provided file.  The domain
+/// selects the bus node to connect to; pass NULL to use the first
+/// configured node.  Returns NULL on any failure.
+///
+/// # Safety
+///
+/// String arguments must be NULL or NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn osrf_client_connect(
+    config_file: *const c_char,
+    domain: *const c_char,
+) -> *mut OsrfClient {
+    let config_file = match string_from_c(config_file) {
+        Some(f) => f,
+        None => return ptr::null_mut(),
+    };
+
+    let mut config = match conf::Config::from_file(&config_file) {
+        Ok(c) => c,
+        Err(e) => {
+            error!("osrf_client_connect: {e}");
+            return ptr::null_mut();
+        }
+    };
+
+    let domain = match string_from_c(domain) {
+        Some(d) => d,
+        None => match config.nodes().first() {
+            Some(node) => node.name().to_string(),
+            None => {
+                error!("osrf_client_connect: config defines no bus nodes");
+                return ptr::null_mut();
+            }
+        },
+    };
+
+    if let Err(e) = config.set_primary_connection("client", &domain) {
+        error!("osrf_client_connect: {e}");
+        return ptr::null_mut();
+    }
+
+    match Client::connect(config.into_shared()) {
+        Ok(client) => Box::into_raw(Box::new(OsrfClient { client })),
+        Err(e) => {
+            error!("osrf_client_connect: {e}");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Issues a request to the provided service.
+///
+/// Params are passed as a JSON array string, e.g. "[1, \"two\"]";
+/// NULL means no params.  Returns a request handle for response
+/// collection, or NULL on failure.
+///
+/// # Safety
+///
+/// The client must be a handle from osrf_client_connect() that has
+/// not been disconnected.  String arguments must be NULL or
+/// NUL-terminated.
+#[no_mangle]
+pub unsafe extern "C" fn osrf_request(
+    client: *mut OsrfClient,
+    service: *const c_char,
+    method: *const c_char,
+    params_json: *const c_char,
+) -> *mut OsrfRequest {
+    if client.is_null() {
+        return ptr::null_mut();
+    }
+
+    let (service, method) = match (string_from_c(service), string_from_c(method)) {
+        (Some(s), Some(m)) => (s, m),
+        _ => return ptr::null_mut(),
+    };
+
+    let params: Vec<json::JsonValue> = match string_from_c(params_json) {
+        Some(p) => match json::parse(&p) {
+            Ok(json::JsonValue::Array(list)) => list,
+            Ok(_) => {
+                error!("osrf_request: params must be a JSON array");
+                return ptr::null_mut();
+            }
+            Err(e) => {
+                error!("osrf_request: cannot parse params: {e}");
+                return ptr::null_mut();
+            }
+        },
+        None => Vec::new(),
+    };
+
+    let session = (*client).client.session(&service);
+
+    match session.request(&method, params) {
+        Ok(request) => Box::into_raw(Box::new(OsrfRequest { request })),
+        Err(e) => {
+            error!("osrf_request: {e}");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Returns the next response as a JSON string, waiting up to
+/// timeout seconds.
+///
+/// Returns NULL once the request is complete, on timeout, or on
+/// error.  The returned string must be released with osrf_free().
+///
+/// # Safety
+///
+/// The request must be a handle from osrf_request() that has not
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn osrf_recv(request: *mut OsrfRequest, timeout: c_int) -> *mut c_char {
+    if request.is_null() {
+        return ptr::null_mut();
+    }
+
+    let response = match (*request)
+        .request
+        .recv(std::time::Duration::from_secs(timeout.max(0) as u64))
+    {
+        Ok(Some(r)) => r,
+        Ok(None) => return ptr::null_mut(),
+        Err(e) => {
+            error!("osrf_recv: {e}");
+            return ptr::null_mut();
+        }
+    };
+
+    match CString::new(response.dump()) {
+        Ok(s) => s.into_raw(),
+        Err(e) => {
+            error!("osrf_recv: response contains a NUL byte: {e}");
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Releases a string returned by osrf_recv().
+///
+/// # Safety
+///
+/// The pointer must be NULL or a string returned by osrf_recv().
+#[no_mangle]
+pub unsafe extern "C" fn osrf_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Releases a request handle.
+///
+/// # Safety
+///
+/// The pointer must be NULL or a handle from osrf_request().
+#[no_mangle]
+pub unsafe extern "C" fn osrf_request_free(request: *mut OsrfRequest) {
+    if !request.is_null() {
+        drop(Box::from_raw(request));
+    }
+}
+
+/// Disconnects and releases a client handle.
+///
+/// Any request handles created from this client must be freed
+/// first.
+///
+/// # Safety
+///
+/// The pointer must be NULL or a handle from osrf_client_connect().
+#[no_mangle]
+pub unsafe extern "C" fn osrf_client_disconnect(client: *mut OsrfClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
@@ -0,0 +1,107 @@
+use super::conf;
+use super::logging::Logger;
+use std::env;
+
+const DEFAULT_OSRF_CONFIG: &str = "conf/opensrf.yml";
+
+/// Parses the core command line options, loads the configuration
+/// file, and activates the logger.
+///
+/// Returns the parsed Config for the caller to refine (e.g. set the
+/// primary connection) plus the getopts Matches so callers can pull
+/// values for any options they added.
+pub fn init() -> Result<(conf::Config, getopts::Matches), String> {
+    init_with_options(&mut getopts::Options::new())
+}
+
+/// Zero-configuration init for local development.
+///
+/// Skips option parsing and the configuration file entirely,
+/// activating Config::localhost_defaults() with a stderr logger.
+pub fn init_dev() -> Result<conf::Config, String> {
+    let config = conf::Config::localhost_defaults()?;
+
+    let logger = Logger::new(config.log_options())?;
+
+    if let Err(e) = logger.init() {
+        return Err(format!("Error initializing logger: {e}"));
+    }
+
+    Ok(config)
+}
+
+/// Same as init(), but callers may add their own command line
+/// options to the mix before parsing occurs.
+pub fn init_with_options(
+    opts: &mut getopts::Options,
+) -> Result<(conf::Config, getopts::Matches), String> {
+    opts.optflag("l", "localhost", "Use localhost as the host/domain name");
+    opts.optopt("h", "hostname", "hostname of this host", "HOSTNAME");
+    opts.optopt("c", "osrf-config", "OpenSRF configuration file", "OSRF_CONFIG");
+    opts.optopt("", "bus-username", "Bus username override", "BUS_USERNAME");
+    opts.optopt("", "bus-password", "Bus password override", "BUS_PASSWORD");
+    opts.optopt("", "log-level", "Log level override", "LOG_LEVEL");
+    opts.optopt("", "log-facility", "Syslog facility override", "LOG_FACILITY");
+    opts.optflag("", "log-stderr", "Log to STDERR regardless of configuration");
+
+    let args: Vec<String> = env::args().collect();
+
+    let params = match opts.parse(&args[1..]) {
+        Ok(p) => p,
+        Err(e) => return Err(format!("Error parsing options: {e}")),
+    };
+
+    let filename = match params.opt_str("osrf-config") {
+        Some(f) => f,
+        None => match env::var("OSRF_CONFIG") {
+            Ok(f) => f,
+            Err(_) => DEFAULT_OSRF_CONFIG.to_string(),
+        },
+    };
+
+    let mut config = conf::Config::from_file(&filename)?;
+
+    if params.opt_present("localhost") {
+        config.set_hostname("localhost");
+    } else if let Some(hostname) = params.opt_str("hostname") {
+        config.set_hostname(&hostname);
+    }
+
+    // Command line wins over the environment for credential
+    // overrides, so tooling can run with e.g. an admin bus account.
+    let bus_username = params
+        .opt_str("bus-username")
+        .or_else(|| env::var("OSRF_BUS_USERNAME").ok());
+
+    let bus_password = params
+        .opt_str("bus-password")
+        .or_else(|| env::var("OSRF_BUS_PASSWORD").ok());
+
+    if bus_username.is_some() || bus_password.is_some() {
+        config.override_bus_credentials(bus_username.as_deref(), bus_password.as_deref());
+    }
+
+    // Logging overrides apply before the logger is built, so a
+    // single process can be debugged at e.g. trace level without
+    // editing the shared config file.
+    if let Some(level) = params.opt_str("log-level") {
+        let level = conf::LogOptions::level_filter_from_str(&level);
+        config.log_options_mut().set_log_level(level);
+    }
+
+    if let Some(facility) = params.opt_str("log-facility") {
+        config.log_options_mut().set_syslog_facility(&facility);
+    }
+
+    if params.opt_present("log-stderr") {
+        config.log_options_mut().set_log_file(conf::LogFile::Stderr);
+    }
+
+    let logger = Logger::new(config.log_options())?;
+
+    if let Err(e) = logger.init() {
+        return Err(format!("Error initializing logger: {e}"));
+    }
+
+    Ok((config, params))
+}
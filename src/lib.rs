@@ -0,0 +1,57 @@
+// Modules below make up the portable client core and compile for
+// wasm32; the remainder require native targets and are grouped by
+// cargo feature: "client" for the bus/session layer, "init" for the
+// command line init layer, "server" for the server/worker side.
+pub mod classified;
+pub mod message;
+pub mod params;
+pub mod util;
+pub mod websocket;
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub mod addr;
+#[cfg(all(not(target_arch = "wasm32"), feature = "async"))]
+pub mod async_bus;
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+pub mod app;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub mod auth;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub mod bus;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub mod client;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub mod conf;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub mod dispatch;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub mod ffi;
+#[cfg(all(not(target_arch = "wasm32"), feature = "init"))]
+pub mod init;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub mod logging;
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+pub mod method;
+#[cfg(all(not(target_arch = "wasm32"), feature = "nats"))]
+pub mod nats_bus;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub mod sclient;
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+pub mod server;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub mod session;
+#[cfg(all(not(target_arch = "wasm32"), feature = "server"))]
+pub mod worker;
+#[cfg(all(not(target_arch = "wasm32"), feature = "xmpp"))]
+pub mod xmpp_bus;
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub use client::Client;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub use conf::Config;
+#[cfg(all(not(target_arch = "wasm32"), feature = "init"))]
+pub use init::init_dev;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub use logging::Logger;
+#[cfg(all(not(target_arch = "wasm32"), feature = "client"))]
+pub use session::SessionHandle;
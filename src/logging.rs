@@ -0,0 +1,264 @@
+use super::conf;
+use super::util;
+use log::{Level, LevelFilter, Metadata, Record};
+use std::cell::RefCell;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use std::process;
+
+const SYSLOG_UNIX_PATH: &str = "/dev/log";
+
+thread_local! {
+    /// Key/value pairs appended to every log line emitted by this
+    /// thread; see Logger::push_context().
+    static LOG_CONTEXT: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+
+    /// Correlation id for the request this thread is currently
+    /// servicing; see Logger::set_xid().
+    static CURRENT_XID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Removes the context pair it was created for when dropped.
+#[must_use = "context is removed when the guard is dropped"]
+pub struct LogContextGuard {
+    _priv: (),
+}
+
+impl Drop for LogContextGuard {
+    fn drop(&mut self) {
+        LOG_CONTEXT.with(|ctx| {
+            ctx.borrow_mut().pop();
+        });
+    }
+}
+
+/// Main logger for OpenSRF processes, implementing the log::Log
+/// facade so the standard log macros may be used throughout.
+///
+/// Messages are delivered to syslog, STDERR, or a flat file per
+/// the logging configuration.
+pub struct Logger {
+    options: conf::LogOptions,
+    application: String,
+}
+
+impl Logger {
+    pub fn new(options: &conf::LogOptions) -> Result<Self, String> {
+        Ok(Logger {
+            options: options.clone(),
+            application: Logger::find_application_name(),
+        })
+    }
+
+    fn find_application_name() -> String {
+        if let Some(name) = std::env::args().next() {
+            if let Some(name) = name.split('/').next_back() {
+                return name.to_string();
+            }
+        }
+
+        "opensrf".to_string()
+    }
+
+    pub fn set_application(&mut self, application: &str) {
+        self.application = application.to_string();
+    }
+
+    /// Attaches a key/value pair (e.g. patron id, workstation) to
+    /// every log line emitted by this thread until the returned
+    /// guard is dropped.
+    pub fn push_context(key: &str, value: &str) -> LogContextGuard {
+        LOG_CONTEXT.with(|ctx| {
+            ctx.borrow_mut()
+                .push((key.to_string(), value.to_string()));
+        });
+
+        LogContextGuard { _priv: () }
+    }
+
+    /// Drops all log context for this thread, e.g. at the end of a
+    /// request in case any guards were leaked into longer-lived
+    /// state.
+    pub fn clear_context() {
+        LOG_CONTEXT.with(|ctx| ctx.borrow_mut().clear());
+    }
+
+    /// Builds a unique correlation ("XID") value: epoch
+    /// microseconds, our pid, and a random tail, so ids sort
+    /// roughly by creation time and collisions across hosts are
+    /// practically impossible.
+    pub fn mk_xid() -> String {
+        let micros = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_micros())
+            .unwrap_or(0);
+
+        format!("{micros}{}{}", process::id(), util::random_number(4))
+    }
+
+    /// Sets the correlation id for the request this thread is
+    /// servicing; outbound messages built on this thread carry it
+    /// so one user action can be traced across services and hosts.
+    pub fn set_xid(xid: &str) {
+        CURRENT_XID.with(|x| *x.borrow_mut() = Some(xid.to_string()));
+    }
+
+    /// Drops this thread's correlation id, e.g. once its request
+    /// is complete.
+    pub fn clear_xid() {
+        CURRENT_XID.with(|x| *x.borrow_mut() = None);
+    }
+
+    /// The correlation id for the request this thread is currently
+    /// servicing, if any.
+    pub fn xid() -> Option<String> {
+        CURRENT_XID.with(|x| x.borrow().clone())
+    }
+
+    /// The current thread's context pairs, formatted for appending
+    /// to a log line.
+    fn context_string() -> String {
+        LOG_CONTEXT.with(|ctx| {
+            let ctx = ctx.borrow();
+
+            if ctx.is_empty() {
+                return String::new();
+            }
+
+            let pairs: Vec<String> =
+                ctx.iter().map(|(k, v)| format!("{k}={v}")).collect();
+
+            format!(" [{}]", pairs.join(" "))
+        })
+    }
+
+    pub fn log_level(&self) -> &LevelFilter {
+        self.options.log_level()
+    }
+
+    /// Installs the logger as the global logger for the log crate.
+    ///
+    /// May only be called once per process.
+    pub fn init(self) -> Result<(), log::SetLoggerError> {
+        log::set_max_level(*self.options.log_level());
+        log::set_boxed_logger(Box::new(self))
+    }
+
+    /// Encode the syslog priority value from our facility + severity.
+    fn syslog_priority(&self, level: Level) -> u8 {
+        let facility: u8 = match self.options.syslog_facility() {
+            Some(f) => Logger::facility_number(f),
+            None => 16, // local0
+        };
+
+        let severity: u8 = match level {
+            Level::Error => 3,
+            Level::Warn => 4,
+            Level::Info => 6,
+            Level::Debug | Level::Trace => 7,
+        };
+
+        facility * 8 + severity
+    }
+
+    fn facility_number(facility: &str) -> u8 {
+        match facility.to_lowercase().as_str() {
+            "user" => 1,
+            "daemon" => 3,
+            "local0" => 16,
+            "local1" => 17,
+            "local2" => 18,
+            "local3" => 19,
+            "local4" => 20,
+            "local5" => 21,
+            "local6" => 22,
+            "local7" => 23,
+            _ => 16,
+        }
+    }
+
+    fn level_tag(level: Level) -> &'static str {
+        match level {
+            Level::Error => "ERR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+
+    fn format_line(&self, record: &Record) -> String {
+        let target = if !record.target().is_empty() {
+            record.target()
+        } else {
+            "opensrf"
+        };
+
+        format!(
+            "{} [{}:{}:{}] {}{}",
+            Logger::level_tag(record.level()),
+            process::id(),
+            target,
+            match record.line() {
+                Some(l) => l.to_string(),
+                None => String::from("0"),
+            },
+            record.args(),
+            Logger::context_string()
+        )
+    }
+
+    fn write_stderr(&self, message: &str) {
+        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+        eprintln!("{timestamp} {} {message}", self.application);
+    }
+
+    fn write_file(&self, filename: &str, message: &str) {
+        let timestamp = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f");
+
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(filename) {
+            writeln!(file, "{timestamp} {} {message}", self.application).ok();
+        }
+    }
+
+    fn write_syslog(&self, level: Level, message: &str) {
+        let datagram = match UnixDatagram::unbound() {
+            Ok(s) => s,
+            Err(_) => return self.write_stderr(message),
+        };
+
+        let line = format!(
+            "<{}>{} {}",
+            self.syslog_priority(level),
+            self.application,
+            message
+        );
+
+        if datagram.send_to(line.as_bytes(), SYSLOG_UNIX_PATH).is_err() {
+            self.write_stderr(message);
+        }
+    }
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= *self.options.log_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let message = self.format_line(record);
+
+        match self.options.log_file() {
+            conf::LogFile::Syslog => self.write_syslog(record.level(), &message),
+            conf::LogFile::Stderr => self.write_stderr(&message),
+            conf::LogFile::Filename(f) => self.write_file(f, &message),
+        }
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,46 @@
+//! Example client exercising the demo service.
+use opensrf::client::Client;
+use opensrf::init;
+use std::time::Duration;
+
+fn main() -> Result<(), String> {
+    let (mut config, _params) = init::init()?;
+
+    let domain = config.nodes()[0].name().to_string();
+    config.set_primary_connection("client", &domain)?;
+
+    let client = Client::connect(config.into_shared())?;
+
+    let ses = client.session("opensrf.rsdemo");
+
+    // Stateless round-trip.
+    let mut req = ses.request("opensrf.rsdemo.echo", vec!["Hello", "World"])?;
+
+    while let Some(resp) = req.recv(Duration::from_secs(10))? {
+        println!("Echo response: {resp}");
+    }
+
+    // Stateful conversation with the same worker.
+    ses.connect()?;
+
+    let mut req = ses.request("opensrf.rsdemo.echo", vec!["Hello", "Again"])?;
+
+    while let Some(resp) = req.recv(Duration::from_secs(10))? {
+        println!("Echo response: {resp}");
+    }
+
+    let mut req = ses.request("opensrf.rsdemo.sleep", vec![2])?;
+
+    while let Some(resp) = req.recv(Duration::from_secs(10))? {
+        println!("Sleep response: {resp}");
+    }
+
+    ses.disconnect()?;
+
+    // Iterator flavor.
+    for resp in ses.sendrecv("opensrf.rsdemo.echo", vec!["Hello", "Iterator"])? {
+        println!("Echo response: {resp}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,779 @@
+use super::classified::ClassifiedJson;
+use super::util;
+use json::JsonValue;
+use std::fmt;
+
+const DEFAULT_API_LEVEL: u8 = 1;
+const DEFAULT_INGRESS: &str = "opensrf";
+
+/// The message types defined by the OpenSRF protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageType {
+    Connect,
+    Request,
+    Result,
+    Status,
+    Disconnect,
+    Unknown,
+}
+
+impl From<&str> for MessageType {
+    fn from(s: &str) -> Self {
+        match s {
+            "CONNECT" => MessageType::Connect,
+            "REQUEST" => MessageType::Request,
+            "RESULT" => MessageType::Result,
+            "STATUS" => MessageType::Status,
+            "DISCONNECT" => MessageType::Disconnect,
+            _ => MessageType::Unknown,
+        }
+    }
+}
+
+impl From<MessageType> for &'static str {
+    fn from(mtype: MessageType) -> &'static str {
+        match mtype {
+            MessageType::Connect => "CONNECT",
+            MessageType::Request => "REQUEST",
+            MessageType::Result => "RESULT",
+            MessageType::Status => "STATUS",
+            MessageType::Disconnect => "DISCONNECT",
+            MessageType::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+impl fmt::Display for MessageType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s: &str = (*self).into();
+        write!(f, "{s}")
+    }
+}
+
+/// Status codes, modeled after their HTTP equivalents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageStatus {
+    Error = -1,
+    Unknown = 0,
+    Continue = 100,
+    Ok = 200,
+    Accepted = 202,
+    Nocontent = 204,
+    Complete = 205,
+    Partial = 206,
+    PartialComplete = 207,
+    Redirected = 307,
+    BadRequest = 400,
+    Unauthorized = 401,
+    Forbidden = 403,
+    MethodNotFound = 404,
+    NotAllowed = 405,
+    ServiceNotFound = 406,
+    Timeout = 408,
+    Expfailed = 417,
+    InternalServerError = 500,
+    NotImplemented = 501,
+    ServiceUnavailable = 503,
+    VersionNotSupported = 505,
+}
+
+impl From<isize> for MessageStatus {
+    fn from(num: isize) -> Self {
+        match num {
+            100 => MessageStatus::Continue,
+            200 => MessageStatus::Ok,
+            202 => MessageStatus::Accepted,
+            204 => MessageStatus::Nocontent,
+            205 => MessageStatus::Complete,
+            206 => MessageStatus::Partial,
+            207 => MessageStatus::PartialComplete,
+            307 => MessageStatus::Redirected,
+            400 => MessageStatus::BadRequest,
+            401 => MessageStatus::Unauthorized,
+            403 => MessageStatus::Forbidden,
+            404 => MessageStatus::MethodNotFound,
+            405 => MessageStatus::NotAllowed,
+            406 => MessageStatus::ServiceNotFound,
+            408 => MessageStatus::Timeout,
+            417 => MessageStatus::Expfailed,
+            500 => MessageStatus::InternalServerError,
+            501 => MessageStatus::NotImplemented,
+            503 => MessageStatus::ServiceUnavailable,
+            505 => MessageStatus::VersionNotSupported,
+            _ => MessageStatus::Unknown,
+        }
+    }
+}
+
+impl MessageStatus {
+    pub fn is_4xx(&self) -> bool {
+        let num = *self as isize;
+        (400..500).contains(&num)
+    }
+    pub fn is_5xx(&self) -> bool {
+        let num = *self as isize;
+        num >= 500
+    }
+}
+
+impl fmt::Display for MessageStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} ({})", self, *self as isize)
+    }
+}
+
+/// The message payload is the data content of the message, which
+/// may be empty, e.g. for DISCONNECT messages.
+#[derive(Debug, Clone)]
+pub enum Payload {
+    Method(Method),
+    Result(Result),
+    Status(Status),
+    NoPayload,
+}
+
+impl Payload {
+    pub fn to_json_value(&self) -> JsonValue {
+        match self {
+            Payload::Method(pl) => pl.to_json_value(),
+            Payload::Result(pl) => pl.to_json_value(),
+            Payload::Status(pl) => pl.to_json_value(),
+            Payload::NoPayload => JsonValue::Null,
+        }
+    }
+}
+
+/// A single API call with parameters.
+#[derive(Debug, Clone)]
+pub struct Method {
+    method: String,
+    params: Vec<JsonValue>,
+}
+
+impl Method {
+    pub fn new(method: &str, params: Vec<JsonValue>) -> Method {
+        Method {
+            method: method.to_string(),
+            params,
+        }
+    }
+
+    pub fn from_json_value(json_obj: JsonValue) -> Option<Method> {
+        let classed = ClassifiedJson::declassify(json_obj)?;
+
+        if classed.class() != "osrfMethod" {
+            return None;
+        }
+
+        let hash = classed.take_json();
+
+        let method = match hash["method"].as_str() {
+            Some(m) => m.to_string(),
+            None => return None,
+        };
+
+        let mut params = Vec::new();
+        if let JsonValue::Array(vec) = &hash["params"] {
+            params = vec.to_vec();
+        }
+
+        Some(Method { method, params })
+    }
+
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn params(&self) -> &Vec<JsonValue> {
+        &self.params
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        ClassifiedJson::classify(
+            json::object! {
+                method: self.method(),
+                params: self.params().to_vec(),
+            },
+            "osrfMethod",
+        )
+    }
+}
+
+/// The result of a successfully executed (portion of a) request.
+#[derive(Debug, Clone)]
+pub struct Result {
+    status: MessageStatus,
+    status_label: String,
+    content: JsonValue,
+}
+
+impl Result {
+    pub fn new(status: MessageStatus, status_label: &str, content: JsonValue) -> Result {
+        Result {
+            status,
+            content,
+            status_label: status_label.to_string(),
+        }
+    }
+
+    pub fn from_json_value(json_obj: JsonValue) -> Option<Result> {
+        let classed = ClassifiedJson::declassify(json_obj)?;
+
+        if classed.class() != "osrfResult" {
+            return None;
+        }
+
+        let mut hash = classed.take_json();
+
+        let code = util::json_isize(&hash["statusCode"])?;
+        let stat: MessageStatus = code.into();
+
+        let status_label = match hash["status"].as_str() {
+            Some(s) => s.to_string(),
+            None => stat.to_string(),
+        };
+
+        Some(Result {
+            status: stat,
+            status_label,
+            content: hash["content"].take(),
+        })
+    }
+
+    pub fn status(&self) -> &MessageStatus {
+        &self.status
+    }
+
+    pub fn status_label(&self) -> &str {
+        &self.status_label
+    }
+
+    pub fn content(&self) -> &JsonValue {
+        &self.content
+    }
+
+    pub fn take_content(&mut self) -> JsonValue {
+        self.content.take()
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        ClassifiedJson::classify(
+            json::object! {
+                status: self.status_label(),
+                statusCode: *self.status() as isize,
+                content: self.content().clone(),
+            },
+            "osrfResult",
+        )
+    }
+}
+
+/// A communication layer status message.
+#[derive(Debug, Clone)]
+pub struct Status {
+    status: MessageStatus,
+    status_label: String,
+}
+
+impl Status {
+    pub fn new(status: MessageStatus, status_label: &str) -> Status {
+        Status {
+            status,
+            status_label: status_label.to_string(),
+        }
+    }
+
+    pub fn from_json_value(json_obj: JsonValue) -> Option<Status> {
+        let classed = ClassifiedJson::declassify(json_obj)?;
+
+        let hash = classed.take_json();
+
+        let code = util::json_isize(&hash["statusCode"])?;
+        let stat: MessageStatus = code.into();
+
+        let status_label = match hash["status"].as_str() {
+            Some(s) => s.to_string(),
+            None => stat.to_string(),
+        };
+
+        Some(Status {
+            status: stat,
+            status_label,
+        })
+    }
+
+    pub fn status(&self) -> &MessageStatus {
+        &self.status
+    }
+
+    pub fn status_label(&self) -> &str {
+        &self.status_label
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        ClassifiedJson::classify(
+            json::object! {
+                status: self.status_label(),
+                statusCode: *self.status() as isize,
+            },
+            "osrfConnectStatus",
+        )
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "stat={} class={}", self.status, self.status_label)
+    }
+}
+
+/// A single OpenSRF message, the "osrfMessage" of the protocol.
+#[derive(Debug, Clone)]
+pub struct Message {
+    mtype: MessageType,
+    thread_trace: usize,
+    api_level: u8,
+    ingress: String,
+    payload: Payload,
+
+    /// Optional deduplication key for Request messages.
+    idempotency_key: Option<String>,
+
+    /// True for Request messages that want no responses, not even
+    /// a Complete status.
+    no_reply: bool,
+
+    /// Caller locale, e.g. "en-US", for internationalized
+    /// services.
+    locale: Option<String>,
+}
+
+impl Message {
+    pub fn new(mtype: MessageType, thread_trace: usize, payload: Payload) -> Message {
+        Message {
+            mtype,
+            thread_trace,
+            payload,
+            api_level: DEFAULT_API_LEVEL,
+            ingress: DEFAULT_INGRESS.to_string(),
+            idempotency_key: None,
+            no_reply: false,
+            locale: None,
+        }
+    }
+
+    pub fn mtype(&self) -> &MessageType {
+        &self.mtype
+    }
+
+    pub fn thread_trace(&self) -> usize {
+        self.thread_trace
+    }
+
+    pub fn api_level(&self) -> u8 {
+        self.api_level
+    }
+
+    pub fn set_api_level(&mut self, level: u8) {
+        self.api_level = level;
+    }
+
+    pub fn ingress(&self) -> &str {
+        &self.ingress
+    }
+
+    pub fn set_ingress(&mut self, ingress: &str) {
+        self.ingress = ingress.to_string();
+    }
+
+    pub fn idempotency_key(&self) -> Option<&str> {
+        self.idempotency_key.as_deref()
+    }
+
+    pub fn no_reply(&self) -> bool {
+        self.no_reply
+    }
+
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    pub fn set_locale(&mut self, locale: &str) {
+        self.locale = Some(locale.to_string());
+    }
+
+    pub fn set_no_reply(&mut self, no_reply: bool) {
+        self.no_reply = no_reply;
+    }
+
+    pub fn set_idempotency_key(&mut self, key: &str) {
+        self.idempotency_key = Some(key.to_string());
+    }
+
+    pub fn payload(&self) -> &Payload {
+        &self.payload
+    }
+
+    pub fn take_payload(&mut self) -> Payload {
+        std::mem::replace(&mut self.payload, Payload::NoPayload)
+    }
+
+    /// Creates a Message from a JSON value, which must be a classed
+    /// "osrfMessage" hash.
+    pub fn from_json_value(json_obj: JsonValue) -> Option<Message> {
+        let classed = ClassifiedJson::declassify(json_obj)?;
+
+        if classed.class() != "osrfMessage" {
+            return None;
+        }
+
+        let mut hash = classed.take_json();
+
+        let mtype_str = hash["type"].as_str()?;
+        let mtype: MessageType = mtype_str.into();
+
+        let thread_trace = util::json_usize(&hash["threadTrace"])?;
+
+        let payload = Message::payload_from_json_value(mtype, hash["payload"].take())?;
+
+        let mut msg = Message::new(mtype, thread_trace, payload);
+
+        if let Some(api_level) = hash["api_level"].as_u8() {
+            msg.set_api_level(api_level);
+        }
+
+        if let Some(ingress) = hash["ingress"].as_str() {
+            msg.set_ingress(ingress);
+        }
+
+        if let Some(key) = hash["idempotency_key"].as_str() {
+            msg.set_idempotency_key(key);
+        }
+
+        if hash["no_reply"].as_bool().unwrap_or(false) {
+            msg.set_no_reply(true);
+        }
+
+        if let Some(locale) = hash["locale"].as_str() {
+            msg.set_locale(locale);
+        }
+
+        Some(msg)
+    }
+
+    fn payload_from_json_value(mtype: MessageType, payload_obj: JsonValue) -> Option<Payload> {
+        match mtype {
+            MessageType::Request => {
+                let method = Method::from_json_value(payload_obj)?;
+                Some(Payload::Method(method))
+            }
+
+            MessageType::Result => {
+                let result = Result::from_json_value(payload_obj)?;
+                Some(Payload::Result(result))
+            }
+
+            MessageType::Status => {
+                let stat = Status::from_json_value(payload_obj)?;
+                Some(Payload::Status(stat))
+            }
+
+            _ => Some(Payload::NoPayload),
+        }
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        let mtype: &str = self.mtype.into();
+
+        let mut obj = json::object! {
+            type: mtype,
+            threadTrace: self.thread_trace,
+            api_level: self.api_level,
+            ingress: self.ingress(),
+        };
+
+        if let Some(key) = self.idempotency_key() {
+            obj.insert("idempotency_key", key).ok();
+        }
+
+        if self.no_reply {
+            obj.insert("no_reply", true).ok();
+        }
+
+        if let Some(locale) = self.locale() {
+            obj.insert("locale", locale).ok();
+        }
+
+        match self.payload {
+            // Avoid adding the "payload" key for non-payload messages.
+            Payload::NoPayload => {}
+            _ => {
+                obj.insert("payload", self.payload.to_json_value()).ok();
+            }
+        }
+
+        ClassifiedJson::classify(obj, "osrfMessage")
+    }
+}
+
+/// The outermost envelope for bus-level delivery, which may contain
+/// multiple OpenSRF messages.
+#[derive(Debug, Clone)]
+pub struct TransportMessage {
+    to: String,
+    from: String,
+    thread: String,
+    body: Vec<Message>,
+    router_command: Option<String>,
+    router_class: Option<String>,
+    router_reply: Option<String>,
+
+    /// Optional free-form argument for router commands that need
+    /// more than a service class, e.g. the target address of a
+    /// "drain" command.
+    router_argument: Option<String>,
+
+    /// Optional affinity key the router hashes to pick a consistent
+    /// controller for stateless requests.
+    affinity_key: Option<String>,
+
+    /// True if the router should deliver a copy of this message to
+    /// every registered controller for the destination service.
+    broadcast: bool,
+
+    /// Correlation id for tracing one logical action across
+    /// services and hosts in the logs.
+    osrf_xid: Option<String>,
+}
+
+impl TransportMessage {
+    pub fn new(to: &str, from: &str, thread: &str) -> TransportMessage {
+        TransportMessage {
+            to: to.to_string(),
+            from: from.to_string(),
+            thread: thread.to_string(),
+            body: Vec::new(),
+            router_command: None,
+            router_class: None,
+            router_reply: None,
+            router_argument: None,
+            affinity_key: None,
+            broadcast: false,
+            osrf_xid: None,
+        }
+    }
+
+    pub fn with_body(to: &str, from: &str, thread: &str, msg: Message) -> TransportMessage {
+        let mut tm = TransportMessage::new(to, from, thread);
+        tm.body_mut().push(msg);
+        tm
+    }
+
+    pub fn to(&self) -> &str {
+        &self.to
+    }
+
+    pub fn set_to(&mut self, to: &str) {
+        self.to = to.to_string();
+    }
+
+    pub fn from(&self) -> &str {
+        &self.from
+    }
+
+    pub fn set_from(&mut self, from: &str) {
+        self.from = from.to_string();
+    }
+
+    pub fn thread(&self) -> &str {
+        &self.thread
+    }
+
+    pub fn body(&self) -> &Vec<Message> {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut Vec<Message> {
+        &mut self.body
+    }
+
+    pub fn router_command(&self) -> Option<&str> {
+        self.router_command.as_deref()
+    }
+
+    pub fn set_router_command(&mut self, command: &str) {
+        self.router_command = Some(command.to_string());
+    }
+
+    pub fn router_class(&self) -> Option<&str> {
+        self.router_class.as_deref()
+    }
+
+    pub fn set_router_class(&mut self, class: &str) {
+        self.router_class = Some(class.to_string());
+    }
+
+    pub fn router_reply(&self) -> Option<&str> {
+        self.router_reply.as_deref()
+    }
+
+    pub fn set_router_reply(&mut self, reply: &str) {
+        self.router_reply = Some(reply.to_string());
+    }
+
+    pub fn router_argument(&self) -> Option<&str> {
+        self.router_argument.as_deref()
+    }
+
+    pub fn set_router_argument(&mut self, argument: &str) {
+        self.router_argument = Some(argument.to_string());
+    }
+
+    pub fn affinity_key(&self) -> Option<&str> {
+        self.affinity_key.as_deref()
+    }
+
+    pub fn set_affinity_key(&mut self, key: &str) {
+        self.affinity_key = Some(key.to_string());
+    }
+
+    pub fn osrf_xid(&self) -> Option<&str> {
+        self.osrf_xid.as_deref()
+    }
+
+    pub fn set_osrf_xid(&mut self, xid: &str) {
+        self.osrf_xid = Some(xid.to_string());
+    }
+
+    pub fn broadcast(&self) -> bool {
+        self.broadcast
+    }
+
+    pub fn set_broadcast(&mut self, broadcast: bool) {
+        self.broadcast = broadcast;
+    }
+
+    pub fn from_json_value(mut json_obj: JsonValue) -> Option<TransportMessage> {
+        let to = json_obj["to"].as_str()?;
+        let from = json_obj["from"].as_str()?;
+        let thread = json_obj["thread"].as_str()?;
+
+        let mut tmsg = TransportMessage::new(to, from, thread);
+
+        if let JsonValue::Array(arr) = json_obj["body"].take() {
+            for body in arr {
+                if let Some(msg) = Message::from_json_value(body) {
+                    tmsg.body_mut().push(msg);
+                }
+            }
+        }
+
+        if let Some(rc) = json_obj["router_command"].as_str() {
+            tmsg.set_router_command(rc);
+        }
+
+        if let Some(rc) = json_obj["router_class"].as_str() {
+            tmsg.set_router_class(rc);
+        }
+
+        if let Some(rr) = json_obj["router_reply"].as_str() {
+            tmsg.set_router_reply(rr);
+        }
+
+        if let Some(ra) = json_obj["router_argument"].as_str() {
+            tmsg.set_router_argument(ra);
+        }
+
+        if let Some(key) = json_obj["affinity_key"].as_str() {
+            tmsg.set_affinity_key(key);
+        }
+
+        if json_obj["broadcast"].as_bool().unwrap_or(false) {
+            tmsg.set_broadcast(true);
+        }
+
+        if let Some(xid) = json_obj["osrf_xid"].as_str() {
+            tmsg.set_osrf_xid(xid);
+        }
+
+        Some(tmsg)
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        let body: Vec<JsonValue> = self.body().iter().map(|b| b.to_json_value()).collect();
+
+        let mut obj = json::object! {
+            to: self.to(),
+            from: self.from(),
+            thread: self.thread(),
+            body: body,
+        };
+
+        if let Some(rc) = self.router_command() {
+            obj.insert("router_command", rc).ok();
+        }
+
+        if let Some(rc) = self.router_class() {
+            obj.insert("router_class", rc).ok();
+        }
+
+        if let Some(rr) = self.router_reply() {
+            obj.insert("router_reply", rr).ok();
+        }
+
+        if let Some(ra) = self.router_argument() {
+            obj.insert("router_argument", ra).ok();
+        }
+
+        if let Some(key) = self.affinity_key() {
+            obj.insert("affinity_key", key).ok();
+        }
+
+        if self.broadcast() {
+            obj.insert("broadcast", true).ok();
+        }
+
+        if let Some(xid) = self.osrf_xid() {
+            obj.insert("osrf_xid", xid).ok();
+        }
+
+        obj
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_round_trip() {
+        let method = Method::new("opensrf.system.echo", vec![json::from("hello")]);
+        let msg = Message::new(MessageType::Request, 1, Payload::Method(method));
+        let tmsg = TransportMessage::with_body("to-addr", "from-addr", "thread-1", msg);
+
+        let tmsg2 = TransportMessage::from_json_value(tmsg.to_json_value()).unwrap();
+
+        assert_eq!(tmsg2.to(), "to-addr");
+        assert_eq!(tmsg2.thread(), "thread-1");
+        assert_eq!(tmsg2.body().len(), 1);
+
+        let msg2 = &tmsg2.body()[0];
+        assert_eq!(msg2.mtype(), &MessageType::Request);
+        assert_eq!(msg2.thread_trace(), 1);
+
+        if let Payload::Method(m) = msg2.payload() {
+            assert_eq!(m.method(), "opensrf.system.echo");
+            assert_eq!(m.params()[0], "hello");
+        } else {
+            panic!("wrong payload type");
+        }
+    }
+
+    #[test]
+    fn test_status_codes() {
+        let stat: MessageStatus = 404.into();
+        assert_eq!(stat, MessageStatus::MethodNotFound);
+        assert!(stat.is_4xx());
+        assert!(!stat.is_5xx());
+    }
+}
@@ -0,0 +1,96 @@
+use super::app::ApplicationWorker;
+use super::message;
+use super::session::ServerSession;
+use std::time::Duration;
+
+/// Function signature for published method handlers.
+pub type MethodHandler = fn(
+    &mut Box<dyn ApplicationWorker>,
+    &mut ServerSession,
+    &message::Method,
+) -> Result<(), String>;
+
+/// How many parameters a method will accept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamCount {
+    Any,
+    Zero,
+    Exactly(u8),
+    AtLeast(u8),
+    Range(u8, u8), // Inclusive
+}
+
+impl ParamCount {
+    /// Returns true if the number of params provided matches the
+    /// constraint.
+    pub fn matches(pc: &ParamCount, count: u8) -> bool {
+        match *pc {
+            ParamCount::Any => true,
+            ParamCount::Zero => count == 0,
+            ParamCount::Exactly(c) => count == c,
+            ParamCount::AtLeast(c) => count >= c,
+            ParamCount::Range(s, e) => s <= count && e >= count,
+        }
+    }
+}
+
+impl std::fmt::Display for ParamCount {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ParamCount::Any => write!(f, "Any"),
+            ParamCount::Zero => write!(f, "Zero"),
+            ParamCount::Exactly(c) => write!(f, "=={c}"),
+            ParamCount::AtLeast(c) => write!(f, ">={c}"),
+            ParamCount::Range(s, e) => write!(f, "{s}..{e}"),
+        }
+    }
+}
+
+/// A published API method.
+#[derive(Clone)]
+pub struct Method {
+    pub name: &'static str,
+    pub param_count: ParamCount,
+    pub handler: MethodHandler,
+
+    /// Optional cap on handler runtime, enforced by the worker's
+    /// watchdog; None defers to the service-level max-runtime
+    /// setting.
+    pub max_runtime: Option<Duration>,
+}
+
+impl Method {
+    pub fn name(&self) -> &str {
+        self.name
+    }
+
+    pub fn param_count(&self) -> &ParamCount {
+        &self.param_count
+    }
+
+    pub fn handler(&self) -> MethodHandler {
+        self.handler
+    }
+
+    pub fn max_runtime(&self) -> Option<Duration> {
+        self.max_runtime
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_param_count_matches() {
+        assert!(ParamCount::matches(&ParamCount::Any, 0));
+        assert!(ParamCount::matches(&ParamCount::Zero, 0));
+        assert!(!ParamCount::matches(&ParamCount::Zero, 1));
+        assert!(ParamCount::matches(&ParamCount::Exactly(2), 2));
+        assert!(!ParamCount::matches(&ParamCount::Exactly(2), 3));
+        assert!(ParamCount::matches(&ParamCount::AtLeast(1), 4));
+        assert!(!ParamCount::matches(&ParamCount::AtLeast(2), 1));
+        assert!(ParamCount::matches(&ParamCount::Range(1, 3), 2));
+        assert!(!ParamCount::matches(&ParamCount::Range(1, 3), 4));
+    }
+}
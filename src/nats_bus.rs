@@ -0,0 +1,262 @@
+//! Experimental NATS JetStream transport.
+//!
+//! Maps the Redis stream addressing scheme onto JetStream: the
+//! stream key "opensrf:service:foo" becomes the subject
+//! "opensrf.service.foo", backed by a work-queue JetStream stream
+//! and consumed through a durable pull consumer, mirroring the
+//! one-group-per-stream convention the Redis bus uses.
+//!
+//! Stream names cannot contain '.', so the JetStream stream for a
+//! subject is the subject with dots replaced by dashes.
+
+// The sync nats crate is deprecated upstream in favor of
+// async-nats, which this single-threaded crate cannot use yet.
+#![allow(deprecated)]
+
+use super::addr::ClientAddress;
+use super::conf;
+use super::message::TransportMessage;
+use log::{debug, trace};
+use nats::jetstream::{JetStream, PullSubscription, RetentionPolicy, StreamConfig};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+/// Poll duration used to emulate a non-blocking read; JetStream
+/// pull consumers have no zero-timeout fetch.
+const NONBLOCK_POLL: Duration = Duration::from_millis(10);
+
+/// Manages the JetStream connection for a single bus participant.
+///
+/// Mirrors the API surface of bus::Bus that the client and worker
+/// layers rely on.
+pub struct NatsBus {
+    jetstream: JetStream,
+
+    /// Our unique bus address.
+    address: ClientAddress,
+
+    /// Domain, i.e. bus node name, we're connected to.
+    domain: String,
+
+    /// Durable pull subscriptions by stream key.
+    subscriptions: HashMap<String, PullSubscription>,
+}
+
+impl NatsBus {
+    pub fn new(config: &conf::BusConnection) -> Result<Self, String> {
+        let node = config.node();
+        let creds = config.credentials();
+
+        let host = node
+            .addresses()
+            .first()
+            .cloned()
+            .unwrap_or_else(|| node.name().to_string());
+
+        let url = format!("nats://{host}:{}", node.port());
+
+        debug!("Connecting to NATS at {url}");
+
+        let connection = nats::Options::with_user_pass(creds.username(), creds.password())
+            .connect(&url)
+            .map_err(|e| format!("Error connecting to NATS at {url}: {e}"))?;
+
+        let jetstream = nats::jetstream::new(connection);
+
+        let mut bus = NatsBus {
+            jetstream,
+            address: ClientAddress::new(node.name()),
+            domain: node.name().to_string(),
+            subscriptions: HashMap::new(),
+        };
+
+        bus.setup_stream(None)?;
+
+        Ok(bus)
+    }
+
+    pub fn address(&self) -> &ClientAddress {
+        &self.address
+    }
+
+    pub fn set_address(&mut self, address: &ClientAddress) {
+        self.address = address.clone();
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// The NATS subject for a bus stream key.
+    fn subject(stream: &str) -> String {
+        stream.replace(':', ".")
+    }
+
+    /// The JetStream stream name for a bus stream key.
+    fn stream_name(stream: &str) -> String {
+        stream.replace(':', "-")
+    }
+
+    /// Creates the JetStream stream and durable pull consumer for
+    /// the provided stream key, defaulting to our bus address.
+    ///
+    /// Work-queue retention gives the same consume-once semantics
+    /// as the Redis consumer groups.
+    pub fn setup_stream(&mut self, name: Option<&str>) -> Result<(), String> {
+        let sname = name.unwrap_or(self.address.full()).to_string();
+
+        if self.subscriptions.contains_key(&sname) {
+            return Ok(());
+        }
+
+        debug!("NatsBus setting up stream={sname}");
+
+        let stream_config = StreamConfig {
+            name: NatsBus::stream_name(&sname),
+            subjects: vec![NatsBus::subject(&sname)],
+            retention: RetentionPolicy::WorkQueue,
+            ..Default::default()
+        };
+
+        if let Err(e) = self.jetstream.add_stream(stream_config) {
+            return Err(format!("Error creating JetStream stream={sname}: {e}"));
+        }
+
+        let sub = self
+            .jetstream
+            .pull_subscribe(&NatsBus::subject(&sname))
+            .map_err(|e| format!("Error subscribing to stream={sname}: {e}"))?;
+
+        self.subscriptions.insert(sname, sub);
+
+        Ok(())
+    }
+
+    /// Returns at most one JSON value pulled from the stream.
+    ///
+    /// Same timeout semantics as bus::Bus: zero returns immediately
+    /// if no messages are available, Duration::MAX blocks
+    /// indefinitely.
+    pub fn recv_json_value(
+        &mut self,
+        timeout: Duration,
+        stream: Option<&str>,
+    ) -> Result<Option<json::JsonValue>, String> {
+        let sname = stream.unwrap_or(self.address.full()).to_string();
+
+        trace!("NatsBus recv_json_value() timeout={timeout:?} stream={sname}");
+
+        self.setup_stream(Some(&sname))?;
+
+        // Unwrap is safe; setup_stream() just created the entry.
+        let sub = self.subscriptions.get(&sname).unwrap();
+
+        let msg = if timeout == Duration::MAX {
+            match sub.next() {
+                Some(m) => m,
+                None => return Ok(None),
+            }
+        } else {
+            let duration = if timeout.is_zero() {
+                NONBLOCK_POLL
+            } else {
+                timeout
+            };
+
+            match sub.next_timeout(duration) {
+                Ok(m) => m,
+                Err(e) if e.kind() == ErrorKind::TimedOut => return Ok(None),
+                Err(e) => return Err(format!("NatsBus recv error: {e}")),
+            }
+        };
+
+        if let Err(e) = msg.ack() {
+            return Err(format!("NatsBus cannot ack message: {e}"));
+        }
+
+        let json_string = match String::from_utf8(msg.data.clone()) {
+            Ok(s) => s,
+            Err(e) => return Err(format!("NatsBus received non-utf8 data: {e}")),
+        };
+
+        trace!("NatsBus read json: {json_string}");
+
+        match json::parse(&json_string) {
+            Ok(json_val) => Ok(Some(json_val)),
+            Err(e) => Err(format!(
+                "NatsBus received unparseable JSON: {e} : {json_string}"
+            )),
+        }
+    }
+
+    /// Returns at most one TransportMessage pulled from the stream.
+    pub fn recv(
+        &mut self,
+        timeout: Duration,
+        stream: Option<&str>,
+    ) -> Result<Option<TransportMessage>, String> {
+        let json_op = self.recv_json_value(timeout, stream)?;
+
+        match json_op {
+            Some(json_val) => match TransportMessage::from_json_value(json_val) {
+                Some(msg) => Ok(Some(msg)),
+                None => Err("NatsBus received malformed TransportMessage".to_string()),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// Sends a TransportMessage to its "to" address.
+    pub fn send(&mut self, msg: &TransportMessage) -> Result<(), String> {
+        self.send_to(msg, msg.to())
+    }
+
+    /// Sends a TransportMessage to the provided stream, regardless
+    /// of its "to" address.
+    pub fn send_to(&mut self, msg: &TransportMessage, recipient: &str) -> Result<(), String> {
+        let json_str = msg.to_json_value().dump();
+
+        trace!("NatsBus sending to={recipient}: {json_str}");
+
+        if let Err(e) = self
+            .jetstream
+            .publish(&NatsBus::subject(recipient), &json_str)
+        {
+            return Err(format!("Error in send() {e}"));
+        }
+
+        Ok(())
+    }
+
+    /// Removes all pending entries from our stream.
+    pub fn clear_stream(&mut self) -> Result<(), String> {
+        let sname = NatsBus::stream_name(self.address.full());
+
+        if let Err(e) = self.jetstream.purge_stream(&sname) {
+            return Err(format!("Error in clear_stream(): {e}"));
+        }
+
+        Ok(())
+    }
+
+    /// Removes our stream and its consumer entirely.
+    pub fn delete_stream(&mut self) -> Result<(), String> {
+        let sname = NatsBus::stream_name(self.address.full());
+
+        debug!("NatsBus deleting stream={sname}");
+
+        self.subscriptions.remove(self.address.full());
+
+        if let Err(e) = self.jetstream.delete_stream(&sname) {
+            return Err(format!("Error in delete_stream(): {e}"));
+        }
+
+        Ok(())
+    }
+
+    /// Removes our stream, dropping any unprocessed messages.
+    pub fn disconnect(&mut self) -> Result<(), String> {
+        self.delete_stream()
+    }
+}
@@ -0,0 +1,117 @@
+use json::JsonValue;
+
+/// Generic container for API method parameters.
+///
+/// Adds a layer of convenience over Vec<JsonValue> with From impls
+/// for common parameter shapes.
+#[derive(Debug, Clone, Default)]
+pub struct ApiParams {
+    params: Vec<JsonValue>,
+}
+
+impl ApiParams {
+    pub fn new() -> Self {
+        ApiParams { params: Vec::new() }
+    }
+
+    pub fn params(&self) -> &Vec<JsonValue> {
+        &self.params
+    }
+
+    /// Consumes the ApiParams, returning the contained values.
+    pub fn take_params(self) -> Vec<JsonValue> {
+        self.params
+    }
+
+    /// Adds a parameter to the end of the list.
+    pub fn add(&mut self, value: JsonValue) {
+        self.params.push(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+}
+
+impl<T> From<Vec<T>> for ApiParams
+where
+    T: Into<JsonValue>,
+{
+    fn from(values: Vec<T>) -> ApiParams {
+        ApiParams {
+            params: values.into_iter().map(|v| v.into()).collect(),
+        }
+    }
+}
+
+impl From<JsonValue> for ApiParams {
+    fn from(value: JsonValue) -> ApiParams {
+        ApiParams {
+            params: vec![value],
+        }
+    }
+}
+
+impl From<&JsonValue> for ApiParams {
+    fn from(value: &JsonValue) -> ApiParams {
+        ApiParams {
+            params: vec![value.clone()],
+        }
+    }
+}
+
+impl From<&str> for ApiParams {
+    fn from(value: &str) -> ApiParams {
+        ApiParams {
+            params: vec![json::from(value)],
+        }
+    }
+}
+
+impl From<String> for ApiParams {
+    fn from(value: String) -> ApiParams {
+        ApiParams {
+            params: vec![json::from(value)],
+        }
+    }
+}
+
+impl From<isize> for ApiParams {
+    fn from(value: isize) -> ApiParams {
+        ApiParams {
+            params: vec![json::from(value)],
+        }
+    }
+}
+
+impl From<usize> for ApiParams {
+    fn from(value: usize) -> ApiParams {
+        ApiParams {
+            params: vec![json::from(value)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_impls() {
+        let p: ApiParams = "hello".into();
+        assert_eq!(p.len(), 1);
+        assert_eq!(p.params()[0], "hello");
+
+        let p: ApiParams = vec![json::from(1), json::from(2)].into();
+        assert_eq!(p.len(), 2);
+
+        let mut p = ApiParams::new();
+        assert!(p.is_empty());
+        p.add(json::from("x"));
+        assert_eq!(p.take_params().len(), 1);
+    }
+}
@@ -0,0 +1,69 @@
+use super::client::Client;
+use super::session::DEFAULT_REQUEST_TIMEOUT;
+use json::JsonValue;
+
+/// Client for pulling host-level settings from the opensrf.settings
+/// service.
+pub struct SettingsClient;
+
+impl SettingsClient {
+    /// Fetches the full settings blob for the provided hostname.
+    pub fn get_host_settings(client: &Client, hostname: &str) -> Result<HostSettings, String> {
+        let ses = client.session("opensrf.settings");
+
+        let mut req = ses.request("opensrf.settings.host_config.get", vec![hostname])?;
+
+        match req.recv(DEFAULT_REQUEST_TIMEOUT)? {
+            Some(settings) => Ok(HostSettings { settings }),
+            None => Err("No response from opensrf.settings".to_string()),
+        }
+    }
+}
+
+/// Read-only wrapper around the host settings blob.
+pub struct HostSettings {
+    settings: JsonValue,
+}
+
+impl HostSettings {
+    pub fn settings(&self) -> &JsonValue {
+        &self.settings
+    }
+
+    /// Returns the value at the provided slash-separated path, e.g.
+    /// "apps/opensrf.settings/unix_config/max_children".
+    ///
+    /// Returns JsonValue::Null if the path does not exist.
+    pub fn value(&self, slashpath: &str) -> &JsonValue {
+        let mut node = self.settings();
+
+        for part in slashpath.split('/') {
+            node = &node[part];
+        }
+
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_path() {
+        let hs = HostSettings {
+            settings: json::object! {
+                apps: {
+                    "opensrf.settings": {
+                        unix_config: { max_children: 15 }
+                    }
+                }
+            },
+        };
+
+        let v = hs.value("apps/opensrf.settings/unix_config/max_children");
+        assert_eq!(v.as_usize().unwrap(), 15);
+
+        assert!(hs.value("no/such/path").is_null());
+    }
+}
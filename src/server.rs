@@ -0,0 +1,836 @@
+use super::addr::ServiceAddress;
+use super::app;
+use super::client::Client;
+use super::conf;
+use super::message;
+use super::message::TransportMessage;
+use super::method;
+use super::sclient::SettingsClient;
+use super::session::ServerSession;
+use super::util;
+use super::worker::{Worker, WorkerState, WorkerStateEvent};
+use log::{debug, error, info, trace, warn};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// How often the listen loop wakes to check for signals and failed
+/// threads when no worker events are arriving.
+const LISTEN_WAKE_TIME: u64 = 1;
+
+/// Idle workers beyond the max-idle setting must sit idle at least
+/// this long before being reaped, so short lulls between bursts
+/// don't churn threads.
+const IDLE_REAP_AGE: Duration = Duration::from_secs(60);
+
+/// Counters shared between the Server and its Workers so any worker
+/// can service an opensrf.system.stats call.
+pub struct ServerStats {
+    start_time: u64,
+    config_fingerprint: String,
+    idle_workers: AtomicUsize,
+    active_workers: AtomicUsize,
+    method_calls: Mutex<HashMap<String, usize>>,
+}
+
+impl ServerStats {
+    fn new(config: &conf::Config) -> ServerStats {
+        // A stable digest of the active configuration so monitoring
+        // can tell when processes disagree about their config.
+        let mut hasher = DefaultHasher::new();
+        format!("{config:?}").hash(&mut hasher);
+
+        let start_time = match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs(),
+            Err(_) => 0,
+        };
+
+        ServerStats {
+            start_time,
+            config_fingerprint: format!("{:x}", hasher.finish()),
+            idle_workers: AtomicUsize::new(0),
+            active_workers: AtomicUsize::new(0),
+            method_calls: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn set_worker_counts(&self, idle: usize, active: usize) {
+        self.idle_workers.store(idle, Ordering::Relaxed);
+        self.active_workers.store(active, Ordering::Relaxed);
+    }
+
+    /// Bumps the call counter for a method.
+    pub fn record_call(&self, method: &str) {
+        if let Ok(mut calls) = self.method_calls.lock() {
+            *calls.entry(method.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    /// Seconds since this service started.
+    fn uptime(&self) -> u64 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs().saturating_sub(self.start_time),
+            Err(_) => 0,
+        }
+    }
+
+    /// Builds the opensrf.system.stats response body.
+    pub fn to_json_value(&self, backlog: i32) -> json::JsonValue {
+        let uptime = self.uptime();
+
+        let mut methods = json::JsonValue::new_object();
+        if let Ok(calls) = self.method_calls.lock() {
+            for (name, count) in calls.iter() {
+                methods.insert(name, *count).ok();
+            }
+        }
+
+        json::object! {
+            uptime: uptime,
+            config_fingerprint: self.config_fingerprint.as_str(),
+            backlog: backlog,
+            workers: json::object! {
+                idle: self.idle_workers.load(Ordering::Relaxed),
+                active: self.active_workers.load(Ordering::Relaxed),
+            },
+            method_calls: methods,
+        }
+    }
+
+    /// Builds the opensrf.system.status response body: a compact
+    /// self-report -- worker counts by state, requests served,
+    /// uptime, backlog depth, and config digest -- so monitoring
+    /// can query the service directly over the bus.
+    pub fn status_json(&self, service: &str, backlog: i32) -> json::JsonValue {
+        let requests = match self.method_calls.lock() {
+            Ok(calls) => calls.values().sum::<usize>(),
+            Err(_) => 0,
+        };
+
+        let idle = self.idle_workers.load(Ordering::Relaxed);
+        let active = self.active_workers.load(Ordering::Relaxed);
+
+        json::object! {
+            service: service,
+            uptime: self.uptime(),
+            requests: requests,
+            backlog: backlog,
+            config_fingerprint: self.config_fingerprint.as_str(),
+            workers: json::object! {
+                idle: idle,
+                active: active,
+                total: idle + active,
+            },
+        }
+    }
+}
+
+/// Placeholder handler for system methods, which the Worker services
+/// directly; see Worker::handle_system_method().
+fn system_method_handler(
+    _worker: &mut Box<dyn app::ApplicationWorker>,
+    _session: &mut ServerSession,
+    method: &message::Method,
+) -> Result<(), String> {
+    Err(format!("System method not intercepted: {}", method.method()))
+}
+
+/// A spawned worker thread and what we know about it.
+struct WorkerThread {
+    state: WorkerState,
+    join_handle: thread::JoinHandle<()>,
+
+    /// When the worker last went idle.
+    idle_since: Instant,
+
+    /// True once we've asked this worker to exit; keeps the idle
+    /// reaper from stopping the same worker twice.
+    reaped: bool,
+}
+
+/// Manages the worker pool for one service.
+pub struct Server {
+    service: String,
+
+    application: Box<dyn app::Application>,
+
+    client: Client,
+
+    config: Arc<conf::Config>,
+
+    methods: Arc<HashMap<String, method::Method>>,
+
+    workers: HashMap<u64, WorkerThread>,
+
+    worker_id_gen: u64,
+
+    min_workers: usize,
+
+    max_workers: usize,
+
+    min_idle: usize,
+
+    max_idle: usize,
+
+    /// How long shutdown waits for busy workers to finish their
+    /// current requests.
+    shutdown_grace: Duration,
+
+    /// Stream depth beyond which a fully saturated server bounces
+    /// queued requests with a BUSY status; zero disables.
+    busy_threshold: usize,
+
+    /// True once our bus has joined the service stream's consumer
+    /// group for load shedding.
+    busy_stream_ready: bool,
+
+    /// Per-origin token buckets shared by every worker, when the
+    /// service configures rate limiting.
+    rate_limiter: Option<Arc<Mutex<util::RateLimiter>>>,
+
+    /// Set by our signal handlers.
+    stopping: Arc<AtomicBool>,
+
+    /// Set when a drain has been requested; workers finish their
+    /// current conversations and exit instead of picking up new work.
+    draining: Arc<AtomicBool>,
+
+    stats: Arc<ServerStats>,
+
+    to_parent_tx: mpsc::Sender<WorkerStateEvent>,
+    to_parent_rx: mpsc::Receiver<WorkerStateEvent>,
+}
+
+impl Server {
+    /// Launches a service and blocks until it exits.
+    pub fn start(
+        application: Box<dyn app::Application>,
+        config: Arc<conf::Config>,
+    ) -> Result<(), String> {
+        let service = application.name().to_string();
+
+        let client = Client::connect(config.clone())?;
+
+        let config = Server::apply_host_settings(&client, &service, config)?;
+
+        let methods = application.register_methods(client.clone(), config.clone())?;
+
+        let mut method_map = HashMap::new();
+        for method in methods {
+            method_map.insert(method.name().to_string(), method);
+        }
+
+        Server::add_system_methods(&mut method_map);
+
+        let options = config.service_options(&service).cloned().unwrap_or_default();
+
+        let stats = Arc::new(ServerStats::new(&config));
+
+        let rate_limiter = match (options.rate_limit_burst(), options.rate_limit_per_sec()) {
+            (burst, rate) if burst > 0 && rate > 0 => {
+                Some(Arc::new(Mutex::new(util::RateLimiter::new(burst, rate))))
+            }
+            _ => None,
+        };
+
+        let (tx, rx) = mpsc::channel();
+
+        let mut server = Server {
+            service,
+            application,
+            client,
+            config,
+            methods: Arc::new(method_map),
+            workers: HashMap::new(),
+            worker_id_gen: 0,
+            min_workers: options.min_workers(),
+            max_workers: options.max_workers(),
+            min_idle: options.min_idle(),
+            max_idle: options.max_idle(),
+            shutdown_grace: Duration::from_secs(options.shutdown_grace()),
+            busy_threshold: options.busy_threshold(),
+            busy_stream_ready: false,
+            rate_limiter,
+            stopping: Arc::new(AtomicBool::new(false)),
+            draining: Arc::new(AtomicBool::new(false)),
+            stats,
+            to_parent_tx: tx,
+            to_parent_rx: rx,
+        };
+
+        server.setup_signal_handlers()?;
+
+        let client = server.client.clone();
+        let config = server.config.clone();
+        server.application.init(client, config)?;
+
+        server.register_routers()?;
+        server.spawn_min_workers();
+        server.update_stats();
+        server.listen();
+        server.unregister_routers()?;
+
+        if let Err(e) = server.application.shutdown() {
+            error!("server: application shutdown failed: {e}");
+        }
+
+        // With the routers notified, drop whatever is left on our
+        // stream and remove it.
+        let drain_op = server
+            .client
+            .singleton()
+            .borrow_mut()
+            .bus_mut()
+            .drain(None);
+
+        if let Err(e) = drain_op {
+            error!("server: drain error: {e}");
+        }
+
+        Ok(())
+    }
+
+    /// Fetches our service stanza from opensrf.settings and merges
+    /// it over the locally configured values, when the service opts
+    /// in via use-settings-server.
+    ///
+    /// A missing or unreachable settings service logs a warning and
+    /// leaves the local configuration in place, so a host can still
+    /// boot its services -- including opensrf.settings itself --
+    /// from local files alone.
+    fn apply_host_settings(
+        client: &Client,
+        service: &str,
+        config: Arc<conf::Config>,
+    ) -> Result<Arc<conf::Config>, String> {
+        let wanted = match config.service_options(service) {
+            Some(ops) => ops.use_settings_server(),
+            None => false,
+        };
+
+        if !wanted {
+            return Ok(config);
+        }
+
+        let host_settings =
+            match SettingsClient::get_host_settings(client, config.hostname()) {
+                Ok(hs) => hs,
+                Err(e) => {
+                    warn!(
+                        "server: cannot fetch host settings for {service}; \
+                        using local configuration: {e}"
+                    );
+                    return Ok(config);
+                }
+            };
+
+        let mut conf = (*config).clone();
+
+        let stanza = host_settings.value(&format!("apps/{service}"));
+
+        if stanza.is_null() {
+            info!("server: no {service} stanza in host settings");
+        } else if let Some(ops) = conf.service_options_mut(service) {
+            ops.apply_host_settings(stanza);
+            info!("server: applied {service} settings from opensrf.settings");
+        }
+
+        conf.set_host_settings(host_settings.settings().clone());
+
+        Ok(conf.into_shared())
+    }
+
+    /// Adds the standard system methods every service exposes, e.g.
+    /// for monitoring.
+    fn add_system_methods(methods: &mut HashMap<String, method::Method>) {
+        let echo = method::Method {
+            name: "opensrf.system.echo",
+            param_count: method::ParamCount::Any,
+            handler: system_method_handler,
+            max_runtime: None,
+        };
+
+        methods.insert(echo.name().to_string(), echo);
+
+        let stats = method::Method {
+            name: "opensrf.system.stats",
+            param_count: method::ParamCount::Zero,
+            handler: system_method_handler,
+            max_runtime: None,
+        };
+
+        methods.insert(stats.name().to_string(), stats);
+
+        let status = method::Method {
+            name: "opensrf.system.status",
+            param_count: method::ParamCount::Zero,
+            handler: system_method_handler,
+            max_runtime: None,
+        };
+
+        methods.insert(status.name().to_string(), status);
+    }
+
+    fn setup_signal_handlers(&self) -> Result<(), String> {
+        for sig in [signal_hook::consts::SIGTERM, signal_hook::consts::SIGINT] {
+            if let Err(e) = signal_hook::flag::register(sig, self.stopping.clone()) {
+                return Err(format!("Cannot register signal handler: {e}"));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tells the router on every configured node we're open for
+    /// business.
+    fn register_routers(&self) -> Result<(), String> {
+        for node in self.config.nodes() {
+            info!("Registering service {} on node {}", self.service, node.name());
+
+            self.client.send_router_command(
+                node.name(),
+                "register",
+                Some(&self.service),
+                false,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn unregister_routers(&self) -> Result<(), String> {
+        for node in self.config.nodes() {
+            info!(
+                "Unregistering service {} on node {}",
+                self.service,
+                node.name()
+            );
+
+            self.client.send_router_command(
+                node.name(),
+                "unregister",
+                Some(&self.service),
+                false,
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn next_worker_id(&mut self) -> u64 {
+        self.worker_id_gen += 1;
+        self.worker_id_gen
+    }
+
+    fn spawn_min_workers(&mut self) {
+        if self.draining.load(Ordering::Relaxed) {
+            return;
+        }
+
+        while self.workers.len() < self.min_workers {
+            self.spawn_one_worker();
+        }
+    }
+
+    fn spawn_one_worker(&mut self) {
+        let worker_id = self.next_worker_id();
+
+        debug!("server: spawning worker {worker_id}");
+
+        let service = self.service.to_string();
+        let config = self.config.clone();
+        let methods = self.methods.clone();
+        let stopping = self.stopping.clone();
+        let to_parent_tx = self.to_parent_tx.clone();
+        let factory = self.application.worker_factory();
+        let stats = self.stats.clone();
+        let draining = self.draining.clone();
+        let rate_limiter = self.rate_limiter.clone();
+
+        // Name the thread so individual workers are identifiable in
+        // ps/top output.
+        let builder =
+            thread::Builder::new().name(format!("{}-{}", &self.service, worker_id));
+
+        let spawn_op = builder.spawn(move || {
+            let mut worker = match Worker::new(
+                service,
+                worker_id,
+                config,
+                methods,
+                stopping,
+                draining,
+                stats,
+                rate_limiter,
+                to_parent_tx,
+            ) {
+                Ok(w) => w,
+                Err(e) => {
+                    error!("Cannot create worker {worker_id}: {e}");
+                    return;
+                }
+            };
+
+            worker.listen(factory);
+        });
+
+        let join_handle = match spawn_op {
+            Ok(handle) => handle,
+            Err(e) => {
+                error!("server: cannot spawn worker thread: {e}");
+                return;
+            }
+        };
+
+        self.workers.insert(
+            worker_id,
+            WorkerThread {
+                state: WorkerState::Idle,
+                join_handle,
+                idle_since: Instant::now(),
+                reaped: false,
+            },
+        );
+    }
+
+    fn idle_worker_count(&self) -> usize {
+        self.workers
+            .values()
+            .filter(|v| v.state == WorkerState::Idle)
+            .count()
+    }
+
+    /// Refreshes the shared worker-state counters.
+    fn update_stats(&self) {
+        let idle = self.idle_worker_count();
+        self.stats.set_worker_counts(idle, self.workers.len() - idle);
+    }
+
+    /// Main listen loop: tracks worker state events, maintains the
+    /// worker pool, and exits when our stopping flag is set.
+    fn listen(&mut self) {
+        loop {
+            if self.stopping.load(Ordering::Relaxed) {
+                info!("server: shutting down on signal");
+                self.shutdown_drain();
+                return;
+            }
+
+            if self.draining.load(Ordering::Relaxed) && self.workers.is_empty() {
+                info!("server: drain complete");
+                return;
+            }
+
+            if let Err(e) = self.check_bus_commands() {
+                error!("server: error checking bus: {e}");
+            }
+
+            let event = match self
+                .to_parent_rx
+                .recv_timeout(Duration::from_secs(LISTEN_WAKE_TIME))
+            {
+                Ok(evt) => evt,
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    self.check_failed_threads();
+                    continue;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    // Should not be possible since we hold a sender.
+                    error!("server: worker event channel disconnected");
+                    return;
+                }
+            };
+
+            self.handle_worker_event(event);
+            self.check_failed_threads();
+            self.update_stats();
+
+            // Top up the pool when idle capacity runs low.
+            if self.idle_worker_count() < std::cmp::max(self.min_idle, 1) {
+                if self.workers.len() < self.max_workers {
+                    self.spawn_one_worker();
+                } else if self.idle_worker_count() == 0 {
+                    warn!("server: reached max workers!");
+                    self.shed_queued_requests();
+                }
+            }
+
+            self.reap_idle_workers();
+        }
+    }
+
+    /// With every worker busy, bounces queued requests beyond the
+    /// busy threshold with a ServiceUnavailable status, oldest
+    /// first, so callers can fail fast or retry elsewhere instead
+    /// of waiting out a timeout in the stream.
+    fn shed_queued_requests(&mut self) {
+        if self.busy_threshold == 0 {
+            return;
+        }
+
+        let service_addr = ServiceAddress::new(&self.service).full().to_string();
+
+        let singleton = self.client.singleton();
+        let mut singleton = singleton.borrow_mut();
+        let bus = singleton.bus_mut();
+
+        if !self.busy_stream_ready {
+            if let Err(e) = bus.setup_stream(Some(&service_addr)) {
+                return error!("server: cannot setup service stream: {e}");
+            }
+
+            self.busy_stream_ready = true;
+        }
+
+        let backlog = match bus.xlen(&service_addr) {
+            Ok(n) => n as usize,
+            Err(e) => return error!("server: cannot read stream depth: {e}"),
+        };
+
+        if backlog <= self.busy_threshold {
+            return;
+        }
+
+        warn!(
+            "server: shedding {} queued requests over threshold {}",
+            backlog - self.busy_threshold,
+            self.busy_threshold
+        );
+
+        for _ in 0..(backlog - self.busy_threshold) {
+            let tmsg = match bus.recv(Duration::ZERO, Some(&service_addr)) {
+                Ok(Some(t)) => t,
+                Ok(None) => return,
+                Err(e) => return error!("server: error shedding requests: {e}"),
+            };
+
+            for msg in tmsg.body() {
+                if msg.mtype() != &message::MessageType::Request {
+                    continue;
+                }
+
+                let status = message::Payload::Status(message::Status::new(
+                    message::MessageStatus::ServiceUnavailable,
+                    "Service is busy",
+                ));
+
+                let reply = TransportMessage::with_body(
+                    tmsg.from(),
+                    bus.address().full(),
+                    tmsg.thread(),
+                    message::Message::new(
+                        message::MessageType::Status,
+                        msg.thread_trace(),
+                        status,
+                    ),
+                );
+
+                if let Err(e) = bus.send_to(&reply, tmsg.from()) {
+                    error!("server: cannot send BUSY reply: {e}");
+                }
+            }
+        }
+    }
+
+    /// Asks long-idle workers to exit while more than max_idle
+    /// would remain, so memory use shrinks back after a burst.
+    ///
+    /// Stopped workers exit via their control stream and report
+    /// Done like any other; the pool never drops below
+    /// min_workers.
+    fn reap_idle_workers(&mut self) {
+        if self.max_idle == 0 {
+            // Reaping disabled.
+            return;
+        }
+
+        loop {
+            let idle: Vec<(u64, Instant)> = self
+                .workers
+                .iter()
+                .filter(|(_, v)| v.state == WorkerState::Idle && !v.reaped)
+                .map(|(k, v)| (*k, v.idle_since))
+                .collect();
+
+            if idle.len() <= self.max_idle || self.workers.len() <= self.min_workers {
+                return;
+            }
+
+            // Reap the longest-idle worker, provided it has idled
+            // long enough to look like excess capacity.
+            let (worker_id, idle_since) = match idle.iter().min_by_key(|(_, t)| *t) {
+                Some(w) => *w,
+                None => return,
+            };
+
+            if idle_since.elapsed() < IDLE_REAP_AGE {
+                return;
+            }
+
+            info!("server: reaping idle worker {worker_id}");
+
+            if let Err(e) = self.send_worker_control(worker_id, "stop") {
+                error!("server: cannot stop worker {worker_id}: {e}");
+                return;
+            }
+
+            if let Some(worker) = self.workers.get_mut(&worker_id) {
+                worker.reaped = true;
+            }
+        }
+    }
+
+    fn handle_worker_event(&mut self, event: WorkerStateEvent) {
+        trace!("server: worker event: {event:?}");
+
+        let worker_id = event.worker_id();
+
+        if event.state() == WorkerState::Done {
+            self.remove_worker(worker_id);
+            self.spawn_min_workers();
+            return;
+        }
+
+        if let Some(worker) = self.workers.get_mut(&worker_id) {
+            if event.state() == WorkerState::Idle && worker.state != WorkerState::Idle {
+                worker.idle_since = Instant::now();
+            }
+
+            worker.state = event.state();
+        } else {
+            warn!("server: event for unknown worker {worker_id}");
+        }
+    }
+
+    /// Reaps any threads that exited without saying goodbye and
+    /// respawns to maintain the minimum pool size.
+    fn check_failed_threads(&mut self) {
+        let failed: Vec<u64> = self
+            .workers
+            .iter()
+            .filter(|(_, v)| v.join_handle.is_finished())
+            .map(|(k, _)| *k)
+            .collect();
+
+        for worker_id in failed {
+            warn!("server: worker {worker_id} exited unexpectedly");
+            self.remove_worker(worker_id);
+        }
+
+        self.spawn_min_workers();
+    }
+
+    /// Performs a non-blocking check for admin commands arriving on
+    /// our bus address, e.g. a drain notice relayed by the router.
+    fn check_bus_commands(&mut self) -> Result<(), String> {
+        let tmsg = match self
+            .client
+            .singleton()
+            .borrow_mut()
+            .bus_mut()
+            .recv(Duration::ZERO, None)?
+        {
+            Some(t) => t,
+            None => return Ok(()),
+        };
+
+        match tmsg.router_command().unwrap_or("") {
+            "drain" => self.drain(),
+            command => warn!("server: unexpected bus command: {command}"),
+        }
+
+        Ok(())
+    }
+
+    /// Drain sequence for shutdown: unregister from the routers so
+    /// no new traffic heads our way, let workers finish their
+    /// current requests -- they already see the stopping flag and
+    /// refuse new work -- then wait up to the grace period for the
+    /// pool to empty.
+    fn shutdown_drain(&mut self) {
+        if let Err(e) = self.unregister_routers() {
+            error!("server: error unregistering: {e}");
+        }
+
+        // Suppresses pool top-ups while we wait.
+        self.draining.store(true, Ordering::Relaxed);
+
+        let timer = util::Timer::new(self.shutdown_grace);
+
+        while !self.workers.is_empty() && !timer.done() {
+            match self.to_parent_rx.recv_timeout(std::cmp::min(
+                timer.remaining(),
+                Duration::from_secs(LISTEN_WAKE_TIME),
+            )) {
+                Ok(event) => self.handle_worker_event(event),
+                Err(mpsc::RecvTimeoutError::Timeout) => (),
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+
+            // Reap threads that exited without a Done event.
+            let finished: Vec<u64> = self
+                .workers
+                .iter()
+                .filter(|(_, v)| v.join_handle.is_finished())
+                .map(|(k, _)| *k)
+                .collect();
+
+            for worker_id in finished {
+                self.remove_worker(worker_id);
+            }
+        }
+
+        if !self.workers.is_empty() {
+            warn!(
+                "server: {} workers still busy after {:?} grace period",
+                self.workers.len(),
+                self.shutdown_grace
+            );
+        }
+    }
+
+    /// Unregisters from all routers and lets in-flight conversations
+    /// finish before exiting; used for rolling restarts.
+    fn drain(&mut self) {
+        info!("server: drain requested");
+
+        if let Err(e) = self.unregister_routers() {
+            error!("server: error unregistering: {e}");
+        }
+
+        self.draining.store(true, Ordering::Relaxed);
+    }
+
+    /// Writes a command ("stop", "dump-state", "reload-env") to one
+    /// worker's control stream.
+    pub fn send_worker_control(&self, worker_id: u64, command: &str) -> Result<(), String> {
+        let stream = Worker::control_stream(&self.service, worker_id);
+
+        let mut tmsg = TransportMessage::new(
+            &stream,
+            self.client.address().full(),
+            &util::random_number(16),
+        );
+
+        tmsg.set_router_command(command);
+
+        self.client
+            .singleton()
+            .borrow_mut()
+            .bus_mut()
+            .send_to(&tmsg, &stream)
+    }
+
+    fn remove_worker(&mut self, worker_id: u64) {
+        if let Some(worker) = self.workers.remove(&worker_id) {
+            if let Err(e) = worker.join_handle.join() {
+                error!("server: worker {worker_id} panicked: {e:?}");
+            }
+        }
+    }
+}
@@ -0,0 +1,2476 @@
+use super::addr::{ClientAddress, ServiceAddress};
+use super::bus;
+use super::bus::Bus;
+use super::classified::DataSerializer;
+use super::client::Client;
+use super::logging::Logger;
+use super::message;
+use super::message::Message;
+use super::message::MessageStatus;
+use super::message::MessageType;
+use super::message::Payload;
+use super::message::TransportMessage;
+use super::params::ApiParams;
+use super::util;
+use json::JsonValue;
+use log::{debug, error, trace, warn};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::process;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Default wait for a CONNECT acknowledgement; see
+/// SessionHandle::set_connect_timeout().
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default wait when collecting responses; see
+/// SessionHandle::set_request_timeout().
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default cap on total request attempts for a RetryPolicy.
+const DEFAULT_RETRY_ATTEMPTS: usize = 3;
+
+/// First request retry waits this long; each subsequent retry
+/// doubles the delay.
+const RETRY_BASE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// How often a keepalive thread checks for its stop signal while
+/// waiting out the heartbeat interval.
+const KEEPALIVE_POLL: Duration = Duration::from_millis(250);
+
+/// Default cap on idle sessions kept by a SessionPool.
+const DEFAULT_POOL_MAX_IDLE: usize = 8;
+
+/// Pooled sessions idle longer than this are discarded rather than
+/// handed back out.
+const DEFAULT_POOL_MAX_IDLE_AGE: Duration = Duration::from_secs(300);
+
+/// How long a SessionPool health check waits for its echo.
+const POOL_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Receive timeouts tolerated on a connected session before
+/// failover (when enabled) considers the worker dead.
+const FAILOVER_TIMEOUT_THRESHOLD: usize = 2;
+
+/// How long RequestSet::recv() camps on one request before
+/// re-checking the others.
+const REQUEST_SET_POLL: Duration = Duration::from_millis(100);
+
+/// How long recv() naps between backlog checks while bus reads are
+/// paused by a full backlog; see BacklogOverflowPolicy::Block.
+const BACKLOG_FULL_POLL: Duration = Duration::from_millis(100);
+
+/// What to do when a session's backlog exceeds its configured
+/// maximum; see SessionHandle::set_backlog_max().
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BacklogOverflowPolicy {
+    /// Stop reading from the bus until callers drain the backlog.
+    /// Undelivered messages wait on the bus, applying backpressure
+    /// to the sender.  Requests whose replies are stuck behind the
+    /// pause may time out.
+    Block,
+    /// Evict the oldest backlogged message and return an error to
+    /// the caller currently waiting in recv().
+    Drop,
+    /// Tear down the conversation and return an error.
+    Disconnect,
+}
+
+/// Opt-in rules for resubmitting failed requests.
+///
+/// Attach to a whole session with SessionHandle::set_retry_policy()
+/// or to one request with SessionHandle::request_with_retry().
+///
+/// A ServiceNotFound failure means the request was never delivered
+/// -- e.g. the service was mid-restart -- and is always safe to
+/// resubmit.  Any other listed status may have reached a worker, so
+/// it is only retried when the policy is flagged idempotent.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    backoff: Duration,
+    retry_statuses: Vec<MessageStatus>,
+    idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new()
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: DEFAULT_RETRY_ATTEMPTS,
+            backoff: RETRY_BASE_BACKOFF,
+            retry_statuses: vec![MessageStatus::Timeout, MessageStatus::ServiceNotFound],
+            idempotent: false,
+        }
+    }
+
+    pub fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+
+    /// Caps total attempts, counting the initial submission.
+    pub fn set_max_attempts(&mut self, attempts: usize) {
+        self.max_attempts = std::cmp::max(attempts, 1);
+    }
+
+    pub fn backoff(&self) -> Duration {
+        self.backoff
+    }
+
+    /// Sets the delay before the first retry; each subsequent
+    /// retry doubles it.
+    pub fn set_backoff(&mut self, backoff: Duration) {
+        self.backoff = backoff;
+    }
+
+    /// Replaces the set of failure statuses worth retrying.
+    pub fn set_retry_statuses(&mut self, statuses: Vec<MessageStatus>) {
+        self.retry_statuses = statuses;
+    }
+
+    pub fn idempotent(&self) -> bool {
+        self.idempotent
+    }
+
+    /// Marks the method safe to run more than once, allowing
+    /// retries for statuses where it may have already run.
+    pub fn set_idempotent(&mut self, idempotent: bool) {
+        self.idempotent = idempotent;
+    }
+
+    /// True if a request failing with this status should be
+    /// resubmitted.
+    fn should_retry(&self, status: MessageStatus) -> bool {
+        if !self.retry_statuses.contains(&status) {
+            return false;
+        }
+
+        self.idempotent || status == MessageStatus::ServiceNotFound
+    }
+}
+
+/// Client-side state for one conversation with a service.
+pub struct Session {
+    client: Client,
+
+    /// The service we're talking to.
+    service: String,
+
+    /// True if we're in a connected (stateful) conversation with a
+    /// specific worker.
+    connected: bool,
+
+    /// Identifier for this session, shared by all of its messages.
+    thread: String,
+
+    /// Each outbound request gets the next thread_trace value.
+    last_thread_trace: usize,
+
+    /// Messages for this session that have been pulled off the
+    /// transport backlog but not yet delivered to a Request, each
+    /// paired with its arrival time.
+    backlog: Vec<(Instant, Message)>,
+
+    /// Per-session backlog cap.  None means only the client-wide
+    /// limits apply; see SessionHandle::set_backlog_max().
+    backlog_max: Option<usize>,
+
+    /// Overflow behavior when backlog_max is exceeded.
+    backlog_policy: BacklogOverflowPolicy,
+
+    /// Messages evicted from the backlog for the life of this
+    /// session.
+    backlog_dropped: usize,
+
+    /// Thread traces of requests that have received their Complete
+    /// status.
+    complete_requests: HashSet<usize>,
+
+    /// Address of the specific worker we're connected to, if any.
+    remote_addr: Option<ClientAddress>,
+
+    /// When set, stateless requests carry this key so the router can
+    /// hash them to a consistent worker.
+    affinity_key: Option<String>,
+
+    /// Locale for this session's requests, overriding the
+    /// client-level default.
+    locale: Option<String>,
+
+    /// Serializer for this session's requests and responses,
+    /// overriding the client-level serializer; see
+    /// SessionHandle::set_serializer().
+    serializer: Option<Arc<dyn DataSerializer>>,
+
+    /// When set, requests created on this session are resubmitted
+    /// per the policy after retryable failures.
+    retry_policy: Option<RetryPolicy>,
+
+    /// Status carried by the most recent request failure, consulted
+    /// when deciding whether to retry.
+    last_failure_status: Option<MessageStatus>,
+
+    /// Method name and submission time per outstanding request
+    /// thread trace, for the client call metrics.
+    pending_metrics: HashMap<usize, (String, Instant)>,
+
+    /// Partial response fragments by thread trace, reassembled
+    /// and delivered on PartialComplete.
+    partial_buffers: HashMap<usize, String>,
+
+    /// Latest progress report per request thread trace; see
+    /// Request::progress().
+    progress: HashMap<usize, JsonValue>,
+
+    /// True if this session may transparently re-CONNECT and
+    /// resume after a worker drops the conversation, e.g. on
+    /// keepalive expiry; see SessionHandle::set_auto_reconnect().
+    auto_reconnect: bool,
+
+    /// True if this session may abandon an unresponsive connected
+    /// worker and re-CONNECT to another.
+    failover: bool,
+
+    /// True if this session's stateless requests should ride the
+    /// service's priority lane.
+    priority: bool,
+
+    /// How long response collection waits by default; individual
+    /// requests may override it.
+    request_timeout: Duration,
+
+    /// How long connect() waits for its acknowledgement.
+    connect_timeout: Duration,
+
+    /// Hard wall-clock cutoff shared by every receive on this
+    /// session, including nested ones like the CONNECT inside
+    /// connect_request(); see SessionHandle::set_deadline().
+    deadline: Option<Instant>,
+
+    /// How many additional CONNECT attempts are made after the
+    /// first one times out.
+    connect_retries: usize,
+
+    /// When true, a session whose CONNECT attempts all time out
+    /// proceeds stateless instead of failing; requests route
+    /// through the router as usual.
+    connect_fallback: bool,
+}
+
+impl fmt::Display for Session {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Session({} {})", self.service, self.thread)
+    }
+}
+
+impl Session {
+    fn new(client: Client, service: &str) -> Session {
+        Session {
+            client,
+            service: service.to_string(),
+            connected: false,
+            thread: util::random_number(16),
+            last_thread_trace: 0,
+            backlog: Vec::new(),
+            backlog_max: None,
+            backlog_policy: BacklogOverflowPolicy::Block,
+            backlog_dropped: 0,
+            complete_requests: HashSet::new(),
+            remote_addr: None,
+            affinity_key: None,
+            locale: None,
+            serializer: None,
+            retry_policy: None,
+            last_failure_status: None,
+            pending_metrics: HashMap::new(),
+            partial_buffers: HashMap::new(),
+            progress: HashMap::new(),
+            auto_reconnect: false,
+            failover: false,
+            priority: false,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            connect_timeout: CONNECT_TIMEOUT,
+            deadline: None,
+            connect_retries: 0,
+            connect_fallback: false,
+        }
+    }
+
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    pub fn thread(&self) -> &str {
+        &self.thread
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    /// Replaces the random thread with one built from a
+    /// caller-supplied label; see SessionHandle::set_thread_prefix().
+    fn set_thread_prefix(&mut self, prefix: &str) -> Result<(), String> {
+        if self.last_thread_trace > 0 || self.connected {
+            return Err(format!(
+                "{self} cannot rename a session that has already sent requests"
+            ));
+        }
+
+        // A random suffix preserves uniqueness when one workflow
+        // opens several sessions under the same label.
+        self.thread = format!("{prefix}-{}", util::random_number(8));
+
+        Ok(())
+    }
+
+    fn request_complete(&self, thread_trace: usize) -> bool {
+        self.complete_requests.contains(&thread_trace)
+    }
+
+    fn mark_complete(&mut self, thread_trace: usize) {
+        self.complete_requests.insert(thread_trace);
+        self.progress.remove(&thread_trace);
+    }
+
+    /// Where outbound messages should land: the connected worker's
+    /// address when connected, otherwise the service-level address
+    /// for the router / any listening worker to pick up.
+    fn destination_addr(&self) -> String {
+        if self.connected {
+            if let Some(addr) = self.remote_addr.as_ref() {
+                return addr.full().to_string();
+            }
+        }
+
+        if self.priority {
+            return ServiceAddress::new_priority(&self.service)
+                .full()
+                .to_string();
+        }
+
+        ServiceAddress::new(&self.service).full().to_string()
+    }
+
+    /// The locale for outbound requests: ours if set, else the
+    /// client-level default.
+    fn locale(&self) -> Option<String> {
+        self.locale
+            .clone()
+            .or_else(|| self.client.singleton().borrow().locale().map(str::to_string))
+    }
+
+    /// Builds a receive timer honoring the session deadline, when
+    /// one is set, so status-driven resets cannot extend the total
+    /// wall time past it.
+    fn timer(&self, timeout: Duration) -> util::Timer {
+        match self.deadline {
+            Some(d) => util::Timer::new_with_deadline(timeout, d),
+            None => util::Timer::new(timeout),
+        }
+    }
+
+    /// This session's serializer override, else the client-wide
+    /// serializer, if any.
+    fn serializer(&self) -> Option<Arc<dyn DataSerializer>> {
+        self.serializer
+            .clone()
+            .or_else(|| self.client.singleton().borrow().serializer().cloned())
+    }
+
+    /// Packs the params with the configured serializer, if any.
+    fn pack_params(&self, params: ApiParams) -> ApiParams {
+        match self.serializer() {
+            Some(s) => params
+                .params()
+                .iter()
+                .map(|p| s.pack(p))
+                .collect::<Vec<JsonValue>>()
+                .into(),
+            None => params,
+        }
+    }
+
+    fn unpack_content(&self, content: JsonValue) -> JsonValue {
+        match self.serializer() {
+            Some(s) => s.unpack(&content),
+            None => content,
+        }
+    }
+
+    fn send_msg(&mut self, msg: Message) -> Result<(), String> {
+        self.send_msgs(vec![msg])
+    }
+
+    /// Packs one or more messages into a single TransportMessage so
+    /// they ride one bus send.
+    fn send_msgs(&mut self, msgs: Vec<Message>) -> Result<(), String> {
+        let mut tmsg = TransportMessage::new(
+            &self.destination_addr(),
+            self.client.address().full(),
+            &self.thread,
+        );
+
+        for msg in msgs {
+            tmsg.body_mut().push(msg);
+        }
+
+        if !self.connected {
+            if let Some(key) = self.affinity_key.as_deref() {
+                tmsg.set_affinity_key(key);
+            }
+        }
+
+        // Tag the envelope for cross-host log tracing: reuse the
+        // xid of the request we're servicing, if any, else mint
+        // one here at the origin.
+        let xid = Logger::xid().unwrap_or_else(Logger::mk_xid);
+        tmsg.set_osrf_xid(&xid);
+
+        self.client.singleton().borrow_mut().send(&tmsg)
+    }
+
+    /// Builds a Request message for the method and params, assigning
+    /// the next thread trace and recording call metrics.
+    fn build_request(&mut self, method: &str, params: ApiParams) -> Message {
+        self.last_thread_trace += 1;
+        let trace = self.last_thread_trace;
+
+        let params = self.pack_params(params);
+        let payload = Payload::Method(message::Method::new(method, params.take_params()));
+
+        let mut msg = Message::new(MessageType::Request, trace, payload);
+
+        if let Some(locale) = self.locale() {
+            msg.set_locale(&locale);
+        }
+
+        self.client
+            .singleton()
+            .borrow_mut()
+            .record_call(&self.service, method);
+
+        self.pending_metrics
+            .insert(trace, (method.to_string(), Instant::now()));
+
+        msg
+    }
+
+    /// Issues a new request, returning its thread trace.
+    fn request(
+        &mut self,
+        method: &str,
+        params: ApiParams,
+        idempotency_key: Option<&str>,
+    ) -> Result<usize, String> {
+        debug!("{self} sending request {method}");
+
+        self.last_failure_status = None;
+
+        let mut msg = self.build_request(method, params);
+
+        if let Some(key) = idempotency_key {
+            msg.set_idempotency_key(key);
+        }
+
+        let trace = msg.thread_trace();
+
+        self.send_msg(msg)?;
+
+        Ok(trace)
+    }
+
+    /// Issues several requests packed into one bus send, returning
+    /// their thread traces in call order.
+    fn request_batch(&mut self, calls: Vec<(String, ApiParams)>) -> Result<Vec<usize>, String> {
+        debug!("{self} sending batch of {} requests", calls.len());
+
+        self.last_failure_status = None;
+
+        let mut msgs = Vec::with_capacity(calls.len());
+        let mut traces = Vec::with_capacity(calls.len());
+
+        for (method, params) in calls {
+            let msg = self.build_request(&method, params);
+            traces.push(msg.thread_trace());
+            msgs.push(msg);
+        }
+
+        self.send_msgs(msgs)?;
+
+        Ok(traces)
+    }
+
+    /// Establishes a stateful connection and issues a request in a
+    /// single bus send.
+    ///
+    /// The CONNECT shares the request's thread trace, so the
+    /// worker's Ok status is absorbed -- marking the session
+    /// connected -- while collecting the request's responses.
+    fn connect_request(&mut self, method: &str, params: ApiParams) -> Result<usize, String> {
+        if self.connected {
+            return self.request(method, params, None);
+        }
+
+        debug!("{self} sending CONNECT+REQUEST {method}");
+
+        self.last_failure_status = None;
+
+        let msg = self.build_request(method, params);
+        let trace = msg.thread_trace();
+
+        let connect = Message::new(MessageType::Connect, trace, Payload::NoPayload);
+
+        self.send_msgs(vec![connect, msg])?;
+
+        Ok(trace)
+    }
+
+    /// Issues a request flagged no-reply, expecting no responses
+    /// and no Complete status.
+    fn request_noreply(&mut self, method: &str, params: ApiParams) -> Result<(), String> {
+        debug!("{self} sending no-reply request {method}");
+
+        self.last_thread_trace += 1;
+
+        let params = self.pack_params(params);
+        let payload = Payload::Method(message::Method::new(method, params.take_params()));
+
+        let mut msg = Message::new(MessageType::Request, self.last_thread_trace, payload);
+        msg.set_no_reply(true);
+
+        if let Some(locale) = self.locale() {
+            msg.set_locale(&locale);
+        }
+
+        self.client
+            .singleton()
+            .borrow_mut()
+            .record_call(&self.service, method);
+
+        self.send_msg(msg)
+    }
+
+    /// Establishes a stateful connection with a single worker.
+    fn connect(&mut self) -> Result<(), String> {
+        if self.connected {
+            return Ok(());
+        }
+
+        for attempt in 0..=self.connect_retries {
+            debug!("{self} sending CONNECT attempt={}", attempt + 1);
+
+            self.send_msg(Message::new(
+                MessageType::Connect,
+                self.last_thread_trace,
+                Payload::NoPayload,
+            ))?;
+
+            self.recv(self.last_thread_trace, self.connect_timeout)?;
+
+            if self.connected {
+                return Ok(());
+            }
+        }
+
+        if self.connect_fallback {
+            warn!("{self} no worker accepted the connection; continuing stateless");
+            return Ok(());
+        }
+
+        Err(format!("{self} connect timed out"))
+    }
+
+    /// Abandons an apparently dead connected worker and CONNECTs
+    /// anew, after confirming via the router that the service still
+    /// has live controllers to fail over to.
+    ///
+    /// Returns false when failover is disabled or the session is
+    /// not connected.
+    fn attempt_failover(&mut self) -> Result<bool, String> {
+        if !self.failover || !self.connected {
+            return Ok(false);
+        }
+
+        let domain = self.client.domain().to_string();
+        let summary = self.client.router_summary(&domain)?;
+
+        let has_workers = summary
+            .services()
+            .iter()
+            .any(|s| s.name() == self.service && !s.controllers().is_empty());
+
+        if !has_workers {
+            return Err(format!(
+                "{self} cannot fail over: router reports no controllers for service"
+            ));
+        }
+
+        warn!("{self} connected worker is unresponsive; failing over");
+
+        self.connected = false;
+        self.remote_addr = None;
+
+        self.connect()?;
+
+        Ok(true)
+    }
+
+    /// Breaks a stateful connection.
+    pub(crate) fn disconnect(&mut self) -> Result<(), String> {
+        if self.connected {
+            debug!("{self} sending DISCONNECT");
+
+            self.send_msg(Message::new(
+                MessageType::Disconnect,
+                self.last_thread_trace,
+                Payload::NoPayload,
+            ))?;
+        }
+
+        self.connected = false;
+        self.remote_addr = None;
+
+        Ok(())
+    }
+
+    /// Returns the next response value for the provided request
+    /// thread trace, waiting up to timeout seconds.
+    ///
+    /// Returns None when the timeout is exceeded or the request is
+    /// complete.
+    fn recv(&mut self, thread_trace: usize, timeout: Duration) -> Result<Option<JsonValue>, String> {
+        let mut timer = self.timer(timeout);
+
+        loop {
+            trace!(
+                "{self} in recv() for trace={thread_trace} remaining={:?}",
+                timer.remaining()
+            );
+
+            if let Some(index) = self
+                .backlog
+                .iter()
+                .position(|(_, m)| m.thread_trace() == thread_trace)
+            {
+                let msg = self.backlog.remove(index).1;
+
+                if let Some(value) = self.handle_reply(msg, &mut timer)? {
+                    return Ok(Some(value));
+                }
+
+                if self.request_complete(thread_trace) {
+                    return Ok(None);
+                }
+
+                continue;
+            }
+
+            // Respect the per-session cap before pulling more
+            // messages off the bus.
+            if self.enforce_backlog_max()? {
+                if timer.done() {
+                    return Ok(None);
+                }
+
+                std::thread::sleep(std::cmp::min(timer.remaining(), BACKLOG_FULL_POLL));
+                continue;
+            }
+
+            // One pass through the client backlog and bus happens
+            // even with no time left, so zero-timeout polls can
+            // observe responses that have already arrived.
+            let tmsg_op = self
+                .client
+                .singleton()
+                .borrow_mut()
+                .recv_session(&mut timer, &self.thread)?;
+
+            if let Some(mut tmsg) = tmsg_op {
+                // Responses arrive from a specific worker.  Track its
+                // address for connected conversations.
+                if let Ok(addr) = ClientAddress::from_string(tmsg.from()) {
+                    self.remote_addr = Some(addr);
+                }
+
+                for msg in tmsg.body_mut().drain(..) {
+                    self.backlog.push((Instant::now(), msg));
+                }
+
+                self.evict_backlog();
+            } else if timer.done() {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Applies the per-session backlog cap.
+    ///
+    /// Returns true when bus reads should pause until callers
+    /// drain the backlog; returns Err when the configured policy
+    /// turns overflow into a caller-visible failure.
+    fn enforce_backlog_max(&mut self) -> Result<bool, String> {
+        let max = match self.backlog_max {
+            Some(max) => max,
+            None => return Ok(false),
+        };
+
+        if self.backlog.len() < max {
+            return Ok(false);
+        }
+
+        match self.backlog_policy {
+            BacklogOverflowPolicy::Block => {
+                trace!("{self} backlog full at {max}; pausing bus reads");
+                Ok(true)
+            }
+            BacklogOverflowPolicy::Drop => {
+                self.backlog.remove(0);
+                self.backlog_dropped += 1;
+                Err(format!(
+                    "{self} backlog exceeded {max} messages; dropped oldest"
+                ))
+            }
+            BacklogOverflowPolicy::Disconnect => {
+                self.disconnect().ok();
+                Err(format!(
+                    "{self} backlog exceeded {max} messages; disconnecting"
+                ))
+            }
+        }
+    }
+
+    /// Summarizes the undelivered messages sitting in this
+    /// session's backlog; see SessionHandle::backlog_summary().
+    fn backlog_summary(&self) -> JsonValue {
+        let entries = self
+            .backlog
+            .iter()
+            .map(|(added, msg)| {
+                json::object! {
+                    "trace": msg.thread_trace(),
+                    "type": msg.mtype().to_string(),
+                    "age_ms": added.elapsed().as_millis() as usize,
+                }
+            })
+            .collect::<Vec<JsonValue>>();
+
+        JsonValue::Array(entries)
+    }
+
+    /// Evicts expired or excess backlog entries, oldest first,
+    /// using the client-wide backlog limits.
+    ///
+    /// Replies to requests that gave up waiting would otherwise
+    /// accumulate for the life of the session.
+    fn evict_backlog(&mut self) {
+        let (max_size, max_age) = {
+            let singleton = self.client.singleton().borrow();
+            (singleton.backlog_max_size(), singleton.backlog_max_age())
+        };
+
+        let before = self.backlog.len();
+
+        self.backlog.retain(|(added, _)| added.elapsed() < max_age);
+
+        while self.backlog.len() > max_size {
+            // Entries are stored in arrival order.
+            self.backlog.remove(0);
+        }
+
+        let dropped = before - self.backlog.len();
+
+        if dropped > 0 {
+            self.backlog_dropped += dropped;
+            warn!("{self} evicted {dropped} backlogged messages");
+        }
+    }
+
+    fn handle_reply(
+        &mut self,
+        mut msg: Message,
+        timer: &mut util::Timer,
+    ) -> Result<Option<JsonValue>, String> {
+        let thread_trace = msg.thread_trace();
+
+        match msg.take_payload() {
+            Payload::Result(mut res) => match res.status() {
+                // Chunked responses arrive as string fragments;
+                // buffer until the final chunk lands.
+                MessageStatus::Partial => {
+                    trace!("{self} received partial response for trace={thread_trace}");
+
+                    let fragment = res.take_content();
+
+                    let part = fragment.as_str().ok_or_else(|| {
+                        format!("{self} received non-string partial response: {fragment}")
+                    })?;
+
+                    self.partial_buffers
+                        .entry(thread_trace)
+                        .or_default()
+                        .push_str(part);
+
+                    // More chunks are on the way.
+                    timer.reset();
+                    Ok(None)
+                }
+                MessageStatus::PartialComplete => {
+                    let mut body = self.partial_buffers.remove(&thread_trace).unwrap_or_default();
+
+                    if let Some(tail) = res.take_content().as_str() {
+                        body.push_str(tail);
+                    }
+
+                    trace!("{self} reassembled chunked response for trace={thread_trace}");
+
+                    let value = json::parse(&body).map_err(|e| {
+                        format!("{self} reassembled response is unparseable JSON: {e} : {body}")
+                    })?;
+
+                    self.record_first_response(thread_trace);
+
+                    Ok(Some(self.unpack_content(value)))
+                }
+                _ => {
+                    trace!("{self} received response for trace={thread_trace}");
+                    self.record_first_response(thread_trace);
+                    Ok(Some(self.unpack_content(res.take_content())))
+                }
+            },
+            Payload::Status(stat) => self.unpack_status_message(thread_trace, &stat, timer),
+            payload => Err(format!(
+                "{self} unexpected response for trace={thread_trace}: {payload:?}"
+            )),
+        }
+    }
+
+    /// Feeds the time to a request's first response into the
+    /// client call metrics.
+    fn record_first_response(&mut self, thread_trace: usize) {
+        if let Some((method, start)) = self.pending_metrics.remove(&thread_trace) {
+            self.client.singleton().borrow_mut().record_latency(
+                &self.service,
+                &method,
+                start.elapsed(),
+            );
+        }
+    }
+
+    fn unpack_status_message(
+        &mut self,
+        thread_trace: usize,
+        statmsg: &message::Status,
+        timer: &mut util::Timer,
+    ) -> Result<Option<JsonValue>, String> {
+        let stat = statmsg.status();
+
+        match stat {
+            MessageStatus::Ok => {
+                self.connected = true;
+                Ok(None)
+            }
+            MessageStatus::Continue => {
+                // Progress reports from long-running handlers ride
+                // Continue statuses with a JSON label; see
+                // ServerSession::send_progress().
+                if let Ok(obj) = json::parse(statmsg.status_label()) {
+                    if obj["percent"].is_number() {
+                        trace!("{self} progress for trace={thread_trace}: {obj}");
+                        self.progress.insert(thread_trace, obj);
+                    }
+                }
+
+                timer.reset();
+                Ok(None)
+            }
+            MessageStatus::Complete => {
+                trace!("{self} request trace={thread_trace} marked as complete");
+                self.mark_complete(thread_trace);
+                Ok(None)
+            }
+            MessageStatus::Partial => {
+                // The fragments themselves arrive as Result
+                // payloads; this status just signals more to come.
+                timer.reset();
+                Ok(None)
+            }
+            MessageStatus::PartialComplete => {
+                trace!("{self} request trace={thread_trace} chunking complete");
+                Ok(None)
+            }
+            MessageStatus::Timeout => {
+                self.connected = false;
+                self.last_failure_status = Some(*stat);
+                Err(format!("{self} request timed out: {statmsg}"))
+            }
+            _ => {
+                self.mark_complete(thread_trace);
+                self.last_failure_status = Some(*stat);
+                Err(format!("{self} request failed: {statmsg}"))
+            }
+        }
+    }
+}
+
+/// Stops the background keepalive it was created for when dropped;
+/// see SessionHandle::start_keepalive().
+#[must_use = "the keepalive stops when the guard is dropped"]
+pub struct KeepaliveGuard {
+    stop: Arc<AtomicBool>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Drop for KeepaliveGuard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+/// Public-facing handle to a client Session.
+pub struct SessionHandle {
+    session: Rc<RefCell<Session>>,
+}
+
+impl SessionHandle {
+    pub fn new(client: Client, service: &str) -> SessionHandle {
+        let session = Session::new(client.clone(), service);
+
+        trace!("Creating session service={service} thread={}", session.thread());
+
+        let session = Rc::new(RefCell::new(session));
+
+        // The client tracks its sessions so Client::shutdown() can
+        // disconnect any still-connected conversations.
+        client
+            .singleton()
+            .borrow_mut()
+            .track_session(Rc::downgrade(&session));
+
+        SessionHandle { session }
+    }
+
+    pub fn connected(&self) -> bool {
+        self.session.borrow().connected()
+    }
+
+    /// Messages evicted from this session's backlog; see
+    /// Client::set_backlog_limits().
+    pub fn backlog_dropped(&self) -> usize {
+        self.session.borrow().backlog_dropped
+    }
+
+    /// Summarizes the messages received for this session but not
+    /// yet delivered to a Request: one object per message with its
+    /// thread trace, message type, and age in milliseconds.
+    ///
+    /// A diagnostic aid for responses that appear to vanish; see
+    /// also Client::backlog_summary() for messages not yet routed
+    /// to any session.
+    pub fn backlog_summary(&self) -> JsonValue {
+        self.session.borrow().backlog_summary()
+    }
+
+    /// Applies an affinity key to all stateless requests on this
+    /// session.
+    ///
+    /// The router hashes the key to pick a consistent worker, so
+    /// related requests (e.g. for one user or cache region) land on
+    /// the same process without a full CONNECT.
+    pub fn set_affinity_key(&self, key: &str) {
+        self.session.borrow_mut().affinity_key = Some(key.to_string());
+    }
+
+    /// Sets the locale carried on this session's requests,
+    /// overriding the client-level default.
+    pub fn set_locale(&self, locale: &str) {
+        self.session.borrow_mut().locale = Some(locale.to_string());
+    }
+
+    /// Applies a retry policy to all requests subsequently created
+    /// on this session.  None removes any existing policy.
+    pub fn set_retry_policy(&self, policy: Option<RetryPolicy>) {
+        self.session.borrow_mut().retry_policy = policy;
+    }
+
+    /// Sets the default timeout for collecting responses on this
+    /// session, in place of DEFAULT_REQUEST_TIMEOUT.
+    ///
+    /// Individual requests may override it; see
+    /// request_with_timeout().
+    pub fn set_request_timeout(&self, timeout: Duration) {
+        self.session.borrow_mut().request_timeout = timeout;
+    }
+
+    /// Round-trips a lightweight echo -- to the connected worker
+    /// when connected, else to any listening worker -- to verify
+    /// liveness before committing an expensive request.
+    ///
+    /// Answered by the opensrf.system.echo system method, so it
+    /// works against any service built on this crate.  Returns
+    /// false when no reply arrives within the timeout.
+    pub fn ping(&self, timeout: Duration) -> Result<bool, String> {
+        let mut req = self.request("opensrf.system.echo", "ping")?;
+
+        match req.first(timeout) {
+            Ok(response) => Ok(response.is_some()),
+            Err(e) => {
+                debug!("Ping failed: {e}");
+                Ok(false)
+            }
+        }
+    }
+
+    /// Overrides the client-wide serializer for this session, so
+    /// one client can speak to both fieldmapper-aware and plain
+    /// services.
+    pub fn set_serializer(&self, serializer: Arc<dyn DataSerializer>) {
+        self.session.borrow_mut().serializer = Some(serializer);
+    }
+
+    /// Labels this session's thread with a caller-supplied prefix,
+    /// e.g. a workflow or job id, so every service touched by the
+    /// workflow can be grepped out of the logs by one identifier.
+    ///
+    /// A random suffix is appended for uniqueness.  Fails once the
+    /// session has sent anything, since responses are matched by
+    /// thread.
+    pub fn set_thread_prefix(&self, prefix: &str) -> Result<(), String> {
+        self.session.borrow_mut().set_thread_prefix(prefix)
+    }
+
+    /// Caps how many undelivered messages this session will hold;
+    /// None removes the cap.  The client-wide limits set via
+    /// Client::set_backlog_limits() still apply.
+    pub fn set_backlog_max(&self, max: Option<usize>) {
+        self.session.borrow_mut().backlog_max = max;
+    }
+
+    /// Sets what happens when the backlog cap is exceeded.
+    pub fn set_backlog_policy(&self, policy: BacklogOverflowPolicy) {
+        self.session.borrow_mut().backlog_policy = policy;
+    }
+
+    /// Sets how long connect() waits for its acknowledgement, in
+    /// place of the 10-second default.
+    pub fn set_connect_timeout(&self, timeout: Duration) {
+        self.session.borrow_mut().connect_timeout = timeout;
+    }
+
+    /// Imposes a hard wall-clock cutoff on every receive for this
+    /// session, nested ones included.
+    ///
+    /// Per-wait timeouts restart whenever the server sends a
+    /// Continue status; the deadline bounds the total elapsed time
+    /// regardless.  None removes the cutoff.
+    pub fn set_deadline(&self, deadline: Option<Instant>) {
+        self.session.borrow_mut().deadline = deadline;
+    }
+
+    /// Sets how many additional CONNECT attempts are made after
+    /// the first one times out.  Defaults to zero.
+    pub fn set_connect_retries(&self, retries: usize) {
+        self.session.borrow_mut().connect_retries = retries;
+    }
+
+    /// When enabled, a session whose CONNECT attempts all time out
+    /// proceeds stateless instead of failing.  Each request then
+    /// routes through the router and may land on a different
+    /// worker, so only enable this for conversations that do not
+    /// depend on worker-side state.
+    pub fn set_connect_fallback(&self, fallback: bool) {
+        self.session.borrow_mut().connect_fallback = fallback;
+    }
+
+    /// Routes this session's stateless requests to the service's
+    /// priority lane, which workers poll ahead of the main service
+    /// stream.
+    ///
+    /// Reserve this for interactive traffic; batch jobs belong on
+    /// the main stream.
+    pub fn set_priority(&self, priority: bool) {
+        self.session.borrow_mut().priority = priority;
+    }
+
+    /// Enables transparent reconnection: when a connected worker
+    /// reports the conversation timed out -- typically keepalive
+    /// expiry on an idle session -- the session re-CONNECTs and
+    /// replays the in-flight request instead of surfacing an
+    /// error.
+    ///
+    /// Only requests governed by a retry policy marked idempotent
+    /// are replayed; others still surface the timeout to the
+    /// caller.
+    pub fn set_auto_reconnect(&self, reconnect: bool) {
+        self.session.borrow_mut().auto_reconnect = reconnect;
+    }
+
+    /// Enables failover for connected conversations: when the
+    /// connected worker stops responding, the session verifies with
+    /// the router that the service is still up, re-CONNECTs to
+    /// another worker, and replays the in-flight request.
+    ///
+    /// Only requests governed by a retry policy marked idempotent
+    /// are replayed; others still surface the timeout to the
+    /// caller.
+    pub fn set_failover(&self, failover: bool) {
+        self.session.borrow_mut().failover = failover;
+    }
+
+    /// Issues a request using the OpenSRF ".atomic" convention:
+    /// the server aggregates every response into one JSON array,
+    /// delivered as a single response value.
+    ///
+    /// The suffix is appended to the method name when absent.
+    pub fn request_atomic(
+        &self,
+        method: &str,
+        params: impl Into<ApiParams>,
+    ) -> Result<Request, String> {
+        if method.ends_with(".atomic") {
+            self.request(method, params)
+        } else {
+            self.request(&format!("{method}.atomic"), params)
+        }
+    }
+
+    /// Issues a new API request and returns the Request for
+    /// response collection.
+    pub fn request(
+        &self,
+        method: &str,
+        params: impl Into<ApiParams>,
+    ) -> Result<Request, String> {
+        let params = params.into();
+
+        let retry = self
+            .session
+            .borrow()
+            .retry_policy
+            .clone()
+            .map(|p| (p, method.to_string(), params.params().clone()));
+
+        let thread_trace = self.session.borrow_mut().request(method, params, None)?;
+
+        Ok(Request::new(self.session.clone(), thread_trace, retry))
+    }
+
+    /// Issues a new API request whose responses are collected with
+    /// the provided default timeout, in place of the session
+    /// default.
+    ///
+    /// The timeout governs sendrecv()-style iteration and
+    /// recv_spooled(); Request::recv() still takes an explicit
+    /// timeout per wait.
+    pub fn request_with_timeout(
+        &self,
+        method: &str,
+        params: impl Into<ApiParams>,
+        timeout: Duration,
+    ) -> Result<Request, String> {
+        let mut request = self.request(method, params)?;
+        request.default_timeout = Some(timeout);
+
+        Ok(request)
+    }
+
+    /// Issues a new API request governed by the provided retry
+    /// policy, overriding any session-level policy.
+    pub fn request_with_retry(
+        &self,
+        method: &str,
+        params: impl Into<ApiParams>,
+        policy: RetryPolicy,
+    ) -> Result<Request, String> {
+        let params = params.into();
+        let retry = Some((policy, method.to_string(), params.params().clone()));
+
+        let thread_trace = self.session.borrow_mut().request(method, params, None)?;
+
+        Ok(Request::new(self.session.clone(), thread_trace, retry))
+    }
+
+    /// Issues a new API request tagged with an idempotency key.
+    ///
+    /// If the service sees the same key again within its dedup
+    /// window (e.g. after a gateway retry), it replays the original
+    /// responses instead of re-running the handler.
+    pub fn request_idempotent(
+        &self,
+        method: &str,
+        params: impl Into<ApiParams>,
+        idempotency_key: &str,
+    ) -> Result<Request, String> {
+        let thread_trace =
+            self.session
+                .borrow_mut()
+                .request(method, params.into(), Some(idempotency_key))?;
+
+        Ok(Request::new(self.session.clone(), thread_trace, None))
+    }
+
+    /// Issues several requests to this service in one bus send,
+    /// returning a Request per call, in call order.
+    ///
+    /// Cuts round trips when multiple independent calls are headed
+    /// to the same service; responses are still collected per
+    /// request.
+    pub fn request_batch<T: Into<ApiParams>>(
+        &self,
+        calls: Vec<(&str, T)>,
+    ) -> Result<Vec<Request>, String> {
+        let calls: Vec<(String, ApiParams)> = calls
+            .into_iter()
+            .map(|(method, params)| (method.to_string(), params.into()))
+            .collect();
+
+        let traces = self.session.borrow_mut().request_batch(calls)?;
+
+        Ok(traces
+            .into_iter()
+            .map(|trace| Request::new(self.session.clone(), trace, None))
+            .collect())
+    }
+
+    /// Connects and issues a request in one envelope, skipping the
+    /// separate CONNECT round trip.
+    ///
+    /// The worker processes the CONNECT first, so the request runs
+    /// on a dedicated connection just as if connect() had been
+    /// called beforehand.
+    pub fn connect_request(
+        &self,
+        method: &str,
+        params: impl Into<ApiParams>,
+    ) -> Result<Request, String> {
+        let thread_trace = self
+            .session
+            .borrow_mut()
+            .connect_request(method, params.into())?;
+
+        Ok(Request::new(self.session.clone(), thread_trace, None))
+    }
+
+    /// Sends a fire-and-forget request: no responses are expected
+    /// or collected, and the worker skips its Complete status.
+    ///
+    /// Suits logging/audit-style calls where nobody reads the
+    /// replies, which otherwise sit in our stream until trimmed.
+    pub fn send_noreply(&self, method: &str, params: impl Into<ApiParams>) -> Result<(), String> {
+        self.session
+            .borrow_mut()
+            .request_noreply(method, params.into())
+    }
+
+    /// Sends a request and returns an iterator over its responses.
+    pub fn sendrecv(
+        &self,
+        method: &str,
+        params: impl Into<ApiParams>,
+    ) -> Result<ResponseIterator, String> {
+        Ok(ResponseIterator::new(self.request(method, params)?))
+    }
+
+    /// Like sendrecv(), but the iterator yields Results so receive
+    /// failures reach the caller instead of silently ending the
+    /// iteration.
+    pub fn try_sendrecv(
+        &self,
+        method: &str,
+        params: impl Into<ApiParams>,
+    ) -> Result<TryResponseIterator, String> {
+        Ok(TryResponseIterator::new(self.request(method, params)?))
+    }
+
+    /// Like sendrecv(), but each response is awaited with the
+    /// provided timeout rather than the session default.
+    pub fn sendrecv_with_timeout(
+        &self,
+        method: &str,
+        params: impl Into<ApiParams>,
+        timeout: Duration,
+    ) -> Result<ResponseIterator, String> {
+        Ok(ResponseIterator::new(self.request_with_timeout(
+            method, params, timeout,
+        )?))
+    }
+
+    /// Sends a request and spools its responses to disk, returning
+    /// an iterator that parses them back one at a time.
+    ///
+    /// Prefer this over sendrecv() for report-style APIs returning
+    /// tens of thousands of rows, where accumulating JsonValues in
+    /// memory is a liability.
+    pub fn sendrecv_spooled(
+        &self,
+        method: &str,
+        params: impl Into<ApiParams>,
+    ) -> Result<SpooledResponseIterator, String> {
+        let mut request = self.request(method, params)?;
+        let reader = request.recv_spooled(request.default_timeout())?;
+
+        Ok(SpooledResponseIterator::new(reader))
+    }
+
+    /// Sends a request and returns its first response, waiting up
+    /// to timeout for one to arrive.
+    ///
+    /// Errors if the request completes, or the timeout lapses,
+    /// without producing a response.  Most settings/auth-style
+    /// calls expect exactly one reply; this saves hand-rolling a
+    /// receive loop for them.  Any responses after the first are
+    /// left for the session to discard.
+    pub fn send_recv_one(
+        &self,
+        method: &str,
+        params: impl Into<ApiParams>,
+        timeout: Duration,
+    ) -> Result<JsonValue, String> {
+        let mut request = self.request(method, params)?;
+
+        match request.recv(timeout)? {
+            Some(value) => Ok(value),
+            None => Err(format!(
+                "{method} returned no response from service {}",
+                self.session.borrow().service()
+            )),
+        }
+    }
+
+    pub fn connect(&self) -> Result<(), String> {
+        self.session.borrow_mut().connect()
+    }
+
+    /// Starts a background heartbeat for this connected session so
+    /// the worker's keepalive window doesn't expire during long
+    /// local processing between requests.
+    ///
+    /// The heartbeat is a content-free status message sent straight
+    /// to the connected worker over a dedicated bus connection,
+    /// since this client's connection is not thread safe.  Drop the
+    /// returned guard to stop the heartbeat -- always do so before
+    /// issuing the session's next request.
+    pub fn start_keepalive(&self, interval: Duration) -> Result<KeepaliveGuard, String> {
+        let session = self.session.borrow();
+
+        let remote_addr = match session.remote_addr.as_ref() {
+            Some(addr) if session.connected() => addr.full().to_string(),
+            _ => {
+                return Err(format!(
+                    "{session} keepalive requires a connected session"
+                ))
+            }
+        };
+
+        let thread = session.thread().to_string();
+        let trace = session.last_thread_trace;
+        let config = session.client.singleton().borrow().config().clone();
+
+        drop(session);
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+
+        let handle = std::thread::spawn(move || {
+            let con = match config.primary_connection() {
+                Some(c) => c,
+                None => return,
+            };
+
+            let mut bus = match Bus::new(con) {
+                Ok(b) => b,
+                Err(e) => return error!("Keepalive cannot connect to bus: {e}"),
+            };
+
+            loop {
+                let started = Instant::now();
+
+                while started.elapsed() < interval {
+                    if thread_stop.load(Ordering::Relaxed) {
+                        return;
+                    }
+
+                    std::thread::sleep(std::cmp::min(
+                        KEEPALIVE_POLL,
+                        interval - started.elapsed(),
+                    ));
+                }
+
+                let status = Payload::Status(message::Status::new(
+                    MessageStatus::Continue,
+                    "keepalive",
+                ));
+
+                let tmsg = TransportMessage::with_body(
+                    &remote_addr,
+                    bus.address().full(),
+                    &thread,
+                    Message::new(MessageType::Status, trace, status),
+                );
+
+                trace!("Sending keepalive for thread {thread}");
+
+                if let Err(e) = bus.send_to(&tmsg, &remote_addr) {
+                    return error!("Keepalive send failed; stopping: {e}");
+                }
+            }
+        });
+
+        Ok(KeepaliveGuard {
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn disconnect(&self) -> Result<(), String> {
+        self.session.borrow_mut().disconnect()
+    }
+}
+
+/// Disconnects a still-connected session when its last strong
+/// handle -- SessionHandle or Request -- is dropped.
+///
+/// The client's session registry holds only weak references, so a
+/// strong count of one means nothing else can use the session.
+fn disconnect_last_handle(session: &Rc<RefCell<Session>>) {
+    if Rc::strong_count(session) > 1 {
+        return;
+    }
+
+    let mut session = session.borrow_mut();
+
+    if session.connected() {
+        if let Err(e) = session.disconnect() {
+            warn!("{} disconnect on drop failed: {e}", *session);
+        }
+    }
+}
+
+impl Drop for SessionHandle {
+    /// Sends a DISCONNECT for a still-connected session so a
+    /// forgotten disconnect() does not leave the worker holding a
+    /// stateful session until its keepalive expires.
+    fn drop(&mut self) {
+        disconnect_last_handle(&self.session);
+    }
+}
+
+/// Pool of connected sessions for one frequently called service.
+///
+/// Re-CONNECTing for every stateful conversation costs a round
+/// trip; a pool keeps a bounded set of connected sessions to check
+/// out and return instead.  Sessions idle too long are discarded,
+/// and a session checked out after sitting idle is health-checked
+/// with a lightweight echo before being handed back out.
+pub struct SessionPool {
+    client: Client,
+    service: String,
+
+    /// Idle connected sessions, each paired with its checkin time.
+    idle: Vec<(Instant, SessionHandle)>,
+
+    /// Cap on idle sessions retained at checkin.
+    max_idle: usize,
+
+    /// Idle sessions older than this are discarded at checkout.
+    max_idle_age: Duration,
+}
+
+impl SessionPool {
+    pub fn new(client: Client, service: &str) -> SessionPool {
+        SessionPool {
+            client,
+            service: service.to_string(),
+            idle: Vec::new(),
+            max_idle: DEFAULT_POOL_MAX_IDLE,
+            max_idle_age: DEFAULT_POOL_MAX_IDLE_AGE,
+        }
+    }
+
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    /// Idle sessions currently held by the pool.
+    pub fn idle_count(&self) -> usize {
+        self.idle.len()
+    }
+
+    /// Caps the idle sessions retained at checkin; excess sessions
+    /// are disconnected, oldest first.
+    pub fn set_max_idle(&mut self, max_idle: usize) {
+        self.max_idle = max_idle;
+    }
+
+    /// Sets how long a session may sit idle before checkout
+    /// discards it instead of reusing it.
+    pub fn set_max_idle_age(&mut self, max_idle_age: Duration) {
+        self.max_idle_age = max_idle_age;
+    }
+
+    /// Returns a connected session, reusing a healthy idle one when
+    /// available and connecting a new one otherwise.
+    pub fn checkout(&mut self) -> Result<SessionHandle, String> {
+        // Most recently checked in first; it's the most likely to
+        // still be healthy.
+        while let Some((added, session)) = self.idle.pop() {
+            if added.elapsed() > self.max_idle_age {
+                session.disconnect().ok();
+                continue;
+            }
+
+            if self.healthy(&session) {
+                return Ok(session);
+            }
+
+            debug!("SessionPool({}) discarding unhealthy session", self.service);
+            session.disconnect().ok();
+        }
+
+        let session = self.client.session(&self.service);
+        session.connect()?;
+
+        Ok(session)
+    }
+
+    /// Returns a session to the pool for reuse.
+    ///
+    /// Sessions that are no longer connected are dropped instead of
+    /// pooled; if the pool is at capacity, the oldest idle session
+    /// is disconnected to make room.
+    pub fn checkin(&mut self, session: SessionHandle) {
+        if !session.connected() {
+            return;
+        }
+
+        self.idle.push((Instant::now(), session));
+
+        while self.idle.len() > self.max_idle {
+            let (_, session) = self.idle.remove(0);
+            session.disconnect().ok();
+        }
+    }
+
+    /// Disconnects and discards every idle session.
+    pub fn clear(&mut self) {
+        for (_, session) in self.idle.drain(..) {
+            session.disconnect().ok();
+        }
+    }
+
+    /// True if the session's worker still answers a ping.
+    fn healthy(&self, session: &SessionHandle) -> bool {
+        session.connected()
+            && session
+                .ping(POOL_HEALTH_CHECK_TIMEOUT)
+                .unwrap_or(false)
+    }
+}
+
+/// An in-flight API request.
+pub struct Request {
+    session: Rc<RefCell<Session>>,
+    thread_trace: usize,
+    complete: bool,
+
+    /// Policy plus the method and params needed to resubmit, when
+    /// retries apply to this request.
+    retry: Option<(RetryPolicy, String, Vec<JsonValue>)>,
+
+    /// Submissions so far, counting the original.
+    attempts: usize,
+
+    /// Consecutive receive timeouts, feeding failover detection.
+    timeouts: usize,
+
+    /// Per-request override of the session's default response
+    /// timeout.
+    default_timeout: Option<Duration>,
+}
+
+impl Request {
+    fn new(
+        session: Rc<RefCell<Session>>,
+        thread_trace: usize,
+        retry: Option<(RetryPolicy, String, Vec<JsonValue>)>,
+    ) -> Request {
+        Request {
+            session,
+            thread_trace,
+            complete: false,
+            retry,
+            attempts: 1,
+            timeouts: 0,
+            default_timeout: None,
+        }
+    }
+
+    /// True once this request has received its Complete status.
+    pub fn complete(&self) -> bool {
+        self.complete
+    }
+
+    /// The thread trace tying this request's messages together,
+    /// unique within its session.
+    pub fn thread_trace(&self) -> usize {
+        self.thread_trace
+    }
+
+    /// How long response collection waits when no explicit timeout
+    /// is given: this request's override if set, else the session
+    /// default.
+    pub fn default_timeout(&self) -> Duration {
+        self.default_timeout
+            .unwrap_or_else(|| self.session.borrow().request_timeout)
+    }
+
+    /// Overrides the session's default response timeout for this
+    /// request.
+    pub fn set_default_timeout(&mut self, timeout: Duration) {
+        self.default_timeout = Some(timeout);
+    }
+
+    /// The most recent progress report for this request, an object
+    /// with "percent" and "note" keys; see
+    /// ServerSession::send_progress().
+    ///
+    /// Cleared once the request completes.
+    pub fn progress(&self) -> Option<JsonValue> {
+        self.session
+            .borrow()
+            .progress
+            .get(&self.thread_trace)
+            .cloned()
+    }
+
+    /// Returns the next response for this request, waiting up to
+    /// timeout seconds.
+    ///
+    /// Returns None once the request is complete or the timeout is
+    /// exceeded.
+    pub fn recv(&mut self, timeout: Duration) -> Result<Option<JsonValue>, String> {
+        if self.complete {
+            return Ok(None);
+        }
+
+        loop {
+            let result = self.session.borrow_mut().recv(self.thread_trace, timeout);
+
+            let err = match result {
+                Ok(response) => {
+                    if self.session.borrow().request_complete(self.thread_trace) {
+                        self.complete = true;
+                    }
+
+                    if response.is_some() {
+                        self.timeouts = 0;
+                    } else if !self.complete && !timeout.is_zero() {
+                        // The wait lapsed without any reply.
+                        self.timeouts += 1;
+
+                        if self.try_failover()? {
+                            continue;
+                        }
+                    }
+
+                    return Ok(response);
+                }
+                Err(e) => e,
+            };
+
+            // A worker reporting Timeout on a connected session
+            // has dropped the conversation; optionally reconnect
+            // and resume in place of the retry machinery below.
+            if self.try_reconnect()? {
+                continue;
+            }
+
+            let (policy, method, params) = match self.retry.as_ref() {
+                Some(r) => r,
+                None => return Err(err),
+            };
+
+            let status = self.session.borrow().last_failure_status;
+
+            let retryable = match status {
+                Some(stat) => policy.should_retry(stat),
+                None => false,
+            };
+
+            if !retryable || self.attempts >= policy.max_attempts() {
+                return Err(err);
+            }
+
+            let backoff = policy.backoff() * (1 << std::cmp::min(self.attempts - 1, 16)) as u32;
+
+            debug!(
+                "Resubmitting {method} in {backoff:?} (attempt {} of {}) after: {err}",
+                self.attempts + 1,
+                policy.max_attempts()
+            );
+
+            std::thread::sleep(backoff);
+
+            self.thread_trace = self
+                .session
+                .borrow_mut()
+                .request(method, params.clone().into(), None)?;
+
+            self.attempts += 1;
+        }
+    }
+
+    /// Non-blocking recv(): returns a response only if one has
+    /// already arrived, for polling-style event loops.
+    pub fn try_recv(&mut self) -> Result<Option<JsonValue>, String> {
+        self.recv(Duration::ZERO)
+    }
+
+    /// Collects every remaining response into a Vec, waiting up to
+    /// timeout for each one, until the request completes.
+    pub fn recv_all(&mut self, timeout: Duration) -> Result<Vec<JsonValue>, String> {
+        let mut responses = Vec::new();
+
+        while let Some(value) = self.recv(timeout)? {
+            responses.push(value);
+        }
+
+        Ok(responses)
+    }
+
+    /// Returns the first response, draining -- and discarding --
+    /// any that follow so the request is left complete.
+    ///
+    /// Returns None if the request completes, or the timeout
+    /// lapses, without producing a response.
+    pub fn first(&mut self, timeout: Duration) -> Result<Option<JsonValue>, String> {
+        let first = self.recv(timeout)?;
+
+        if first.is_some() {
+            while self.recv(timeout)?.is_some() {}
+        }
+
+        Ok(first)
+    }
+
+    /// After a connected worker reports our conversation timed
+    /// out, re-establishes the connection and resubmits this
+    /// request, provided auto-reconnect is enabled and the
+    /// request's retry policy marks it idempotent.
+    ///
+    /// Returns true when the request was resubmitted.
+    fn try_reconnect(&mut self) -> Result<bool, String> {
+        {
+            let session = self.session.borrow();
+
+            if !session.auto_reconnect
+                || session.last_failure_status != Some(MessageStatus::Timeout)
+            {
+                return Ok(false);
+            }
+        }
+
+        let (method, params) = match self.retry.as_ref() {
+            Some((policy, method, params))
+                if policy.idempotent() && self.attempts < policy.max_attempts() =>
+            {
+                (method.clone(), params.clone())
+            }
+            _ => return Ok(false),
+        };
+
+        {
+            let mut session = self.session.borrow_mut();
+            session.last_failure_status = None;
+            session.remote_addr = None;
+            session.connect()?;
+        }
+
+        warn!("Replaying {method} after reconnecting an expired session");
+
+        self.thread_trace = self
+            .session
+            .borrow_mut()
+            .request(&method, params.into(), None)?;
+
+        self.timeouts = 0;
+        self.attempts += 1;
+
+        Ok(true)
+    }
+
+    /// After repeated receive timeouts on a connected session,
+    /// fails over to another worker and resubmits this request,
+    /// provided failover is enabled and the request's retry policy
+    /// marks it idempotent.
+    ///
+    /// Returns true when the request was resubmitted.
+    fn try_failover(&mut self) -> Result<bool, String> {
+        if self.timeouts < FAILOVER_TIMEOUT_THRESHOLD {
+            return Ok(false);
+        }
+
+        let (method, params) = match self.retry.as_ref() {
+            Some((policy, method, params)) if policy.idempotent() => {
+                (method.clone(), params.clone())
+            }
+            _ => return Ok(false),
+        };
+
+        if !self.session.borrow_mut().attempt_failover()? {
+            return Ok(false);
+        }
+
+        warn!("Replaying {method} after worker failover");
+
+        self.thread_trace = self
+            .session
+            .borrow_mut()
+            .request(&method, params.into(), None)?;
+
+        self.timeouts = 0;
+        self.attempts += 1;
+
+        Ok(true)
+    }
+
+    /// Receives every remaining response for this request, writing
+    /// each to a temp file as one line of JSON instead of holding
+    /// them in memory.
+    ///
+    /// Returns a reader over the spooled data.  The file is
+    /// unlinked up front, so the data is reclaimed once the reader
+    /// is dropped.
+    pub fn recv_spooled(&mut self, timeout: Duration) -> Result<BufReader<File>, String> {
+        let path = std::env::temp_dir().join(format!(
+            "opensrf-spool-{}-{}.ndjson",
+            process::id(),
+            util::random_number(8)
+        ));
+
+        let mut file = match File::create(&path) {
+            Ok(f) => f,
+            Err(e) => return Err(format!("Cannot create spool file: {e}")),
+        };
+
+        while let Some(value) = self.recv(timeout)? {
+            if let Err(e) = writeln!(file, "{}", value.dump()) {
+                fs::remove_file(&path).ok();
+                return Err(format!("Cannot write spool file: {e}"));
+            }
+        }
+
+        drop(file);
+
+        let file = match File::open(&path) {
+            Ok(f) => f,
+            Err(e) => {
+                fs::remove_file(&path).ok();
+                return Err(format!("Cannot reopen spool file: {e}"));
+            }
+        };
+
+        fs::remove_file(&path).ok();
+
+        Ok(BufReader::new(file))
+    }
+}
+
+impl Drop for Request {
+    fn drop(&mut self) {
+        if !self.complete {
+            // Late replies to an abandoned request are discarded
+            // instead of piling up in the session backlog.
+            self.session.borrow_mut().mark_complete(self.thread_trace);
+        }
+
+        disconnect_last_handle(&self.session);
+    }
+}
+
+/// Joins several outstanding Requests, yielding responses as they
+/// arrive instead of draining each request in turn.
+///
+/// The member requests may target different services; fan-out
+/// callers add one request per service, then collect responses
+/// with recv() until complete().  Responses are tagged with the
+/// index add() returned for their request.
+pub struct RequestSet {
+    requests: Vec<Request>,
+}
+
+impl Default for RequestSet {
+    fn default() -> Self {
+        RequestSet::new()
+    }
+}
+
+impl RequestSet {
+    pub fn new() -> RequestSet {
+        RequestSet {
+            requests: Vec::new(),
+        }
+    }
+
+    /// Adds an outstanding request to the set, returning its index
+    /// for matching up responses.
+    pub fn add(&mut self, request: Request) -> usize {
+        self.requests.push(request);
+        self.requests.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// True once every member request is complete.
+    pub fn complete(&self) -> bool {
+        self.requests.iter().all(|r| r.complete)
+    }
+
+    /// Returns the next response from any member request, waiting
+    /// up to timeout for one to arrive.
+    ///
+    /// The response is paired with the index of the request it
+    /// answers.  Returns None once every request is complete or
+    /// the timeout lapses.
+    ///
+    /// Incomplete requests are polled in rotation; while we camp
+    /// on one, replies to the others still land in the client
+    /// backlog and are picked up on the next pass.
+    pub fn recv(&mut self, timeout: Duration) -> Result<Option<(usize, JsonValue)>, String> {
+        let timer = util::Timer::new(timeout);
+
+        loop {
+            let mut camp_index = None;
+
+            for (index, request) in self.requests.iter_mut().enumerate() {
+                if request.complete {
+                    continue;
+                }
+
+                if let Some(value) = request.recv(Duration::ZERO)? {
+                    return Ok(Some((index, value)));
+                }
+
+                if !request.complete && camp_index.is_none() {
+                    camp_index = Some(index);
+                }
+            }
+
+            let index = match camp_index {
+                Some(i) => i,
+                None => return Ok(None), // every request is complete
+            };
+
+            if timer.done() {
+                return Ok(None);
+            }
+
+            let wait = std::cmp::min(timer.remaining(), REQUEST_SET_POLL);
+
+            if let Some(value) = self.requests[index].recv(wait)? {
+                return Ok(Some((index, value)));
+            }
+        }
+    }
+}
+
+/// Iterates over spooled responses, parsing one JSON line at a time
+/// from the spool file; see SessionHandle::sendrecv_spooled().
+pub struct SpooledResponseIterator {
+    reader: BufReader<File>,
+}
+
+impl SpooledResponseIterator {
+    pub fn new(reader: BufReader<File>) -> Self {
+        SpooledResponseIterator { reader }
+    }
+}
+
+impl Iterator for SpooledResponseIterator {
+    type Item = JsonValue;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut line = String::new();
+
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => match json::parse(line.trim_end()) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    error!("SpooledResponseIterator bad spool line: {e}");
+                    None
+                }
+            },
+            Err(e) => {
+                error!("SpooledResponseIterator read error: {e}");
+                None
+            }
+        }
+    }
+}
+
+/// Iterates over the responses to a request.
+///
+/// Notably does not surface receive errors -- iteration simply
+/// ends; see TryResponseIterator when failures matter.
+pub struct ResponseIterator {
+    request: Request,
+}
+
+impl Iterator for ResponseIterator {
+    type Item = JsonValue;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.request.recv(self.request.default_timeout()) {
+            Ok(op) => op,
+            Err(e) => {
+                error!("ResponseIterator error: {e}");
+                None
+            }
+        }
+    }
+}
+
+impl ResponseIterator {
+    pub fn new(request: Request) -> Self {
+        ResponseIterator { request }
+    }
+
+    /// Converts into an iterator that surfaces receive errors.
+    pub fn into_try_iter(self) -> TryResponseIterator {
+        TryResponseIterator::new(self.request)
+    }
+}
+
+/// Iterates over the responses to a request, yielding Results so
+/// callers can tell "stream ended" from "request failed"; see
+/// SessionHandle::try_sendrecv().
+///
+/// Iteration ends after the first error is yielded.
+pub struct TryResponseIterator {
+    request: Request,
+}
+
+impl TryResponseIterator {
+    pub fn new(request: Request) -> Self {
+        TryResponseIterator { request }
+    }
+}
+
+impl Iterator for TryResponseIterator {
+    type Item = Result<JsonValue, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.request.recv(self.request.default_timeout()) {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => None,
+            Err(e) => {
+                // A failed request yields no further responses.
+                self.request.complete = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Server-side session, tracking one conversation from the Worker's
+/// point of view.
+pub struct ServerSession {
+    client: Client,
+
+    /// The session thread, provided by the requesting client.
+    thread: String,
+
+    /// Address of the client we're talking to.
+    sender: ClientAddress,
+
+    /// True if this is a stateful (CONNECT'ed) conversation.
+    connected: bool,
+
+    /// thread_trace of the request currently being serviced.
+    last_thread_trace: usize,
+
+    /// When set, every value passed to respond() is also recorded
+    /// here, e.g. for idempotent response replay.
+    response_log: Option<Vec<JsonValue>>,
+
+    /// True if the request asked for no responses; outbound
+    /// replies are quietly dropped.
+    no_reply: bool,
+
+    /// When the caller used the ".atomic" method suffix, responses
+    /// collect here and go out as one array at completion.
+    atomic_responses: Option<Vec<JsonValue>>,
+
+    /// Locale carried by the request being serviced, if any.
+    locale: Option<String>,
+
+    /// Ingress tag carried by the request being serviced, e.g.
+    /// "opensrf" or a gateway name.
+    ingress: Option<String>,
+
+    /// When the request being serviced has a completion deadline,
+    /// subrequests cap their timeouts at the time remaining.
+    deadline: Option<Instant>,
+}
+
+impl fmt::Display for ServerSession {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ServerSession({})", self.thread)
+    }
+}
+
+impl ServerSession {
+    pub fn new(client: Client, thread: &str, sender: ClientAddress) -> ServerSession {
+        ServerSession {
+            client,
+            sender,
+            thread: thread.to_string(),
+            connected: false,
+            last_thread_trace: 0,
+            response_log: None,
+            no_reply: false,
+            atomic_responses: None,
+            locale: None,
+            ingress: None,
+            deadline: None,
+        }
+    }
+
+    /// Locale carried by the request being serviced, if any.
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    pub fn set_locale(&mut self, locale: Option<&str>) {
+        self.locale = locale.map(str::to_string);
+    }
+
+    /// Ingress tag carried by the request being serviced.
+    pub fn ingress(&self) -> Option<&str> {
+        self.ingress.as_deref()
+    }
+
+    pub fn set_ingress(&mut self, ingress: Option<&str>) {
+        self.ingress = ingress.map(str::to_string);
+    }
+
+    /// Marks this conversation fire-and-forget; see
+    /// SessionHandle::send_noreply().
+    pub fn set_no_reply(&mut self, no_reply: bool) {
+        self.no_reply = no_reply;
+    }
+
+    /// Deadline for the request being serviced, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Sets a completion deadline for the request being serviced;
+    /// subrequests cap their timeouts at the time remaining.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Opens a session to another service through the worker's
+    /// client and issues a request on it, propagating this
+    /// request's context: the nested session shares our thread
+    /// label and locale, carries the same osrf_xid via the logging
+    /// thread-local, and honors any deadline.
+    ///
+    /// Multi-hop call chains stay grep-able by one identifier
+    /// without per-handler boilerplate.
+    pub fn subrequest(
+        &self,
+        service: &str,
+        method: &str,
+        params: impl Into<ApiParams>,
+    ) -> Result<Request, String> {
+        let ses = self.client.session(service);
+
+        ses.set_thread_prefix(self.thread())?;
+
+        if let Some(locale) = self.locale() {
+            ses.set_locale(locale);
+        }
+
+        if let Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                return Err(format!("{self} deadline exhausted before subrequest"));
+            }
+
+            ses.set_request_timeout(remaining);
+            ses.set_deadline(Some(deadline));
+        }
+
+        ses.request(method, params)
+    }
+
+    /// Enables or disables ".atomic" response aggregation for the
+    /// request being serviced.
+    pub(crate) fn set_atomic(&mut self, atomic: bool) {
+        self.atomic_responses = if atomic { Some(Vec::new()) } else { None };
+    }
+
+    /// Enables or disables response recording.
+    pub fn set_record_responses(&mut self, record: bool) {
+        self.response_log = if record { Some(Vec::new()) } else { None };
+    }
+
+    /// Takes ownership of any recorded responses.
+    pub fn take_recorded_responses(&mut self) -> Vec<JsonValue> {
+        self.response_log.take().unwrap_or_default()
+    }
+
+    pub fn thread(&self) -> &str {
+        &self.thread
+    }
+
+    /// Address of the requesting client, exposing its domain,
+    /// hostname, and pid for per-origin policies and audit logs.
+    pub fn sender(&self) -> &ClientAddress {
+        &self.sender
+    }
+
+    pub fn connected(&self) -> bool {
+        self.connected
+    }
+
+    pub fn set_connected(&mut self, connected: bool) {
+        self.connected = connected;
+    }
+
+    pub fn last_thread_trace(&self) -> usize {
+        self.last_thread_trace
+    }
+
+    pub fn set_last_thread_trace(&mut self, trace: usize) {
+        self.last_thread_trace = trace
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    fn send_msg(&self, msg: Message) -> Result<(), String> {
+        if self.no_reply {
+            trace!("{self} dropping reply to a no-reply request");
+            return Ok(());
+        }
+
+        let mut tmsg = TransportMessage::with_body(
+            self.sender.full(),
+            self.client.address().full(),
+            &self.thread,
+            msg,
+        );
+
+        // Replies carry the xid of the request they answer.
+        if let Some(xid) = Logger::xid() {
+            tmsg.set_osrf_xid(&xid);
+        }
+
+        self.client.singleton().borrow_mut().send(&tmsg)
+    }
+
+    /// Sends a single response value to the client.
+    pub fn respond<T>(&mut self, value: T) -> Result<(), String>
+    where
+        T: Into<JsonValue>,
+    {
+        let mut value = value.into();
+
+        if let Some(log) = self.response_log.as_mut() {
+            log.push(value.clone());
+        }
+
+        // Atomic requests aggregate; the array goes out with the
+        // Complete status.
+        if let Some(buf) = self.atomic_responses.as_mut() {
+            buf.push(value);
+            return Ok(());
+        }
+
+        if let Some(s) = self.client.singleton().borrow().serializer() {
+            value = s.pack(&value);
+        }
+
+        let result = message::Result::new(MessageStatus::Ok, "OK", value);
+
+        self.send_msg(Message::new(
+            MessageType::Result,
+            self.last_thread_trace,
+            Payload::Result(result),
+        ))
+    }
+
+    /// Sends a large response value as a series of Partial
+    /// messages, each carrying at most chunk_size bytes of its
+    /// JSON, capped by a PartialComplete.
+    ///
+    /// Matches the chunked responses legacy C services produce, so
+    /// big result sets ride many small stream entries instead of
+    /// one multi-megabyte entry.  The receiving session reassembles
+    /// and delivers the value as a single response.
+    pub fn respond_chunked<T>(&mut self, value: T, chunk_size: usize) -> Result<(), String>
+    where
+        T: Into<JsonValue>,
+    {
+        let mut value = value.into();
+
+        if chunk_size == 0 {
+            return Err(format!("{self} chunk_size must be non-zero"));
+        }
+
+        // Aggregated responses go out as one array at completion;
+        // chunking does not apply.
+        if self.atomic_responses.is_some() {
+            return self.respond(value);
+        }
+
+        if let Some(log) = self.response_log.as_mut() {
+            log.push(value.clone());
+        }
+
+        if let Some(s) = self.client.singleton().borrow().serializer() {
+            value = s.pack(&value);
+        }
+
+        let json_str = value.dump();
+
+        for chunk in bus::chunk_str(&json_str, chunk_size) {
+            let result =
+                message::Result::new(MessageStatus::Partial, "Partial", chunk.into());
+
+            self.send_msg(Message::new(
+                MessageType::Result,
+                self.last_thread_trace,
+                Payload::Result(result),
+            ))?;
+        }
+
+        let result = message::Result::new(
+            MessageStatus::PartialComplete,
+            "Partial Complete",
+            JsonValue::Null,
+        );
+
+        self.send_msg(Message::new(
+            MessageType::Result,
+            self.last_thread_trace,
+            Payload::Result(result),
+        ))
+    }
+
+    /// Sends a response value along with the trailing Complete
+    /// status in a single transport message.
+    pub fn respond_complete<T>(&mut self, value: T) -> Result<(), String>
+    where
+        T: Into<JsonValue>,
+    {
+        self.respond(value)?;
+        self.send_complete()
+    }
+
+    /// Tells the client this request is complete, first flushing
+    /// any aggregated atomic responses as a single array response.
+    pub fn send_complete(&mut self) -> Result<(), String> {
+        if let Some(responses) = self.atomic_responses.take() {
+            let mut value = JsonValue::Array(responses);
+
+            if let Some(s) = self.client.singleton().borrow().serializer() {
+                value = s.pack(&value);
+            }
+
+            let result = message::Result::new(MessageStatus::Ok, "OK", value);
+
+            self.send_msg(Message::new(
+                MessageType::Result,
+                self.last_thread_trace,
+                Payload::Result(result),
+            ))?;
+        }
+
+        self.send_status(MessageStatus::Complete, "Request Complete")
+    }
+
+    /// Emits a non-terminal progress report for a long-running
+    /// request, e.g. an import or report job.
+    ///
+    /// Progress rides a Continue status, so observing clients also
+    /// get their receive timers reset while they wait; the latest
+    /// report is available via Request::progress().
+    pub fn send_progress(&self, percent: u8, note: &str) -> Result<(), String> {
+        let label = json::object! {
+            "percent": percent,
+            "note": note,
+        };
+
+        self.send_status(MessageStatus::Continue, &label.dump())
+    }
+
+    pub fn send_status(&self, status: MessageStatus, label: &str) -> Result<(), String> {
+        self.send_msg(Message::new(
+            MessageType::Status,
+            self.last_thread_trace,
+            Payload::Status(message::Status::new(status, label)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conf::Config;
+
+    /// Requires a running message bus plus the demo service from
+    /// src/bin/demo.rs; run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_pool_reuses_checked_in_session() {
+        let mut conf = Config::localhost_defaults().unwrap();
+        conf.set_primary_connection("client", "localhost").unwrap();
+
+        let client = Client::connect(conf.into_shared()).unwrap();
+        let mut pool = SessionPool::new(client, "opensrf.rsdemo");
+
+        let session = pool.checkout().unwrap();
+        let thread = session.session.borrow().thread().to_string();
+
+        pool.checkin(session);
+        assert_eq!(pool.idle_count(), 1);
+
+        // The same connected session comes back out instead of a
+        // fresh CONNECT.
+        let session = pool.checkout().unwrap();
+        assert_eq!(session.session.borrow().thread(), thread);
+        assert_eq!(pool.idle_count(), 0);
+        assert!(session.connected());
+
+        session.disconnect().unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+#[cfg(not(target_arch = "wasm32"))]
+use rand::Rng;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Tracked origins beyond which the rate limiter prunes buckets
+/// that have refilled, so the map doesn't grow without bound.
+const RATE_LIMIT_MAX_ORIGINS: usize = 1024;
+
+/// Returns a string of random numbers of the requested length.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn random_number(size: u8) -> String {
+    let mut rng = rand::thread_rng();
+    (0..size)
+        .map(|_| rng.gen_range(0..10).to_string())
+        .collect::<String>()
+}
+
+/// Returns a string of random numbers of the requested length.
+///
+/// Bare wasm32 has no OS entropy source; a counter-scrambled
+/// sequence is sufficient for thread/trace identifiers.
+#[cfg(target_arch = "wasm32")]
+pub fn random_number(size: u8) -> String {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static SEED: AtomicU64 = AtomicU64::new(0x9E3779B97F4A7C15);
+
+    let mut value = SEED.fetch_add(0xBF58476D1CE4E5B9, Ordering::Relaxed);
+
+    (0..size)
+        .map(|_| {
+            value ^= value << 13;
+            value ^= value >> 7;
+            value ^= value << 17;
+            (value % 10).to_string()
+        })
+        .collect::<String>()
+}
+
+/// Converts a JSON number or string to an isize if possible.
+pub fn json_isize(value: &json::JsonValue) -> Option<isize> {
+    if let Some(i) = value.as_isize() {
+        return Some(i);
+    } else if let Some(s) = value.as_str() {
+        if let Ok(i) = s.parse::<isize>() {
+            return Some(i);
+        }
+    }
+
+    None
+}
+
+/// Converts a JSON number or string to a usize if possible.
+pub fn json_usize(value: &json::JsonValue) -> Option<usize> {
+    if let Some(i) = value.as_usize() {
+        return Some(i);
+    } else if let Some(s) = value.as_str() {
+        if let Ok(i) = s.parse::<usize>() {
+            return Some(i);
+        }
+    }
+
+    None
+}
+
+/// Simple countdown timer for timeout-style loops.
+///
+/// The timer starts as soon as it's created and may be reset() to
+/// start the countdown again from the original duration.
+pub struct Timer {
+    /// How long we're counting down from.
+    duration: Duration,
+
+    /// Moment the timer starts.
+    start_time: Instant,
+
+    /// Hard wall-clock cutoff that reset() cannot extend past.
+    deadline: Option<Instant>,
+}
+
+impl Timer {
+    pub fn new(duration: Duration) -> Timer {
+        Timer {
+            duration,
+            start_time: Instant::now(),
+            deadline: None,
+        }
+    }
+
+    /// Countdown that also enforces a hard deadline: reset()
+    /// restarts the countdown as usual, but remaining() never
+    /// extends past the deadline, so total wall time stays bounded
+    /// no matter how often the countdown restarts.
+    pub fn new_with_deadline(duration: Duration, deadline: Instant) -> Timer {
+        Timer {
+            duration,
+            start_time: Instant::now(),
+            deadline: Some(deadline),
+        }
+    }
+
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Time remaining on the countdown, or zero.
+    pub fn remaining(&self) -> Duration {
+        let remaining = self.duration.saturating_sub(self.start_time.elapsed());
+
+        match self.deadline {
+            Some(d) => std::cmp::min(remaining, d.saturating_duration_since(Instant::now())),
+            None => remaining,
+        }
+    }
+
+    /// True if the timer has run out.
+    pub fn done(&self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    /// Start the countdown over from the original duration.
+    pub fn reset(&mut self) {
+        self.start_time = Instant::now();
+    }
+}
+
+/// Token-bucket rate limiter keyed by origin string.
+///
+/// Each origin holds up to `burst` tokens, refilled continuously at
+/// `per_second` tokens per second.  Taking a token from an empty
+/// bucket fails, leaving the caller to reject or delay the work.
+pub struct RateLimiter {
+    burst: f64,
+    per_second: f64,
+    buckets: HashMap<String, (f64, Instant)>,
+}
+
+impl RateLimiter {
+    pub fn new(burst: u32, per_second: u32) -> RateLimiter {
+        RateLimiter {
+            burst: burst as f64,
+            per_second: per_second as f64,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Takes one token from the origin's bucket, returning false if
+    /// the origin is over its limit.
+    pub fn try_take(&mut self, origin: &str) -> bool {
+        self.try_take_at(origin, Instant::now())
+    }
+
+    fn try_take_at(&mut self, origin: &str, now: Instant) -> bool {
+        if self.buckets.len() >= RATE_LIMIT_MAX_ORIGINS {
+            // Quiet origins will have refilled; forget them.
+            let burst = self.burst;
+            let per_second = self.per_second;
+            self.buckets.retain(|_, (tokens, last)| {
+                *tokens + now.saturating_duration_since(*last).as_secs_f64() * per_second < burst
+            });
+        }
+
+        let (tokens, last) = self
+            .buckets
+            .entry(origin.to_string())
+            .or_insert((self.burst, now));
+
+        let elapsed = now.saturating_duration_since(*last).as_secs_f64();
+        *tokens = (*tokens + elapsed * self.per_second).min(self.burst);
+        *last = now;
+
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_number() {
+        let n = random_number(16);
+        assert_eq!(n.len(), 16);
+        assert!(n.chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn test_timer() {
+        let t = Timer::new(Duration::from_secs(60));
+        assert!(!t.done());
+        assert!(t.remaining() > Duration::ZERO);
+
+        let t = Timer::new(Duration::ZERO);
+        assert!(t.done());
+        assert_eq!(t.remaining(), Duration::ZERO);
+
+        // A passed deadline trumps the countdown, even after reset.
+        let mut t = Timer::new_with_deadline(Duration::from_secs(60), Instant::now());
+        assert!(t.done());
+        t.reset();
+        assert!(t.done());
+
+        let t = Timer::new_with_deadline(Duration::from_secs(60), Instant::now() + Duration::from_secs(60));
+        assert!(!t.done());
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let mut rl = RateLimiter::new(2, 10);
+        let now = Instant::now();
+
+        // The burst empties, then takes fail.
+        assert!(rl.try_take_at("domain-a", now));
+        assert!(rl.try_take_at("domain-a", now));
+        assert!(!rl.try_take_at("domain-a", now));
+
+        // Other origins have their own buckets.
+        assert!(rl.try_take_at("domain-b", now));
+
+        // At 10 tokens/sec, 100ms buys one token back.
+        assert!(rl.try_take_at("domain-a", now + Duration::from_millis(100)));
+        assert!(!rl.try_take_at("domain-a", now + Duration::from_millis(100)));
+    }
+}
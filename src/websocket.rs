@@ -0,0 +1,217 @@
+//! WebSocket transport envelope and transport-free session logic.
+//!
+//! This is the piece of the client that browser and Tauri
+//! applications use when compiled to wasm32.  No socket I/O happens
+//! here -- the host application owns the WebSocket and shuttles
+//! strings in and out -- so the same request/response logic works
+//! against any WebSocket implementation, including the legacy
+//! OpenSRF websocket translator.
+use super::message::{Message, MessageStatus, MessageType, Payload};
+use super::util;
+use json::JsonValue;
+
+/// The wire envelope used by the OpenSRF websocket gateway:
+/// {"service": ..., "thread": ..., "osrf_msg": [...]}.
+#[derive(Debug, Clone)]
+pub struct WebSocketEnvelope {
+    service: String,
+    thread: String,
+    body: Vec<Message>,
+}
+
+impl WebSocketEnvelope {
+    pub fn new(service: &str, thread: &str) -> WebSocketEnvelope {
+        WebSocketEnvelope {
+            service: service.to_string(),
+            thread: thread.to_string(),
+            body: Vec::new(),
+        }
+    }
+
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    pub fn thread(&self) -> &str {
+        &self.thread
+    }
+
+    pub fn body(&self) -> &Vec<Message> {
+        &self.body
+    }
+
+    pub fn body_mut(&mut self) -> &mut Vec<Message> {
+        &mut self.body
+    }
+
+    pub fn from_json_value(mut json_obj: JsonValue) -> Option<WebSocketEnvelope> {
+        let service = json_obj["service"].as_str().unwrap_or("").to_string();
+        let thread = json_obj["thread"].as_str()?.to_string();
+
+        let mut envelope = WebSocketEnvelope {
+            service,
+            thread,
+            body: Vec::new(),
+        };
+
+        if let JsonValue::Array(arr) = json_obj["osrf_msg"].take() {
+            for body in arr {
+                if let Some(msg) = Message::from_json_value(body) {
+                    envelope.body.push(msg);
+                }
+            }
+        }
+
+        Some(envelope)
+    }
+
+    pub fn to_json_value(&self) -> JsonValue {
+        json::object! {
+            service: self.service(),
+            thread: self.thread(),
+            osrf_msg: self.body().iter().map(|m| m.to_json_value()).collect::<Vec<JsonValue>>(),
+        }
+    }
+}
+
+/// One response event produced by digesting an inbound envelope.
+#[derive(Debug)]
+pub enum WebSocketEvent {
+    /// A response value for the request with this thread trace.
+    Value(usize, JsonValue),
+
+    /// The request with this thread trace is complete.
+    Complete(usize),
+
+    /// The request with this thread trace failed.
+    Error(usize, String),
+}
+
+/// Transport-free client session logic for WebSocket use.
+///
+/// Produces outbound envelope strings and digests inbound envelope
+/// strings into response events; the caller owns the socket.
+pub struct WebSocketSession {
+    service: String,
+    thread: String,
+    last_thread_trace: usize,
+}
+
+impl WebSocketSession {
+    pub fn new(service: &str) -> WebSocketSession {
+        WebSocketSession {
+            service: service.to_string(),
+            thread: util::random_number(16),
+            last_thread_trace: 0,
+        }
+    }
+
+    pub fn service(&self) -> &str {
+        &self.service
+    }
+
+    pub fn thread(&self) -> &str {
+        &self.thread
+    }
+
+    /// Builds a request envelope for the caller to transmit,
+    /// returning the new request's thread trace along with the
+    /// JSON string to send.
+    pub fn request(&mut self, method: &str, params: Vec<JsonValue>) -> (usize, String) {
+        self.last_thread_trace += 1;
+        let trace = self.last_thread_trace;
+
+        let payload = Payload::Method(super::message::Method::new(method, params));
+        let msg = Message::new(MessageType::Request, trace, payload);
+
+        let mut envelope = WebSocketEnvelope::new(&self.service, &self.thread);
+        envelope.body_mut().push(msg);
+
+        (trace, envelope.to_json_value().dump())
+    }
+
+    /// Digests one inbound envelope string into response events.
+    ///
+    /// Envelopes for other session threads are ignored and reported
+    /// as an empty event list.
+    pub fn handle_text(&mut self, text: &str) -> Result<Vec<WebSocketEvent>, String> {
+        let json_val =
+            json::parse(text).map_err(|e| format!("Cannot parse websocket message: {e}"))?;
+
+        let mut envelope = WebSocketEnvelope::from_json_value(json_val)
+            .ok_or_else(|| format!("Malformed websocket envelope: {text}"))?;
+
+        let mut events = Vec::new();
+
+        if envelope.thread() != self.thread {
+            return Ok(events);
+        }
+
+        for mut msg in envelope.body_mut().drain(..) {
+            let trace = msg.thread_trace();
+
+            match msg.take_payload() {
+                Payload::Result(mut res) => {
+                    events.push(WebSocketEvent::Value(trace, res.take_content()));
+                }
+                Payload::Status(stat) => match stat.status() {
+                    MessageStatus::Complete => events.push(WebSocketEvent::Complete(trace)),
+                    MessageStatus::Continue | MessageStatus::Ok => {}
+                    _ => events.push(WebSocketEvent::Error(trace, stat.to_string())),
+                },
+                _ => {}
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_round_trip() {
+        let mut ses = WebSocketSession::new("opensrf.rsdemo");
+
+        let (trace, text) = ses.request("opensrf.rsdemo.echo", vec![json::from("hi")]);
+        assert_eq!(trace, 1);
+
+        let envelope =
+            WebSocketEnvelope::from_json_value(json::parse(&text).unwrap()).unwrap();
+        assert_eq!(envelope.service(), "opensrf.rsdemo");
+        assert_eq!(envelope.thread(), ses.thread());
+        assert_eq!(envelope.body().len(), 1);
+    }
+
+    #[test]
+    fn test_handle_response() {
+        let mut ses = WebSocketSession::new("opensrf.rsdemo");
+        let (trace, _text) = ses.request("opensrf.rsdemo.echo", vec![json::from("hi")]);
+
+        let mut envelope = WebSocketEnvelope::new("opensrf.rsdemo", ses.thread());
+
+        let result =
+            super::super::message::Result::new(MessageStatus::Ok, "OK", json::from("hi"));
+        envelope
+            .body_mut()
+            .push(Message::new(MessageType::Result, trace, Payload::Result(result)));
+
+        let status = super::super::message::Status::new(MessageStatus::Complete, "Complete");
+        envelope
+            .body_mut()
+            .push(Message::new(MessageType::Status, trace, Payload::Status(status)));
+
+        let events = ses.handle_text(&envelope.to_json_value().dump()).unwrap();
+        assert_eq!(events.len(), 2);
+
+        assert!(matches!(&events[0], WebSocketEvent::Value(t, v) if *t == trace && *v == "hi"));
+        assert!(matches!(&events[1], WebSocketEvent::Complete(t) if *t == trace));
+
+        // Mismatched threads are ignored.
+        let other = WebSocketEnvelope::new("opensrf.rsdemo", "another-thread");
+        let events = ses.handle_text(&other.to_json_value().dump()).unwrap();
+        assert!(events.is_empty());
+    }
+}
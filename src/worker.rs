@@ -0,0 +1,1031 @@
+use super::addr;
+use super::addr::{ClientAddress, ServiceAddress};
+use super::app;
+use super::bus::Bus;
+use super::client::Client;
+use super::conf;
+use super::logging::Logger;
+use super::message;
+use super::message::Message;
+use super::message::MessageStatus;
+use super::message::MessageType;
+use super::message::Payload;
+use super::message::TransportMessage;
+use super::method;
+use super::server::ServerStats;
+use super::session::ServerSession;
+use super::util;
+use log::{debug, error, info, trace, warn};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often an idle, non-connected worker wakes to check for
+/// shutdown signals, etc.
+const IDLE_WAKE_TIME: Duration = Duration::from_secs(5);
+
+/// How long a request's idempotency key (and its cached responses)
+/// stays in the dedup cache.
+const IDEMPOTENCY_TTL: Duration = Duration::from_secs(60);
+
+/// How often a watchdog thread checks for its stop signal while
+/// waiting out a method's runtime limit.
+const WATCHDOG_POLL: Duration = Duration::from_millis(250);
+
+/// Watches one handler invocation; see Worker::start_watchdog().
+///
+/// Dropping the guard stops the watchdog.  If the limit fired
+/// first, the caller has already been sent a Timeout status and
+/// fired() returns true.
+struct WatchdogGuard {
+    stop: Arc<AtomicBool>,
+    fired: Arc<AtomicBool>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl WatchdogGuard {
+    fn fired(&self) -> bool {
+        self.fired.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for WatchdogGuard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+/// Pins the calling thread to a single CPU.
+#[cfg(target_os = "linux")]
+fn pin_to_cpu(cpu: usize) -> Result<(), String> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_ZERO(&mut set);
+        libc::CPU_SET(cpu, &mut set);
+
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) != 0 {
+            return Err(format!(
+                "sched_setaffinity failed: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin_to_cpu(_cpu: usize) -> Result<(), String> {
+    Err("CPU pinning is only supported on Linux".to_string())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerState {
+    Idle,
+    Active,
+    Done,
+}
+
+/// Notification sent to the server thread when our state changes.
+#[derive(Debug)]
+pub struct WorkerStateEvent {
+    worker_id: u64,
+    state: WorkerState,
+}
+
+impl WorkerStateEvent {
+    pub fn worker_id(&self) -> u64 {
+        self.worker_id
+    }
+
+    pub fn state(&self) -> WorkerState {
+        self.state
+    }
+}
+
+/// A single worker thread for a service.
+///
+/// Listens for and processes requests, maintaining at most one
+/// server session at a time.
+pub struct Worker {
+    service: String,
+
+    worker_id: u64,
+
+    config: Arc<conf::Config>,
+
+    service_options: conf::ServiceOptions,
+
+    client: Client,
+
+    methods: Arc<HashMap<String, method::Method>>,
+
+    /// Set by the server thread when it's time to shut down.
+    stopping: Arc<AtomicBool>,
+
+    /// Set by the server thread when a drain is in progress; finish
+    /// the current conversation and exit.
+    draining: Arc<AtomicBool>,
+
+    /// Counters shared with the server for opensrf.system.stats.
+    stats: Arc<ServerStats>,
+
+    /// Per-origin token buckets shared with every other worker,
+    /// when the service configures rate limiting.
+    rate_limiter: Option<Arc<Mutex<util::RateLimiter>>>,
+
+    /// True if we're part of a stateful (CONNECT'ed) conversation.
+    connected: bool,
+
+    /// Number of requests we have serviced so far.
+    requests: usize,
+
+    /// True once a handler has panicked; this worker exits after
+    /// the current conversation so a fresh one replaces it.
+    recycle: bool,
+
+    /// State of the conversation currently in flight.
+    session: Option<ServerSession>,
+
+    /// Recently seen idempotency keys and the responses sent for
+    /// them, for duplicate request detection.
+    idempotency_cache: HashMap<String, (Instant, Vec<json::JsonValue>)>,
+
+    to_parent_tx: mpsc::Sender<WorkerStateEvent>,
+}
+
+impl fmt::Display for Worker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Worker ({}) [{}]", self.service, self.worker_id)
+    }
+}
+
+impl Worker {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        service: String,
+        worker_id: u64,
+        config: Arc<conf::Config>,
+        methods: Arc<HashMap<String, method::Method>>,
+        stopping: Arc<AtomicBool>,
+        draining: Arc<AtomicBool>,
+        stats: Arc<ServerStats>,
+        rate_limiter: Option<Arc<Mutex<util::RateLimiter>>>,
+        to_parent_tx: mpsc::Sender<WorkerStateEvent>,
+    ) -> Result<Worker, String> {
+        let client = Client::connect(config.clone())?;
+
+        let service_options = config
+            .service_options(&service)
+            .cloned()
+            .unwrap_or_default();
+
+        Ok(Worker {
+            service,
+            worker_id,
+            config,
+            service_options,
+            client,
+            methods,
+            stopping,
+            draining,
+            stats,
+            rate_limiter,
+            connected: false,
+            requests: 0,
+            recycle: false,
+            session: None,
+            idempotency_cache: HashMap::new(),
+            to_parent_tx,
+        })
+    }
+
+    pub fn worker_id(&self) -> u64 {
+        self.worker_id
+    }
+
+    /// Name of the control stream for one worker of a service.
+    ///
+    /// The server and admin tooling write commands here to manage
+    /// individual workers without signaling the whole process.
+    pub fn control_stream(service: &str, worker_id: u64) -> String {
+        format!("{}:control:{service}:{worker_id}", addr::bus_namespace())
+    }
+
+    pub fn config(&self) -> &Arc<conf::Config> {
+        &self.config
+    }
+
+    fn session(&mut self) -> &mut ServerSession {
+        self.session.as_mut().unwrap()
+    }
+
+    /// Tell the server thread how we're doing.
+    fn notify_state(&self, state: WorkerState) {
+        trace!("{self} notifying parent of state change => {state:?}");
+
+        if let Err(e) = self.to_parent_tx.send(WorkerStateEvent {
+            worker_id: self.worker_id,
+            state,
+        }) {
+            // If we can't reach the server, this worker is on its own.
+            error!("{self} could not notify parent: {e}");
+        }
+    }
+
+    /// Main worker loop.
+    ///
+    /// Exits once max_requests is reached, the stopping flag is set,
+    /// or an unrecoverable error occurs.
+    pub fn listen(&mut self, factory: app::ApplicationWorkerFactory) {
+        let mut app_worker = (factory)();
+
+        let methods: Arc<Vec<method::Method>> =
+            Arc::new(self.methods.values().cloned().collect());
+
+        if let Err(e) =
+            app_worker.absorb_env(self.client.clone(), self.config.clone(), methods)
+        {
+            error!("{self} failed to absorb environment: {e}");
+            self.notify_state(WorkerState::Done);
+            return;
+        }
+
+        // Optionally pin this worker to one of the service's
+        // configured CPUs, round-robin by worker id.
+        let cpus = self.service_options.cpus();
+        if !cpus.is_empty() {
+            let cpu = cpus[self.worker_id as usize % cpus.len()];
+
+            match pin_to_cpu(cpu) {
+                Ok(()) => debug!("{self} pinned to cpu {cpu}"),
+                Err(e) => error!("{self} cannot pin to cpu {cpu}: {e}"),
+            }
+        }
+
+        if let Err(e) = app_worker.worker_start() {
+            error!("{self} worker_start failed: {e}");
+            self.notify_state(WorkerState::Done);
+            return;
+        }
+
+        let service_addr = ServiceAddress::new(&self.service).full().to_string();
+        let priority_addr = ServiceAddress::new_priority(&self.service).full().to_string();
+
+        // All workers of a service share the service-level streams
+        // via their consumer groups.
+        for stream in [&service_addr, &priority_addr] {
+            if let Err(e) = self
+                .client
+                .singleton()
+                .borrow_mut()
+                .bus_mut()
+                .setup_stream(Some(stream))
+            {
+                error!("{self} cannot setup service stream: {e}");
+                self.notify_state(WorkerState::Done);
+                return;
+            }
+        }
+
+        let control_stream = Worker::control_stream(&self.service, self.worker_id);
+
+        if let Err(e) = self
+            .client
+            .singleton()
+            .borrow_mut()
+            .bus_mut()
+            .setup_stream(Some(&control_stream))
+        {
+            error!("{self} cannot setup control stream: {e}");
+            self.notify_state(WorkerState::Done);
+            return;
+        }
+
+        let worker_addr = self.client.address().full().to_string();
+
+        // Identify ourselves to consumer groups so operators can
+        // attribute per-consumer lag to a specific worker.
+        self.client
+            .singleton()
+            .borrow_mut()
+            .bus_mut()
+            .set_consumer_name(&format!(
+                "{}:{}:{}",
+                self.service,
+                self.worker_id,
+                std::process::id()
+            ));
+
+        let max_requests = self.service_options.max_requests();
+        let keepalive = Duration::from_secs(self.service_options.keepalive().max(0) as u64);
+        let reliable = self.service_options.reliable();
+
+        if reliable {
+            self.client
+                .singleton()
+                .borrow_mut()
+                .bus_mut()
+                .set_reliable(true);
+        }
+
+        while self.requests < max_requests {
+            if self.stopping.load(Ordering::Relaxed) {
+                info!("{self} received stop signal");
+                break;
+            }
+
+            if self.draining.load(Ordering::Relaxed) && !self.connected {
+                info!("{self} draining; exiting");
+                break;
+            }
+
+            if self.recycle {
+                info!("{self} recycling after handler panic");
+                break;
+            }
+
+            match self.check_control_stream(&control_stream, &mut app_worker) {
+                Ok(true) => {
+                    info!("{self} stopping on control command");
+                    break;
+                }
+                Ok(false) => {}
+                Err(e) => error!("{self} control stream error: {e}"),
+            }
+
+            let recv_op = if self.connected {
+                // We're connected to a client; listen on our unique
+                // address and only wait up to the keepalive window.
+                self.client
+                    .singleton()
+                    .borrow_mut()
+                    .bus_mut()
+                    .recv(keepalive, None)
+            } else {
+                // Watch our unique address -- affinity-routed
+                // requests land there -- alongside the shared
+                // service streams, polling the priority lane ahead
+                // of the main stream so interactive traffic isn't
+                // starved by batch jobs.
+                let multi_op = self
+                    .client
+                    .singleton()
+                    .borrow_mut()
+                    .bus_mut()
+                    .recv_from_any(
+                        IDLE_WAKE_TIME,
+                        &[&worker_addr, &priority_addr, &service_addr],
+                    );
+
+                match multi_op {
+                    Ok(Some((stream, tmsg))) => {
+                        trace!("{self} received message via stream={stream}");
+                        Ok(Some(tmsg))
+                    }
+                    Ok(None) => Ok(None),
+                    Err(e) => Err(e),
+                }
+            };
+
+            match recv_op {
+                Ok(Some(tmsg)) => {
+                    match self.handle_transport_message(tmsg, &mut app_worker) {
+                        Ok(()) => {
+                            // In reliable mode, unacked messages stay
+                            // pending for reclamation; only a fully
+                            // handled message is acked.
+                            if reliable {
+                                if let Err(e) = self
+                                    .client
+                                    .singleton()
+                                    .borrow_mut()
+                                    .bus_mut()
+                                    .ack_last()
+                                {
+                                    error!("{self} ack error: {e}");
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            error!("{self} error handling message: {e}");
+                            self.reset(&mut app_worker);
+                        }
+                    }
+                }
+                Ok(None) => {
+                    if reliable && !self.connected {
+                        // Idle; pick up anything a dead worker left
+                        // pending past the idle window.
+                        let reclaim_op = self
+                            .client
+                            .singleton()
+                            .borrow_mut()
+                            .bus_mut()
+                            .reclaim_pending(&service_addr, IDLE_WAKE_TIME.as_millis() as usize);
+
+                        if let Err(e) = reclaim_op {
+                            error!("{self} reclaim error: {e}");
+                        }
+                    }
+
+                    if self.connected {
+                        // Client went quiet past the keepalive window.
+                        debug!("{self} keepalive timeout");
+                        self.session().send_status(
+                            MessageStatus::Timeout,
+                            "Timeout waiting on request",
+                        ).ok();
+                        self.reset(&mut app_worker);
+                    }
+                }
+                Err(e) => {
+                    error!("{self} recv error: {e}");
+                }
+            }
+        }
+
+        if let Err(e) = app_worker.worker_end() {
+            error!("{self} worker_end failed: {e}");
+        }
+
+        // Re-route anything still queued on our personal stream to
+        // the shared service stream so another worker can claim it.
+        let drain_op = self
+            .client
+            .singleton()
+            .borrow_mut()
+            .bus_mut()
+            .drain(Some(&service_addr));
+
+        if let Err(e) = drain_op {
+            error!("{self} drain error: {e}");
+        }
+
+        // The control stream is ours alone and worker ids are never
+        // reused; remove it so recycled workers don't leave streams
+        // piling up in Redis.
+        let delete_op = self
+            .client
+            .singleton()
+            .borrow_mut()
+            .bus_mut()
+            .delete_stream(Some(&control_stream));
+
+        if let Err(e) = delete_op {
+            error!("{self} control stream delete error: {e}");
+        }
+
+        self.notify_state(WorkerState::Done);
+    }
+
+    /// Clears session state after a conversation completes or
+    /// fails, giving the application its end_session() callback.
+    fn reset(&mut self, app_worker: &mut Box<dyn app::ApplicationWorker>) {
+        if self.session.is_some() {
+            if let Err(e) = app_worker.end_session() {
+                error!("{self} end_session failed: {e}");
+            }
+        }
+
+        self.connected = false;
+        self.session = None;
+        self.notify_state(WorkerState::Idle);
+    }
+
+    fn handle_transport_message(
+        &mut self,
+        mut tmsg: TransportMessage,
+        app_worker: &mut Box<dyn app::ApplicationWorker>,
+    ) -> Result<(), String> {
+        if self.session.is_none() || self.session().thread() != tmsg.thread() {
+            trace!("{self} creating new session for thread {}", tmsg.thread());
+
+            // A session replaced mid-stream is over as far as the
+            // application is concerned.
+            if self.session.is_some() {
+                if let Err(e) = app_worker.end_session() {
+                    error!("{self} end_session failed: {e}");
+                }
+            }
+
+            let sender = ClientAddress::from_string(tmsg.from())?;
+
+            self.session = Some(ServerSession::new(
+                self.client.clone(),
+                tmsg.thread(),
+                sender,
+            ));
+
+            app_worker.start_session()?;
+        }
+
+        // Adopt the sender's correlation id -- minting one for
+        // older clients that don't send one -- so our log lines,
+        // replies, and subrequests can all be traced back to the
+        // originating action.
+        let xid = match tmsg.osrf_xid() {
+            Some(xid) => xid.to_string(),
+            None => Logger::mk_xid(),
+        };
+
+        Logger::set_xid(&xid);
+        let xid_guard = Logger::push_context("xid", &xid);
+
+        let mut result = Ok(());
+
+        for msg in tmsg.body_mut().drain(..) {
+            result = self.handle_message(msg, app_worker);
+
+            if result.is_err() {
+                break;
+            }
+        }
+
+        drop(xid_guard);
+        Logger::clear_xid();
+
+        result
+    }
+
+    fn handle_message(
+        &mut self,
+        mut msg: Message,
+        app_worker: &mut Box<dyn app::ApplicationWorker>,
+    ) -> Result<(), String> {
+        self.session().set_last_thread_trace(msg.thread_trace());
+
+        trace!("{self} received message of type {}", msg.mtype());
+
+        match msg.mtype() {
+            MessageType::Connect => {
+                self.notify_state(WorkerState::Active);
+                self.connected = true;
+                self.session().set_connected(true);
+                self.session().send_status(MessageStatus::Ok, "OK")
+            }
+
+            MessageType::Disconnect => {
+                debug!("{self} client disconnected");
+                self.reset(app_worker);
+                Ok(())
+            }
+
+            MessageType::Request => {
+                self.notify_state(WorkerState::Active);
+
+                let idempotency_key = msg.idempotency_key().map(|k| k.to_string());
+
+                // Fire-and-forget requests get no responses, not
+                // even a Complete.
+                self.session().set_no_reply(msg.no_reply());
+
+                // Caller context for handlers implementing
+                // per-origin policies or audit logging.
+                let locale = msg.locale().map(str::to_string);
+                let ingress = msg.ingress().to_string();
+                self.session().set_locale(locale.as_deref());
+                self.session().set_ingress(Some(&ingress));
+
+                let result = match msg.take_payload() {
+                    Payload::Method(m) => self.handle_request(m, idempotency_key, app_worker),
+                    _ => Err(format!("{self} request has no method payload")),
+                };
+
+                self.requests += 1;
+
+                // Any log context the handler attached ends with its
+                // request.
+                Logger::clear_context();
+
+                if !self.connected {
+                    // Stateless request complete; ready for the next one.
+                    if self.session.is_some() {
+                        if let Err(e) = app_worker.end_session() {
+                            error!("{self} end_session failed: {e}");
+                        }
+                    }
+
+                    self.session = None;
+                    self.notify_state(WorkerState::Idle);
+                }
+
+                result
+            }
+
+            MessageType::Status => {
+                // Client-side keepalive; its arrival alone holds
+                // the connected session open for another window.
+                trace!("{self} received keepalive");
+                Ok(())
+            }
+
+            _ => Err(format!("{self} unexpected message type: {}", msg.mtype())),
+        }
+    }
+
+    fn handle_request(
+        &mut self,
+        msg_method: message::Method,
+        idempotency_key: Option<String>,
+        app_worker: &mut Box<dyn app::ApplicationWorker>,
+    ) -> Result<(), String> {
+        let method_name = msg_method.method().to_string();
+
+        // The ".atomic" suffix asks for every response aggregated
+        // into one array; dispatch uses the bare name.
+        let (method_name, atomic) = match method_name.strip_suffix(".atomic") {
+            Some(name) => (name.to_string(), true),
+            None => (method_name, false),
+        };
+
+        self.session().set_atomic(atomic);
+
+        // An origin that has outrun its token bucket hears about it
+        // instead of silently queuing behind everyone else.
+        if let Some(limiter) = self.rate_limiter.clone() {
+            let origin = self.session().sender().domain().to_string();
+
+            let allowed = match limiter.lock() {
+                Ok(mut limiter) => limiter.try_take(&origin),
+                Err(_) => true, // Poisoned lock; don't block traffic.
+            };
+
+            if !allowed {
+                warn!("{self} rate limit exceeded for {origin}");
+
+                return self.session().send_status(
+                    MessageStatus::ServiceUnavailable,
+                    &format!("Rate limit exceeded for {origin}"),
+                );
+            }
+        }
+
+        if let Some(key) = idempotency_key.as_deref() {
+            self.prune_idempotency_cache();
+
+            if let Some((_, responses)) = self.idempotency_cache.get(key) {
+                debug!("{self} replaying cached responses for idempotency key {key}");
+
+                let responses = responses.clone();
+                let session = self.session();
+
+                for value in responses {
+                    session.respond(value)?;
+                }
+
+                return session.send_complete();
+            }
+        }
+
+        let method = match self.methods.get(&method_name) {
+            Some(m) => m.clone(),
+            None => {
+                return self.session().send_status(
+                    MessageStatus::MethodNotFound,
+                    &format!("Method not found: {method_name}"),
+                );
+            }
+        };
+
+        self.stats.record_call(&method_name);
+
+        let param_count = msg_method.params().len() as u8;
+
+        if !method::ParamCount::matches(method.param_count(), param_count) {
+            return self.session().send_status(
+                MessageStatus::BadRequest,
+                &format!(
+                    "Invalid param count for {method_name}: wanted {} got {param_count}",
+                    method.param_count()
+                ),
+            );
+        }
+
+        if method_name.starts_with("opensrf.system.") {
+            return self.handle_system_method(&method_name, &msg_method);
+        }
+
+        if self.log_method_call(&method_name) {
+            info!("{self} CALL: {method_name} {}", {
+                let params = msg_method.params();
+                params
+                    .iter()
+                    .map(|p| p.dump())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            });
+        }
+
+        let mut session = self.session.take().unwrap();
+
+        session.set_record_responses(idempotency_key.is_some());
+
+        // Arm the watchdog when this method, or the service's
+        // max-runtime setting, caps handler runtime.  The limit
+        // doubles as a deadline for the handler's subrequests.
+        let max_runtime = method.max_runtime().or_else(|| {
+            match self.service_options.max_runtime() {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            }
+        });
+
+        let watchdog = max_runtime.map(|limit| {
+            session.set_deadline(Some(Instant::now() + limit));
+            self.start_watchdog(&session, &method_name, limit)
+        });
+
+        // Contain handler panics: the caller hears about the
+        // failure instead of waiting out a timeout, and this
+        // worker recycles cleanly instead of dying mid-request.
+        let result = match app_worker.before_request() {
+            Ok(()) => std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                (method.handler())(app_worker, &mut session, &msg_method)
+            })),
+            Err(e) => Ok(Err(format!("before_request failed: {e}"))),
+        };
+
+        self.session = Some(session);
+
+        if let Err(e) = app_worker.after_request() {
+            error!("{self} after_request failed: {e}");
+        }
+
+        let result = match result {
+            Ok(result) => result,
+            Err(panic) => {
+                let payload = if let Some(s) = panic.downcast_ref::<&str>() {
+                    s.to_string()
+                } else if let Some(s) = panic.downcast_ref::<String>() {
+                    s.clone()
+                } else {
+                    String::from("non-string panic payload")
+                };
+
+                error!("{self} method {method_name} panicked: {payload}");
+
+                self.session()
+                    .send_status(
+                        MessageStatus::InternalServerError,
+                        &format!("Internal Server Error: {method_name}"),
+                    )
+                    .ok();
+
+                self.recycle = true;
+                self.reset(app_worker);
+
+                return Err(format!("{self} method {method_name} panicked"));
+            }
+        };
+
+        self.session().set_deadline(None);
+
+        if let Some(guard) = watchdog {
+            if guard.fired() {
+                // The watchdog already sent the caller a Timeout;
+                // recycle rather than trust whatever state the
+                // overrunning handler left behind.
+                drop(guard);
+                self.recycle = true;
+                self.reset(app_worker);
+
+                return Err(format!(
+                    "{self} method {method_name} exceeded its runtime limit"
+                ));
+            }
+        }
+
+        if let Err(err) = result {
+            error!("{self} method {method_name} exited: {err}");
+
+            // The full error is logged above; the caller gets a
+            // sanitized status, with the detail included only when
+            // this worker runs with debug logging.
+            let label = if log::log_enabled!(log::Level::Debug) {
+                format!("osrfMethodException : {method_name}: {err}")
+            } else {
+                format!("osrfMethodException : {method_name} failed")
+            };
+
+            self.session()
+                .send_status(MessageStatus::InternalServerError, &label)
+                .ok();
+
+            self.reset(app_worker);
+            return Err(err);
+        }
+
+        if let Some(key) = idempotency_key {
+            let responses = self.session().take_recorded_responses();
+            self.idempotency_cache
+                .insert(key, (Instant::now(), responses));
+        }
+
+        self.session().send_complete()
+    }
+
+    /// Spawns a watchdog for one handler invocation.
+    ///
+    /// If the handler outlives the limit, the watchdog logs the
+    /// overrun and tells the caller via a Timeout status on its
+    /// own bus connection -- the worker thread is wedged in the
+    /// handler and cannot reply itself.
+    fn start_watchdog(
+        &self,
+        session: &ServerSession,
+        method_name: &str,
+        limit: Duration,
+    ) -> WatchdogGuard {
+        let remote_addr = session.sender().full().to_string();
+        let thread = session.thread().to_string();
+        let trace = session.last_thread_trace();
+        let config = self.config.clone();
+        let label = format!("{self} method {method_name}");
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let fired = Arc::new(AtomicBool::new(false));
+        let thread_stop = stop.clone();
+        let thread_fired = fired.clone();
+
+        let handle = std::thread::spawn(move || {
+            let started = Instant::now();
+
+            while started.elapsed() < limit {
+                if thread_stop.load(Ordering::Relaxed) {
+                    return;
+                }
+
+                std::thread::sleep(std::cmp::min(
+                    WATCHDOG_POLL,
+                    limit.saturating_sub(started.elapsed()),
+                ));
+            }
+
+            thread_fired.store(true, Ordering::Relaxed);
+
+            error!("{label} exceeded its {limit:?} runtime limit");
+
+            let con = match config.primary_connection() {
+                Some(c) => c,
+                None => return,
+            };
+
+            let mut bus = match Bus::new(con) {
+                Ok(b) => b,
+                Err(e) => return error!("Watchdog cannot connect to bus: {e}"),
+            };
+
+            let status = Payload::Status(message::Status::new(
+                MessageStatus::Timeout,
+                "Method runtime exceeded",
+            ));
+
+            let tmsg = TransportMessage::with_body(
+                &remote_addr,
+                bus.address().full(),
+                &thread,
+                Message::new(MessageType::Status, trace, status),
+            );
+
+            if let Err(e) = bus.send_to(&tmsg, &remote_addr) {
+                error!("Watchdog cannot notify caller: {e}");
+            }
+        });
+
+        WatchdogGuard {
+            stop,
+            fired,
+            handle: Some(handle),
+        }
+    }
+
+    /// Performs a non-blocking check of our control stream and
+    /// applies any pending command.
+    ///
+    /// Returns true if this worker should stop once the current
+    /// request is finished.
+    fn check_control_stream(
+        &mut self,
+        stream: &str,
+        app_worker: &mut Box<dyn app::ApplicationWorker>,
+    ) -> Result<bool, String> {
+        let tmsg = match self
+            .client
+            .singleton()
+            .borrow_mut()
+            .bus_mut()
+            .recv(Duration::ZERO, Some(stream))?
+        {
+            Some(t) => t,
+            None => return Ok(false),
+        };
+
+        let command = tmsg.router_command().unwrap_or("");
+
+        info!("{self} received control command: {command}");
+
+        match command {
+            "stop" => return Ok(true),
+
+            "dump-state" => {
+                info!(
+                    "{self} state: connected={} requests={} session={}",
+                    self.connected,
+                    self.requests,
+                    match self.session.as_ref() {
+                        Some(s) => s.thread(),
+                        None => "none",
+                    }
+                );
+            }
+
+            "reload-env" => {
+                if let Err(e) = app_worker.worker_end() {
+                    error!("{self} worker_end failed during reload: {e}");
+                }
+
+                if let Err(e) = app_worker.worker_start() {
+                    error!("{self} worker_start failed during reload: {e}");
+                }
+            }
+
+            _ => error!("{self} unknown control command: {command}"),
+        }
+
+        Ok(false)
+    }
+
+    /// Services the built-in opensrf.system.* methods, which are
+    /// registered by the server rather than the application.
+    fn handle_system_method(
+        &mut self,
+        method_name: &str,
+        msg_method: &message::Method,
+    ) -> Result<(), String> {
+        match method_name {
+            "opensrf.system.echo" => {
+                // Echoes the params back; doubles as a lightweight
+                // liveness probe, e.g. for pooled sessions.
+                let params = msg_method.params().clone();
+                let session = self.session();
+
+                for value in params {
+                    session.respond(value)?;
+                }
+
+                session.send_complete()
+            }
+
+            "opensrf.system.stats" => {
+                let backlog = self
+                    .client
+                    .singleton()
+                    .borrow_mut()
+                    .bus_mut()
+                    .xlen(ServiceAddress::new(&self.service).full())?;
+
+                let value = self.stats.to_json_value(backlog);
+
+                self.session().respond_complete(value)
+            }
+
+            "opensrf.system.status" => {
+                let backlog = self
+                    .client
+                    .singleton()
+                    .borrow_mut()
+                    .bus_mut()
+                    .xlen(ServiceAddress::new(&self.service).full())?;
+
+                let value = self.stats.status_json(&self.service, backlog);
+
+                self.session().respond_complete(value)
+            }
+
+            _ => Err(format!("{self} unknown system method: {method_name}")),
+        }
+    }
+
+    /// Drops idempotency cache entries past their TTL.
+    fn prune_idempotency_cache(&mut self) {
+        self.idempotency_cache
+            .retain(|_, (seen, _)| seen.elapsed() < IDEMPOTENCY_TTL);
+    }
+
+    /// True if this API call may be logged with its parameters.
+    fn log_method_call(&self, method_name: &str) -> bool {
+        !self
+            .config
+            .log_protect()
+            .iter()
+            .any(|m| method_name.starts_with(m))
+    }
+}
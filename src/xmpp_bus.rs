@@ -0,0 +1,299 @@
+//! Legacy XMPP transport for interop with C/Perl OpenSRF.
+//!
+//! Speaks the classic OpenSRF XMPP envelope -- a &lt;message&gt;
+//! stanza with router metadata as attributes, the session thread in
+//! &lt;thread&gt; and the JSON message payload in &lt;body&gt; -- so
+//! a Rust client or worker can talk to ejabberd-based nodes during
+//! a migration.
+//!
+//! Like the C implementation, this speaks just enough XMPP to get
+//! by: a raw TCP stream, pre-SASL "jabber:iq:auth" login, and a
+//! string-scanning stanza reader.  It is not a general XMPP client.
+use super::addr::ClientAddress;
+use super::conf;
+use super::message::TransportMessage;
+use log::{debug, trace};
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// Standard XMPP client port, used when the node config has none.
+pub const DEFAULT_XMPP_PORT: u16 = 5222;
+
+/// Bytes read from the socket per pass while awaiting a stanza.
+const READ_BUFSIZE: usize = 8192;
+
+/// Escapes text for inclusion in XML attribute or element content.
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Reverses escape_xml().
+fn unescape_xml(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&gt;", ">")
+        .replace("&lt;", "<")
+        .replace("&amp;", "&")
+}
+
+/// Manages one legacy XMPP connection for a single bus participant.
+///
+/// Mirrors the API surface of bus::Bus that the client and worker
+/// layers rely on.  The bus address doubles as the JID resource so
+/// legacy nodes can reply directly.
+pub struct XmppBus {
+    stream: TcpStream,
+
+    /// Our unique bus address.
+    address: ClientAddress,
+
+    /// Domain, i.e. XMPP server name, we're connected to.
+    domain: String,
+
+    /// Our full JID: username@domain/resource.
+    jid: String,
+
+    /// Socket bytes read but not yet consumed as a stanza.
+    pending: String,
+}
+
+impl XmppBus {
+    pub fn new(config: &conf::BusConnection) -> Result<Self, String> {
+        let node = config.node();
+        let creds = config.credentials();
+        let domain = node.name().to_string();
+
+        let host = node
+            .addresses()
+            .first()
+            .cloned()
+            .unwrap_or_else(|| domain.clone());
+
+        debug!("Connecting to XMPP server at {host}:{}", node.port());
+
+        let stream = TcpStream::connect((host.as_str(), node.port()))
+            .map_err(|e| format!("Cannot connect to XMPP server {host}: {e}"))?;
+
+        let address = ClientAddress::new(&domain);
+        let jid = format!("{}@{domain}/{}", creds.username(), address.full());
+
+        let mut bus = XmppBus {
+            stream,
+            address,
+            domain,
+            jid,
+            pending: String::new(),
+        };
+
+        bus.login(creds.username(), creds.password())?;
+
+        Ok(bus)
+    }
+
+    pub fn address(&self) -> &ClientAddress {
+        &self.address
+    }
+
+    pub fn set_address(&mut self, address: &ClientAddress) {
+        self.address = address.clone();
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    fn write(&mut self, xml: &str) -> Result<(), String> {
+        trace!("XmppBus sending: {xml}");
+
+        self.stream
+            .write_all(xml.as_bytes())
+            .map_err(|e| format!("XmppBus write error: {e}"))
+    }
+
+    /// Opens the XML stream and authenticates with the pre-SASL
+    /// "jabber:iq:auth" handshake legacy OpenSRF servers allow.
+    fn login(&mut self, username: &str, password: &str) -> Result<(), String> {
+        let domain = self.domain.clone();
+
+        self.write(&format!(
+            r#"<stream:stream to="{domain}" xmlns="jabber:client" xmlns:stream="http://etherx.jabber.org/streams" version="1.0">"#,
+        ))?;
+
+        // The server's stream header; scan past it so the auth
+        // reply is the next thing we parse.
+        self.read_until(">", Duration::MAX)?;
+
+        let resource = escape_xml(self.address.full());
+
+        self.write(&format!(
+            concat!(
+                r#"<iq type="set" id="auth"><query xmlns="jabber:iq:auth">"#,
+                "<username>{}</username><password>{}</password>",
+                "<resource>{}</resource></query></iq>"
+            ),
+            escape_xml(username),
+            escape_xml(password),
+            resource,
+        ))?;
+
+        let reply = self.read_until("</iq>", Duration::MAX)?;
+
+        if !reply.contains(r#"type="result""#) {
+            return Err(format!("XMPP login failed for {}: {reply}", self.jid));
+        }
+
+        debug!("XmppBus logged in as {}", self.jid);
+
+        Ok(())
+    }
+
+    /// Reads from the socket until the pending buffer contains the
+    /// provided marker, returning everything up to and including it.
+    ///
+    /// Same timeout semantics as bus::Bus: zero returns immediately
+    /// if nothing is buffered or readable, Duration::MAX blocks
+    /// indefinitely.
+    fn read_until(&mut self, marker: &str, timeout: Duration) -> Result<String, String> {
+        let duration = if timeout == Duration::MAX {
+            None
+        } else if timeout.is_zero() {
+            Some(Duration::from_millis(10))
+        } else {
+            Some(timeout)
+        };
+
+        self.stream
+            .set_read_timeout(duration)
+            .map_err(|e| format!("XmppBus socket error: {e}"))?;
+
+        let mut buf = [0u8; READ_BUFSIZE];
+
+        loop {
+            if let Some(idx) = self.pending.find(marker) {
+                let end = idx + marker.len();
+                let stanza = self.pending[..end].to_string();
+                self.pending.replace_range(..end, "");
+                return Ok(stanza);
+            }
+
+            let count = match self.stream.read(&mut buf) {
+                Ok(0) => return Err("XmppBus connection closed by server".to_string()),
+                Ok(c) => c,
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => {
+                    return Ok(String::new());
+                }
+                Err(e) => return Err(format!("XmppBus read error: {e}")),
+            };
+
+            match std::str::from_utf8(&buf[..count]) {
+                Ok(s) => self.pending.push_str(s),
+                Err(e) => return Err(format!("XmppBus received non-utf8 data: {e}")),
+            }
+        }
+    }
+
+    /// Pulls one attribute value out of a stanza's opening tag.
+    fn attribute(stanza: &str, name: &str) -> Option<String> {
+        let marker = format!("{name}=\"");
+        let start = stanza.find(&marker)? + marker.len();
+        let end = stanza[start..].find('"')? + start;
+        Some(unescape_xml(&stanza[start..end]))
+    }
+
+    /// Pulls the text content of one child element out of a stanza.
+    fn element_text(stanza: &str, name: &str) -> Option<String> {
+        let open = format!("<{name}>");
+        let close = format!("</{name}>");
+        let start = stanza.find(&open)? + open.len();
+        let end = stanza[start..].find(&close)? + start;
+        Some(unescape_xml(&stanza[start..end]))
+    }
+
+    /// Returns at most one TransportMessage pulled from our JID.
+    ///
+    /// Unlike the Redis bus, XMPP delivers to the connection, so
+    /// there is no stream argument; everything lands here.
+    pub fn recv(&mut self, timeout: Duration) -> Result<Option<TransportMessage>, String> {
+        let stanza = self.read_until("</message>", timeout)?;
+
+        if stanza.is_empty() {
+            return Ok(None);
+        }
+
+        trace!("XmppBus read stanza: {stanza}");
+
+        let body = match XmppBus::element_text(&stanza, "body") {
+            Some(b) => b,
+            None => return Err(format!("XmppBus received message without body: {stanza}")),
+        };
+
+        let from = XmppBus::attribute(&stanza, "from").unwrap_or_default();
+        let thread = XmppBus::element_text(&stanza, "thread").unwrap_or_default();
+
+        let mut wrapper = json::object! {
+            to: self.address.full(),
+            from: from,
+            thread: thread,
+            body: json::parse(&body)
+                .map_err(|e| format!("XmppBus received unparseable JSON: {e} : {body}"))?,
+        };
+
+        if let Some(rc) = XmppBus::attribute(&stanza, "router_command") {
+            wrapper["router_command"] = rc.into();
+        }
+
+        if let Some(rc) = XmppBus::attribute(&stanza, "router_class") {
+            wrapper["router_class"] = rc.into();
+        }
+
+        match TransportMessage::from_json_value(wrapper) {
+            Some(msg) => Ok(Some(msg)),
+            None => Err("XmppBus received malformed TransportMessage".to_string()),
+        }
+    }
+
+    /// Sends a TransportMessage to its "to" address.
+    pub fn send(&mut self, msg: &TransportMessage) -> Result<(), String> {
+        self.send_to(msg, msg.to())
+    }
+
+    /// Sends a TransportMessage to the provided JID, regardless of
+    /// its "to" address.
+    pub fn send_to(&mut self, msg: &TransportMessage, recipient: &str) -> Result<(), String> {
+        let mut attrs = String::new();
+
+        if let Some(rc) = msg.router_command() {
+            attrs.push_str(&format!(r#" router_command="{}""#, escape_xml(rc)));
+        }
+
+        if let Some(rc) = msg.router_class() {
+            attrs.push_str(&format!(r#" router_class="{}""#, escape_xml(rc)));
+        }
+
+        let body = json::stringify(
+            msg.to_json_value()["body"].clone(),
+        );
+
+        self.write(&format!(
+            concat!(
+                r#"<message to="{}" from="{}"{}>"#,
+                "<thread>{}</thread><body>{}</body></message>"
+            ),
+            escape_xml(recipient),
+            escape_xml(&self.jid),
+            attrs,
+            escape_xml(msg.thread()),
+            escape_xml(&body),
+        ))
+    }
+
+    /// Closes the XML stream.
+    pub fn disconnect(&mut self) -> Result<(), String> {
+        self.write("</stream:stream>")
+    }
+}